@@ -0,0 +1,114 @@
+//! Typed and batched account reads.
+
+use std::collections::HashMap;
+
+use anyhow::{ensure, Context, Result};
+use arch_program::pubkey::Pubkey;
+use arch_sdk::AccountInfo;
+use borsh::BorshDeserialize;
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    error::{ArchTestingError, ArchTestingResult},
+    TestContext,
+};
+
+/// Account reads in flight at once in `read_accounts`
+pub const DEFAULT_ACCOUNT_READ_CONCURRENCY: usize = 16;
+
+impl TestContext {
+    /// Read `pubkey` and borsh-deserialize its data as `T`, after checking it's owned by `owner`.
+    ///
+    /// Trailing bytes are allowed, since accounts are often allocated larger than their contents.
+    pub async fn read_account_as<T: BorshDeserialize>(
+        &self,
+        pubkey: Pubkey,
+        owner: &Pubkey,
+    ) -> Result<T> {
+        let data = self.read_owned_account_data(pubkey, owner).await?;
+
+        T::deserialize(&mut data.as_slice()).with_context(|| {
+            format!(
+                "Failed to borsh-deserialize {} ({} bytes) as {}",
+                pubkey,
+                data.len(),
+                std::any::type_name::<T>()
+            )
+        })
+    }
+
+    /// Like `read_account_as`, for accounts whose data is bincode-encoded
+    pub async fn read_account_as_bincode<T: DeserializeOwned>(
+        &self,
+        pubkey: Pubkey,
+        owner: &Pubkey,
+    ) -> Result<T> {
+        let data = self.read_owned_account_data(pubkey, owner).await?;
+
+        bincode::deserialize(&data).with_context(|| {
+            format!(
+                "Failed to bincode-deserialize {} ({} bytes) as {}",
+                pubkey,
+                data.len(),
+                std::any::type_name::<T>()
+            )
+        })
+    }
+
+    /// Read many accounts concurrently (`DEFAULT_ACCOUNT_READ_CONCURRENCY` at a time), keyed by
+    /// pubkey. Fails if any of them can't be read.
+    pub async fn read_accounts(&self, pubkeys: &[Pubkey]) -> Result<HashMap<Pubkey, AccountInfo>> {
+        stream::iter(pubkeys.iter().copied())
+            .map(|pubkey| async move {
+                let account = self
+                    .read_account_info(pubkey)
+                    .await
+                    .with_context(|| format!("Failed to read account {}", pubkey))?;
+                Ok::<_, anyhow::Error>((pubkey, account))
+            })
+            .buffer_unordered(DEFAULT_ACCOUNT_READ_CONCURRENCY)
+            .try_collect()
+            .await
+    }
+
+    /// Read `pubkey`, or `None` if it doesn't exist. Other failures (timeouts, an unreachable
+    /// validator, ...) are errors, so they aren't mistaken for a missing account.
+    pub async fn try_read_account_info(
+        &self,
+        pubkey: Pubkey,
+    ) -> ArchTestingResult<Option<AccountInfo>> {
+        match self.read_account_info(pubkey).await {
+            Ok(account) => Ok(Some(account)),
+            Err(e) if is_account_not_found(&e) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn read_owned_account_data(&self, pubkey: Pubkey, owner: &Pubkey) -> Result<Vec<u8>> {
+        let account = self
+            .read_account_info(pubkey)
+            .await
+            .with_context(|| format!("Failed to read account {}", pubkey))?;
+
+        ensure!(
+            account.owner == *owner,
+            "Account {} is owned by {}, expected {}",
+            pubkey,
+            account.owner,
+            owner
+        );
+        ensure!(!account.data.is_empty(), "Account {} has no data", pubkey);
+
+        Ok(account.data)
+    }
+}
+
+/// Whether a `read_account_info` error says the account doesn't exist, as opposed to the read
+/// failing
+fn is_account_not_found(error: &ArchTestingError) -> bool {
+    match error {
+        ArchTestingError::RpcUnavailable { .. } => false,
+        error => format!("{:#}", error).to_lowercase().contains("not found"),
+    }
+}
@@ -0,0 +1,174 @@
+//! Bitcoin UTXO anchoring for Arch accounts.
+//!
+//! An anchored account is created from a UTXO paying the account's Bitcoin address; the
+//! validator only accepts it once Titan has indexed that UTXO.
+
+use std::str::FromStr;
+
+use anyhow::{ensure, Context, Result};
+use arch_program::{pubkey::Pubkey, sanitized::ArchMessage, system_instruction};
+use arch_sdk::Status;
+use bitcoin::{hashes::Hash, key::Keypair, Address, Amount, OutPoint, Txid};
+
+use crate::TestContext;
+
+/// Value of anchor UTXOs (matches the upstream Arch test suite)
+pub const ANCHOR_UTXO_AMOUNT: Amount = Amount::from_sat(3_000);
+
+/// Confirmations mined on top of a fresh anchor UTXO
+pub const ANCHOR_UTXO_CONFIRMATIONS: u64 = 1;
+
+/// The Bitcoin side of an anchored account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountAnchor {
+    pub outpoint: OutPoint,
+    /// Confirmations of the anchoring transaction (0 while it's in the mempool)
+    pub confirmations: u32,
+}
+
+impl TestContext {
+    /// The Bitcoin address the validator associates with the account `pubkey`
+    pub async fn account_address(&self, pubkey: &Pubkey) -> Result<Address> {
+        let address = self
+            .arch_async_rpc_client
+            .get_account_address(pubkey)
+            .await
+            .with_context(|| format!("Failed to fetch account address of {}", pubkey))?;
+
+        Ok(Address::from_str(&address)?.require_network(self.network)?)
+    }
+
+    /// Create and confirm a UTXO paying the account address of `pubkey`, wait for Titan to index
+    /// it, and return its outpoint (to anchor an account created for `pubkey`)
+    pub async fn send_utxo(&self, pubkey: &Pubkey) -> Result<OutPoint> {
+        let address = self.account_address(pubkey).await?;
+
+        let outpoint = self
+            .send_btc(&address, ANCHOR_UTXO_AMOUNT, ANCHOR_UTXO_CONFIRMATIONS)
+            .await
+            .with_context(|| format!("Failed to send anchor utxo for {}", pubkey))?;
+
+        tracing::debug!("Sent anchor utxo {} for {}", outpoint, pubkey);
+
+        Ok(outpoint)
+    }
+
+    /// End to end: faucet-fund `authority_kp`, create and confirm an anchor UTXO, then create a
+    /// fresh account anchored to it. Returns the account keypair, pubkey and anchor outpoint.
+    pub async fn create_account_with_utxo(
+        &self,
+        authority_kp: Keypair,
+        lamports: u64,
+        space: u64,
+        owner: Pubkey,
+    ) -> Result<(Keypair, Pubkey, OutPoint)> {
+        self.fund_keypair_with_faucet(&authority_kp).await?;
+
+        self.create_anchored_account(authority_kp, lamports, space, owner)
+            .await
+    }
+
+    /// Create a fresh account, paid for by `authority_kp`, anchored to a new UTXO
+    pub(crate) async fn create_anchored_account(
+        &self,
+        authority_kp: Keypair,
+        lamports: u64,
+        space: u64,
+        owner: Pubkey,
+    ) -> Result<(Keypair, Pubkey, OutPoint)> {
+        let (account_keypair, _, _) = self.generate_new_keypair();
+        self.create_anchored_account_for(authority_kp, account_keypair, lamports, space, owner)
+            .await
+    }
+
+    /// `create_anchored_account` for a given account keypair
+    pub(crate) async fn create_anchored_account_for(
+        &self,
+        authority_kp: Keypair,
+        account_keypair: Keypair,
+        lamports: u64,
+        space: u64,
+        owner: Pubkey,
+    ) -> Result<(Keypair, Pubkey, OutPoint)> {
+        let account_pubkey = Pubkey::from_slice(&account_keypair.x_only_public_key().0.serialize());
+        let authority_pubkey = Pubkey::from_slice(&authority_kp.x_only_public_key().0.serialize());
+
+        let anchor = self.send_utxo(&account_pubkey).await?;
+        let recent_blockhash = self.get_recent_blockhash().await?;
+
+        let message = ArchMessage::new(
+            &[system_instruction::create_account_with_anchor(
+                &authority_pubkey,
+                &account_pubkey,
+                lamports,
+                space,
+                &owner,
+                anchor_txid_bytes(&anchor)?,
+                anchor.vout,
+            )],
+            Some(authority_pubkey),
+            recent_blockhash.parse()?,
+        );
+
+        let create_account_tx = self
+            .build_and_sign_transaction(message, vec![authority_kp, account_keypair.clone()])
+            .await?;
+
+        let txid = self.send_transaction(create_account_tx).await?;
+        let processed_tx = self.wait_for_transaction(&txid).await?;
+
+        match processed_tx.status {
+            Status::Processed => Ok((account_keypair, account_pubkey, anchor)),
+            Status::Failed(e) => Err(anyhow::anyhow!("Account creation failed: {}", e)),
+            Status::Queued => Err(anyhow::anyhow!("Account creation transaction still queued")),
+        }
+    }
+
+    /// The UTXO anchoring `pubkey` and its confirmations, or `None` if the account isn't anchored
+    pub async fn account_anchor(&self, pubkey: &Pubkey) -> Result<Option<AccountAnchor>> {
+        let account = self.read_account_info(*pubkey).await?;
+
+        // unanchored accounts report an empty or all-zero utxo
+        let outpoint = match account.utxo.parse::<OutPoint>() {
+            Ok(outpoint) if outpoint.txid != Txid::all_zeros() => outpoint,
+            _ => return Ok(None),
+        };
+
+        let confirmations = self.bitcoin_confirmations(outpoint.txid).await?;
+
+        Ok(Some(AccountAnchor {
+            outpoint,
+            confirmations,
+        }))
+    }
+
+    /// Assert that `pubkey` is anchored to a Bitcoin UTXO, returning the anchor
+    pub async fn assert_account_anchored(&self, pubkey: &Pubkey) -> Result<AccountAnchor> {
+        self.account_anchor(pubkey)
+            .await?
+            .with_context(|| format!("Expected account {} to be anchored", pubkey))
+    }
+
+    /// Assert that the UTXO anchoring `pubkey` has at least `depth` confirmations
+    pub async fn assert_anchor_confirmed(&self, pubkey: &Pubkey, depth: u32) -> Result<()> {
+        let anchor = self.assert_account_anchored(pubkey).await?;
+
+        ensure!(
+            anchor.confirmations >= depth,
+            "Expected anchor {} of {} to have {} confirmations, it has {}",
+            anchor.outpoint,
+            pubkey,
+            depth,
+            anchor.confirmations
+        );
+
+        Ok(())
+    }
+}
+
+/// Txid bytes in the (display) order Arch's anchor instructions expect
+pub(crate) fn anchor_txid_bytes(outpoint: &OutPoint) -> Result<[u8; 32]> {
+    hex::decode(outpoint.txid.to_string())?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Txid of {} isn't 32 bytes", outpoint))
+}
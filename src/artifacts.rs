@@ -0,0 +1,68 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+use tokio::task::spawn_blocking;
+
+pub const FAILURE_LOG_TAIL_LINES: usize = 500;
+
+/// Snapshot docker's view of each container into `<artifact_dir>/<component>/`:
+/// `docker inspect`, a `docker stats` sample and the last `FAILURE_LOG_TAIL_LINES` log lines.
+///
+/// Runs before teardown so the containers still exist; every file is written even if
+/// another capture for the same container failed.
+pub(crate) async fn capture_docker_state(
+    artifact_dir: PathBuf,
+    containers: Vec<(String, String)>,
+) -> Result<()> {
+    spawn_blocking(move || {
+        for (component, container_id) in containers {
+            let component_dir = artifact_dir.join(&component);
+            std::fs::create_dir_all(&component_dir).with_context(|| {
+                format!("Failed to create artifact dir {}", component_dir.display())
+            })?;
+
+            let tail = FAILURE_LOG_TAIL_LINES.to_string();
+            let captures: [(&str, Vec<&str>); 3] = [
+                ("inspect.json", vec!["inspect", &container_id]),
+                (
+                    "stats.json",
+                    vec![
+                        "stats",
+                        "--no-stream",
+                        "--format",
+                        "{{json .}}",
+                        &container_id,
+                    ],
+                ),
+                ("logs.txt", vec!["logs", "--tail", &tail, &container_id]),
+            ];
+
+            for (file_name, args) in captures {
+                if let Err(e) = capture_docker_output(&component_dir.join(file_name), &args) {
+                    tracing::warn!("Failed to capture {} for {}: {}", file_name, component, e);
+                }
+            }
+        }
+
+        Ok(())
+    })
+    .await
+    .context("Failed to spawn blocking task")?
+}
+
+/// Run `docker <args>` and write its stdout (and stderr, which is where `docker logs`
+/// replays the container's stderr) to `path`
+fn capture_docker_output(path: &Path, args: &[&str]) -> Result<()> {
+    let output = Command::new("docker")
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run docker {}", args.join(" ")))?;
+
+    let mut contents = output.stdout;
+    contents.extend_from_slice(&output.stderr);
+
+    std::fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
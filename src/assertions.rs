@@ -0,0 +1,179 @@
+//! Assertions on Arch state, reported with enough context to debug a failure from the log.
+
+use std::{fmt, future::Future};
+
+use anyhow::{ensure, Context, Result};
+use arch_program::pubkey::Pubkey;
+use arch_sdk::{ProcessedTransaction, Status};
+
+use crate::TestContext;
+
+/// Log lines kept by `TxFailure::log_excerpt`
+const LOG_EXCERPT_LINES: usize = 10;
+
+/// A failed transaction, broken into the parts tests usually assert on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxFailure {
+    /// The validator's error message, as reported in `Status::Failed`
+    pub message: String,
+    /// Index of the failing instruction, when the message names one
+    pub instruction_index: Option<usize>,
+    /// Program-defined error code (`custom program error: 0x..`), when there is one
+    pub custom_error_code: Option<u32>,
+    pub logs: Vec<String>,
+}
+
+impl TxFailure {
+    fn parse(message: &str, logs: &[String]) -> Self {
+        Self {
+            message: message.to_string(),
+            instruction_index: number_after(message, "Instruction ", 10)
+                .and_then(|index| usize::try_from(index).ok()),
+            custom_error_code: number_after(message, "custom program error: 0x", 16),
+            logs: logs.to_vec(),
+        }
+    }
+
+    /// The last few log lines, where programs usually explain why they failed
+    pub fn log_excerpt(&self) -> String {
+        let start = self.logs.len().saturating_sub(LOG_EXCERPT_LINES);
+        self.logs[start..].join("\n")
+    }
+}
+
+impl fmt::Display for TxFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if !self.logs.is_empty() {
+            write!(f, "\nlogs (tail):\n{}", self.log_excerpt())?;
+        }
+        Ok(())
+    }
+}
+
+/// The number (in `radix`) following the first occurrence of `prefix` in `message`
+fn number_after(message: &str, prefix: &str, radix: u32) -> Option<u32> {
+    let rest = &message[message.find(prefix)? + prefix.len()..];
+    let digits = rest
+        .find(|c: char| !c.is_digit(radix))
+        .map_or(rest, |end| &rest[..end]);
+
+    u32::from_str_radix(digits, radix).ok()
+}
+
+/// Typed access to a processed transaction's failure
+pub trait ProcessedTransactionExt {
+    /// `Some` if the transaction failed
+    fn failure(&self) -> Option<TxFailure>;
+}
+
+impl ProcessedTransactionExt for ProcessedTransaction {
+    fn failure(&self) -> Option<TxFailure> {
+        match &self.status {
+            Status::Failed(message) => Some(TxFailure::parse(message, &self.logs)),
+            _ => None,
+        }
+    }
+}
+
+/// Assert that a `ProcessedTransaction` failed in a way matching `pattern` (a `TxFailure`
+/// pattern), returning the failure:
+///
+/// ```ignore
+/// assert_tx_failed!(processed_tx, TxFailure { custom_error_code: Some(6), .. });
+/// ```
+#[macro_export]
+macro_rules! assert_tx_failed {
+    ($processed_tx:expr) => {
+        $crate::assert_tx_failed!($processed_tx, _)
+    };
+    ($processed_tx:expr, $pattern:pat) => {{
+        let processed_tx = &$processed_tx;
+        match $crate::ProcessedTransactionExt::failure(processed_tx) {
+            Some(failure) if matches!(failure, $pattern) => failure,
+            Some(failure) => panic!(
+                "Transaction failed, but didn't match {}: {}",
+                stringify!($pattern),
+                failure
+            ),
+            None => panic!(
+                "Expected transaction to fail, its status is {:?}",
+                processed_tx.status
+            ),
+        }
+    }};
+}
+
+impl TestContext {
+    /// Assert that `pubkey` holds exactly `expected` lamports (missing accounts hold 0); failing
+    /// to read it is an error, not a mismatch
+    pub async fn assert_lamports(&self, pubkey: &Pubkey, expected: u64) -> Result<()> {
+        let actual = self.lamports_of(*pubkey).await?;
+
+        ensure!(
+            actual == expected,
+            "Expected {} to hold {} lamports, it holds {} ({:+})",
+            pubkey,
+            expected,
+            actual,
+            actual as i128 - expected as i128
+        );
+
+        Ok(())
+    }
+
+    /// Run `f` and assert the balance of `pubkey` changed by exactly `delta` lamports across it,
+    /// returning whatever `f` returned. Fails without a delta if either balance can't be read.
+    pub async fn assert_lamports_changed_by<F, Fut, T>(
+        &self,
+        pubkey: &Pubkey,
+        delta: i64,
+        f: F,
+    ) -> Result<T>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let before = self
+            .lamports_of(*pubkey)
+            .await
+            .context("Failed to read the balance before the change")?;
+        let result = f().await?;
+        let after = self
+            .lamports_of(*pubkey)
+            .await
+            .context("Failed to read the balance after the change")?;
+
+        let actual_delta = after as i128 - before as i128;
+        ensure!(
+            actual_delta == delta as i128,
+            "Expected {} to change by {:+} lamports, it changed by {:+} ({} -> {})",
+            pubkey,
+            delta,
+            actual_delta,
+            before,
+            after
+        );
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tx_failure_parses_instruction_and_error_code() {
+        let failure = TxFailure::parse(
+            "Error processing Instruction 1: custom program error: 0x1f",
+            &[],
+        );
+        assert_eq!(failure.instruction_index, Some(1));
+        assert_eq!(failure.custom_error_code, Some(0x1f));
+
+        let failure = TxFailure::parse("insufficient funds", &[]);
+        assert_eq!(failure.instruction_index, None);
+        assert_eq!(failure.custom_error_code, None);
+    }
+}
@@ -0,0 +1,171 @@
+//! Manage a long-lived local stack (bitcoind, Titan, validator) with the configuration tests
+//! get from `TestRunnerConfig::new`.
+//!
+//! ```text
+//! cargo run --features cli --bin arch-test-env -- up
+//! arch-test-env status
+//! arch-test-env logs validator --follow
+//! arch-test-env down
+//! ```
+//!
+//! `up` keeps running until ctrl-c, then tears the stack down; `down` removes the containers
+//! left behind by an `up` (or a test run) that didn't get to.
+
+use std::process::{Command, ExitCode, Stdio};
+
+use anyhow::{bail, ensure, Context, Result};
+use arch_testing::{TestRunner, TestRunnerConfig};
+
+const USAGE: &str = "\
+usage: arch-test-env [--network-faults] <command>
+
+commands:
+    up                                   start the stack and wait for ctrl-c
+    down                                 remove the stack's containers
+    status                               show the state of each container
+    logs [SERVICE] [--follow] [--tail N] print container logs (SERVICE: bitcoind, titan,
+                                         validator, toxiproxy)";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    match run(args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("arch-test-env: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(mut args: Vec<String>) -> Result<()> {
+    let mut config = TestRunnerConfig::new()?;
+
+    if let Some(i) = args.iter().position(|arg| arg == "--network-faults") {
+        args.remove(i);
+        config.network_faults = true;
+    }
+
+    let Some(command) = args.first() else {
+        bail!("missing command\n\n{}", USAGE);
+    };
+
+    match command.as_str() {
+        "up" => up(config).await,
+        "down" => down(&config),
+        "status" => status(&config),
+        "logs" => logs(&config, &args[1..]),
+        "help" | "-h" | "--help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        other => bail!("unknown command {:?}\n\n{}", other, USAGE),
+    }
+}
+
+async fn up(config: TestRunnerConfig) -> Result<()> {
+    TestRunner::hold(config)
+        .await
+        .context("Stack failed (leftover containers? try `arch-test-env down`)")
+}
+
+fn down(config: &TestRunnerConfig) -> Result<()> {
+    for (service, container_name) in config.container_names() {
+        let output = Command::new("docker")
+            .args(["rm", "--force", "--volumes", &container_name])
+            .output()
+            .context("Failed to run docker")?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        if output.status.success() {
+            println!("{}: removed {}", service, container_name);
+        } else if stderr.contains("No such container") {
+            println!("{}: not running", service);
+        } else {
+            bail!("Failed to remove {}: {}", container_name, stderr.trim());
+        }
+    }
+
+    Ok(())
+}
+
+fn status(config: &TestRunnerConfig) -> Result<()> {
+    for (service, container_name) in config.container_names() {
+        let output = Command::new("docker")
+            .args([
+                "inspect",
+                "--format",
+                "{{.State.Status}} (started {{.State.StartedAt}})",
+                &container_name,
+            ])
+            .stderr(Stdio::null())
+            .output()
+            .context("Failed to run docker")?;
+
+        let state = if output.status.success() {
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        } else {
+            "not created".to_string()
+        };
+
+        println!("{:<10} {:<40} {}", service, container_name, state);
+    }
+
+    Ok(())
+}
+
+fn logs(config: &TestRunnerConfig, args: &[String]) -> Result<()> {
+    let mut service = None;
+    let mut follow = false;
+    let mut tail = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-f" | "--follow" => follow = true,
+            "--tail" => tail = Some(args.next().context("--tail needs a line count")?.clone()),
+            flag if flag.starts_with('-') => bail!("unknown logs flag {:?}\n\n{}", flag, USAGE),
+            name if service.is_none() => service = Some(name.to_string()),
+            extra => bail!("unexpected argument {:?}\n\n{}", extra, USAGE),
+        }
+    }
+
+    let containers = config
+        .container_names()
+        .into_iter()
+        .filter(|(name, _)| service.as_deref().map_or(true, |service| service == *name))
+        .collect::<Vec<_>>();
+
+    ensure!(
+        !containers.is_empty(),
+        "unknown service {:?}\n\n{}",
+        service.unwrap_or_default(),
+        USAGE
+    );
+    ensure!(!follow || containers.len() == 1, "--follow needs a SERVICE");
+
+    let show_headers = containers.len() > 1;
+    for (service, container_name) in containers {
+        let mut docker_args = vec!["logs".to_string()];
+        if follow {
+            docker_args.push("--follow".to_string());
+        }
+        if let Some(tail) = &tail {
+            docker_args.extend(["--tail".to_string(), tail.clone()]);
+        }
+        docker_args.push(container_name.clone());
+
+        if show_headers {
+            println!("==> {} ({}) <==", service, container_name);
+        }
+
+        let status = Command::new("docker")
+            .args(&docker_args)
+            .status()
+            .context("Failed to run docker")?;
+        ensure!(status.success(), "docker logs {} failed", container_name);
+    }
+
+    Ok(())
+}
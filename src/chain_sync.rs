@@ -0,0 +1,104 @@
+//! Cross-service height synchronization.
+//!
+//! Mining a block on bitcoind doesn't mean Titan has indexed it, or that the validator has
+//! seen it; acting in between is a common source of flaky tests.
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use anyhow::{ensure, Result};
+use bitcoincore_rpc::RpcApi;
+
+use crate::TestContext;
+
+pub const DEFAULT_SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+const SYNC_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Bitcoin block height as seen by each service
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainHeights {
+    pub bitcoind: u64,
+    pub titan: u64,
+    /// Bitcoin height referenced by the validator's latest block
+    pub validator: u64,
+}
+
+impl ChainHeights {
+    pub fn is_synced(&self) -> bool {
+        self.bitcoind == self.titan && self.titan == self.validator
+    }
+}
+
+impl fmt::Display for ChainHeights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bitcoind: {}, titan: {}, validator: {}",
+            self.bitcoind, self.titan, self.validator
+        )
+    }
+}
+
+impl TestContext {
+    /// Current Bitcoin height according to bitcoind, Titan and the validator
+    pub async fn chain_heights(&self) -> Result<ChainHeights> {
+        let bitcoind = self
+            .with_bitcoin_client(|client| client.get_block_count())
+            .await?;
+        let (titan, _) = self.titan_tip().await?;
+
+        let validator_height = self.arch_async_rpc_client.get_block_count().await?;
+        let validator_block_hash = self
+            .arch_async_rpc_client
+            .get_block_hash(validator_height)
+            .await?;
+        let validator_block = self
+            .arch_async_rpc_client
+            .get_block(&validator_block_hash)
+            .await?;
+
+        Ok(ChainHeights {
+            bitcoind,
+            titan,
+            validator: validator_block.bitcoin_block_height,
+        })
+    }
+
+    /// Wait until Titan has indexed bitcoind's tip and the validator has caught up with it
+    pub async fn wait_for_sync(&self) -> Result<ChainHeights> {
+        self.wait_for_sync_with_timeout(DEFAULT_SYNC_TIMEOUT).await
+    }
+
+    pub async fn wait_for_sync_with_timeout(&self, timeout: Duration) -> Result<ChainHeights> {
+        let deadline = Instant::now() + timeout;
+        let mut last_observed = String::from("no heights observed");
+
+        while Instant::now() < deadline {
+            // services may briefly error while catching up; keep polling
+            match self.chain_heights().await {
+                Ok(heights) if heights.is_synced() => return Ok(heights),
+                Ok(heights) => last_observed = heights.to_string(),
+                Err(e) => last_observed = e.to_string(),
+            }
+
+            tokio::time::sleep(SYNC_POLL_INTERVAL).await;
+        }
+
+        Err(anyhow::anyhow!(
+            "Timed out after {:?} waiting for heights to sync (last observed: {})",
+            timeout,
+            last_observed
+        ))
+    }
+
+    /// Assert, without waiting, that all services agree on the Bitcoin height
+    pub async fn assert_heights_synced(&self) -> Result<()> {
+        let heights = self.chain_heights().await?;
+
+        ensure!(heights.is_synced(), "Heights are out of sync ({})", heights);
+
+        Ok(())
+    }
+}
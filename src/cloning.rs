@@ -0,0 +1,106 @@
+//! Copying account state from a remote Arch network (devnet, testnet) into the local validator.
+//!
+//! Accounts can only be created locally when signed by their own key, so a clone lives at a new
+//! local pubkey with the remote account's lamports, owner and data; `cloned_account` maps the
+//! remote pubkey to it.
+
+use anyhow::{ensure, Context, Result};
+use arch_program::pubkey::Pubkey;
+use arch_sdk::{AccountInfo, AsyncArchRpcClient};
+use bitcoin::key::Keypair;
+
+use crate::{RemoteAccount, TestContext};
+
+/// A remote account reproduced locally
+#[derive(Debug, Clone)]
+pub struct ClonedAccount {
+    pub remote_pubkey: Pubkey,
+    pub local_keypair: Keypair,
+    pub local_pubkey: Pubkey,
+    /// The remote account, as it was read
+    pub remote: AccountInfo,
+}
+
+/// Read `pubkey` from the Arch RPC at `remote_rpc_url`
+pub(crate) async fn fetch_remote_account(
+    remote_rpc_url: &str,
+    pubkey: Pubkey,
+) -> Result<AccountInfo> {
+    let remote_client = AsyncArchRpcClient::with_client(remote_rpc_url, reqwest::Client::new());
+
+    remote_client
+        .read_account_info(pubkey)
+        .await
+        .with_context(|| format!("Failed to read account {} from {}", pubkey, remote_rpc_url))
+}
+
+impl TestContext {
+    /// Fetch `pubkey` from the Arch RPC at `remote_rpc_url` and create a local account with the
+    /// same lamports, owner and data (see the module docs for why its pubkey differs).
+    ///
+    /// Programs can't be cloned this way; deploy their ELF instead.
+    pub async fn clone_account_from(
+        &self,
+        remote_rpc_url: &str,
+        pubkey: Pubkey,
+    ) -> Result<ClonedAccount> {
+        let remote = fetch_remote_account(remote_rpc_url, pubkey).await?;
+        ensure!(
+            !remote.is_executable,
+            "Account {} on {} is a program, which can't be cloned as an account",
+            pubkey,
+            remote_rpc_url
+        );
+
+        let (authority_kp, _, _) = self.generate_funded_keypair().await?;
+        let (local_keypair, _, _) = self.generate_new_keypair();
+
+        let local_pubkey = self
+            .create_account_with_data(
+                authority_kp,
+                local_keypair,
+                remote.lamports,
+                remote.owner,
+                &remote.data,
+            )
+            .await
+            .with_context(|| format!("Failed to clone account {}", pubkey))?;
+
+        self.cloned_accounts()
+            .lock()
+            .await
+            .insert(pubkey, local_pubkey);
+        tracing::info!(
+            "Cloned account {} from {} as {}",
+            pubkey,
+            remote_rpc_url,
+            local_pubkey
+        );
+
+        Ok(ClonedAccount {
+            remote_pubkey: pubkey,
+            local_keypair,
+            local_pubkey,
+            remote,
+        })
+    }
+
+    /// Local pubkey of the clone of `remote_pubkey`, if this context cloned it
+    pub async fn cloned_account(&self, remote_pubkey: &Pubkey) -> Option<Pubkey> {
+        self.cloned_accounts()
+            .lock()
+            .await
+            .get(remote_pubkey)
+            .copied()
+    }
+
+    /// Clone `accounts` (see `TestRunnerConfig::cloned_accounts`)
+    pub(crate) async fn clone_configured_accounts(&self, accounts: &[RemoteAccount]) -> Result<()> {
+        for account in accounts {
+            self.clone_account_from(&account.rpc_url, account.pubkey)
+                .await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+//! Waiting for transactions beyond "processed".
+//!
+//! A processed Arch transaction can still be undone if the Bitcoin transaction anchoring its
+//! state changes is dropped; tests that care about that wait for `Commitment::Finalized`.
+
+use std::time::Instant;
+
+use anyhow::{anyhow, Context, Result};
+use arch_sdk::{ProcessedTransaction, RuntimeTransaction, Status};
+
+use crate::{TestContext, DEFAULT_SETTLE_POLL_INTERVAL};
+
+/// Bitcoin confirmations after which `Commitment::Finalized` considers a transaction final
+pub const FINALIZED_CONFIRMATIONS: u32 = 1;
+
+/// How far along a transaction has to be before `send_and_confirm` returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Commitment {
+    /// Executed by the validator (what `wait_for_transaction` waits for)
+    #[default]
+    Processed,
+    /// Processed, and the Bitcoin transaction carrying its state changes (if any) has at least
+    /// `FINALIZED_CONFIRMATIONS` confirmations. This doesn't mine blocks; something else (e.g.
+    /// `start_auto_mining`) has to produce the confirmations.
+    Finalized,
+}
+
+impl TestContext {
+    /// Send `transaction` and wait until it reaches `commitment`. Failed transactions are
+    /// returned as soon as they're processed, since there's nothing of theirs to finalize.
+    pub async fn send_and_confirm(
+        &self,
+        transaction: RuntimeTransaction,
+        commitment: Commitment,
+    ) -> Result<ProcessedTransaction> {
+        let txid = self.send_transaction(transaction).await?;
+        let processed_tx = self.wait_for_transaction(&txid).await?;
+
+        match commitment {
+            Commitment::Processed => Ok(processed_tx),
+            Commitment::Finalized => self.wait_for_finalized(&txid, processed_tx).await,
+        }
+    }
+
+    async fn wait_for_finalized(
+        &self,
+        txid: &str,
+        processed_tx: ProcessedTransaction,
+    ) -> Result<ProcessedTransaction> {
+        if matches!(processed_tx.status, Status::Failed(_)) {
+            return Ok(processed_tx);
+        }
+        let Some(bitcoin_txid) = &processed_tx.bitcoin_txid else {
+            return Ok(processed_tx);
+        };
+
+        let bitcoin_txid: bitcoin::Txid = bitcoin_txid.to_string().parse().with_context(|| {
+            format!(
+                "Transaction {} has an invalid Bitcoin txid {}",
+                txid, bitcoin_txid
+            )
+        })?;
+
+        let deadline = Instant::now() + self.config().confirmation_timeout;
+        let mut last_confirmations = None;
+
+        while Instant::now() < deadline {
+            // not found yet is expected right after processing
+            if let Ok(confirmations) = self.bitcoin_confirmations(bitcoin_txid).await {
+                if confirmations >= FINALIZED_CONFIRMATIONS {
+                    return Ok(processed_tx);
+                }
+                last_confirmations = Some(confirmations);
+            }
+
+            tokio::time::sleep(DEFAULT_SETTLE_POLL_INTERVAL).await;
+        }
+
+        Err(anyhow!(
+            "Timed out waiting for transaction {} to finalize: Bitcoin transaction {} has {} (needs {})",
+            txid,
+            bitcoin_txid,
+            last_confirmations.map_or("not been seen".to_string(), |confirmations| format!(
+                "{} confirmations",
+                confirmations
+            )),
+            FINALIZED_CONFIRMATIONS
+        ))
+    }
+}
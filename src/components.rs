@@ -0,0 +1,226 @@
+//! Controlling the environment's containers from inside a test: restarts, pauses and failure
+//! injection.
+//!
+//! Containers are driven with the `docker` CLI by name, so they stay owned (and torn down) by
+//! the test runner.
+
+use std::{
+    fmt,
+    process::Command,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, ensure, Context, Result};
+use arch_sdk::{ArchRpcClient, AsyncArchRpcClient};
+use bitcoincore_rpc::RpcApi;
+use tokio::task::{spawn_blocking, JoinHandle};
+
+use crate::{containers::bitcoin_container::DEFAULT_WALLET_NAME, TestContext};
+
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A service of the test environment, each running in its own container
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Component {
+    Bitcoin,
+    Titan,
+    Validator,
+}
+
+impl Component {
+    pub const ALL: [Component; 3] = [Component::Bitcoin, Component::Titan, Component::Validator];
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Component::Bitcoin => "bitcoind",
+            Component::Titan => "titan",
+            Component::Validator => "validator",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Run `docker <args>`, returning its stdout
+pub(crate) async fn docker(args: Vec<String>) -> Result<String> {
+    spawn_blocking(move || {
+        let output = Command::new("docker")
+            .args(&args)
+            .output()
+            .with_context(|| format!("Failed to run docker {}", args.join(" ")))?;
+
+        ensure!(
+            output.status.success(),
+            "docker {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    })
+    .await
+    .context("Failed to spawn blocking task")?
+}
+
+impl TestContext {
+    /// Docker container name of `component`
+    pub fn container_name(&self, component: Component) -> String {
+        match component {
+            Component::Bitcoin => self.bitcoin().config.container_name.clone(),
+            Component::Titan => self.titan().config.container_name.clone(),
+            Component::Validator => self.validator().config.container_name.clone(),
+        }
+    }
+
+    /// SIGKILL `component`'s container: no graceful shutdown, no flushing. It stays down until
+    /// `restart_component`.
+    pub async fn kill_component(&self, component: Component) -> Result<()> {
+        tracing::info!("Killing {}", component);
+        docker(vec![
+            "kill".into(),
+            "--signal=KILL".into(),
+            self.container_name(component),
+        ])
+        .await?;
+
+        Ok(())
+    }
+
+    /// Freeze `component`'s container (`docker pause`): it stays up and keeps its connections
+    /// open, but stops responding until `unpause_component`
+    pub async fn pause_component(&self, component: Component) -> Result<()> {
+        tracing::info!("Pausing {}", component);
+        docker(vec!["pause".into(), self.container_name(component)]).await?;
+
+        Ok(())
+    }
+
+    pub async fn unpause_component(&self, component: Component) -> Result<()> {
+        tracing::info!("Unpausing {}", component);
+        docker(vec!["unpause".into(), self.container_name(component)]).await?;
+
+        Ok(())
+    }
+
+    /// `pause_component`, then unpause it in the background after `duration`. Await the
+    /// returned handle to know when it's running again.
+    pub async fn pause_component_for(
+        &self,
+        component: Component,
+        duration: Duration,
+    ) -> Result<JoinHandle<Result<()>>> {
+        self.pause_component(component).await?;
+
+        let ctx = self.clone();
+        Ok(tokio::spawn(async move {
+            tokio::time::sleep(duration).await;
+            ctx.unpause_component(component).await
+        }))
+    }
+
+    /// `kill_component`, then restart it in the background after `restart_after`. Await the
+    /// returned handle to know when it's ready again.
+    pub async fn kill_component_with_restart(
+        &self,
+        component: Component,
+        restart_after: Duration,
+    ) -> Result<JoinHandle<Result<()>>> {
+        self.kill_component(component).await?;
+
+        let ctx = self.clone();
+        Ok(tokio::spawn(async move {
+            tokio::time::sleep(restart_after).await;
+            ctx.restart_component(component).await
+        }))
+    }
+
+    /// Start a stopped (or killed) component's container again and wait until it answers
+    pub async fn restart_component(&self, component: Component) -> Result<()> {
+        tracing::info!("Starting {}", component);
+        docker(vec!["start".into(), self.container_name(component)]).await?;
+
+        self.wait_for_component_ready(component).await
+    }
+
+    /// Poll `component` until it answers, for up to the validator's startup timeout
+    pub(crate) async fn wait_for_component_ready(&self, component: Component) -> Result<()> {
+        let deadline = Instant::now() + self.validator().config.startup_timeout;
+        let mut last_error = String::new();
+
+        while Instant::now() < deadline {
+            let ready = match component {
+                Component::Bitcoin => {
+                    // a restarted bitcoind doesn't reload the test wallet by itself
+                    let _ = self
+                        .with_bitcoin_client(|client| client.load_wallet(DEFAULT_WALLET_NAME))
+                        .await;
+                    self.with_bitcoin_client(|client| client.get_block_count())
+                        .await
+                        .map(|_| ())
+                }
+                Component::Titan => self.titan_tip().await.map(|_| ()),
+                Component::Validator => self
+                    .arch_async_rpc_client
+                    .get_block_count()
+                    .await
+                    .map(|_| ())
+                    .map_err(Into::into),
+            };
+
+            match ready {
+                Ok(()) => return Ok(()),
+                Err(e) => last_error = e.to_string(),
+            }
+
+            tokio::time::sleep(READY_POLL_INTERVAL).await;
+        }
+
+        Err(anyhow!("{} didn't become ready: {}", component, last_error))
+    }
+
+    /// Stop the validator container (keeping its data), start it again, wait for its RPC to
+    /// answer, and rebuild this context's validator RPC clients and websocket.
+    ///
+    /// Only this context's clients are rebuilt; clones made before the restart keep their
+    /// connection pools, which recover on their own after a failed request or two, and their
+    /// (closed) websocket.
+    pub async fn restart_validator(&mut self) -> Result<()> {
+        let container_name = self.validator().config.container_name.clone();
+        tracing::info!("Restarting validator container {}", container_name);
+
+        docker(vec!["stop".into(), container_name.clone()]).await?;
+        docker(vec!["start".into(), container_name]).await?;
+
+        self.rebuild_validator_clients()?;
+        self.wait_for_component_ready(Component::Validator).await
+    }
+
+    /// Fresh connection pools for the validator, so nothing reuses connections to the old
+    /// process, and a websocket that connects anew on its next use
+    pub(crate) fn rebuild_validator_clients(&mut self) -> Result<()> {
+        let validator = self.validator().clone();
+        let http_client = validator.rpc_client_config.build_http_client()?;
+
+        let bitcoin_config = &self.bitcoin().config;
+        let (node_username, node_password) = bitcoin_config.rpc_credentials()?;
+        let arch_rpc_client = ArchRpcClient::new(&arch_sdk::Config {
+            node_endpoint: bitcoin_config.local_network_rpc_url(),
+            node_username,
+            node_password,
+            network: self.network,
+            arch_node_url: validator.rpc_url(),
+        });
+
+        self.arch_async_rpc_client = Arc::new(AsyncArchRpcClient::with_client(
+            &validator.rpc_url(),
+            http_client.clone(),
+        ));
+        self.set_arch_rpc_client(arch_rpc_client);
+        self.set_validator_http_client(http_client);
+        self.reset_validator_websocket();
+
+        Ok(())
+    }
+}
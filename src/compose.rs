@@ -0,0 +1,228 @@
+//! Export of the environment as a docker compose file, to run the same topology outside Rust
+//! (CI `services:` blocks, manual debugging).
+//!
+//! The services are rendered from the same container configs the runner starts, so images,
+//! commands, environment and ports match. The containers reach each other through
+//! `host.docker.internal` and the published ports, exactly like under the runner. What
+//! `TestRunner` does after the containers start (creating the bitcoind wallet, mining the
+//! initial blocks, deploying `preloaded_programs`, ...) isn't part of the file.
+//!
+//! ```ignore
+//! std::fs::write("docker-compose.yml", TestRunnerConfig::new()?.to_compose_yaml()?)?;
+//! ```
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use anyhow::{ensure, Result};
+
+use crate::{
+    containers::local_validator_container::CONTAINER_DATA_DIR, BitcoinContainerConfig,
+    BitcoinRpcAuth, LocalValidatorContainerConfig, TestRunnerConfig, TitanContainerConfig,
+};
+
+/// Compose project name of the exported file
+pub const COMPOSE_PROJECT_NAME: &str = "arch-testing";
+
+const BITCOIN_CONF_CONFIG_NAME: &str = "bitcoin_conf";
+
+struct ComposeService {
+    name: &'static str,
+    image: String,
+    container_name: String,
+    command: Vec<String>,
+    environment: BTreeMap<String, String>,
+    ports: Vec<u16>,
+    volumes: Vec<String>,
+    /// `(config name, path inside the container)`
+    configs: Vec<(&'static str, String)>,
+    depends_on: Vec<&'static str>,
+}
+
+impl TestRunnerConfig {
+    /// Render bitcoind, Titan and the validator, as this configuration starts them, into a
+    /// docker compose file.
+    ///
+    /// Fails for configurations compose can't reproduce: `network_faults` (toxiproxy sits
+    /// between the services) and cookie RPC auth (Titan is handed the cookie's contents, which
+    /// only exist once bitcoind runs). Services added with `with_service` aren't exported.
+    pub fn to_compose_yaml(&self) -> Result<String> {
+        ensure!(
+            !self.network_faults,
+            "network_faults isn't supported in the compose export"
+        );
+
+        let bitcoin_config = BitcoinContainerConfig::from(self.clone());
+        let titan_config = TitanContainerConfig::from(self.clone());
+        let validator_config = LocalValidatorContainerConfig::from(self.clone());
+
+        ensure!(
+            matches!(bitcoin_config.rpc_auth, BitcoinRpcAuth::UserPass),
+            "Cookie RPC auth isn't supported in the compose export"
+        );
+
+        let mut bitcoin_configs = Vec::new();
+        if bitcoin_config.bitcoin_conf.is_some() {
+            bitcoin_configs.push((BITCOIN_CONF_CONFIG_NAME, bitcoin_config.bitcoin_conf_path()));
+        }
+
+        let services = [
+            ComposeService {
+                name: "bitcoind",
+                image: format!("{}:{}", bitcoin_config.image_name, bitcoin_config.image_tag),
+                container_name: bitcoin_config.container_name.clone(),
+                command: bitcoin_config.bitcoind_command(),
+                environment: bitcoin_config.env(),
+                ports: vec![bitcoin_config.rpc_port],
+                volumes: Vec::new(),
+                configs: bitcoin_configs,
+                depends_on: Vec::new(),
+            },
+            ComposeService {
+                name: "titan",
+                image: format!("{}:{}", titan_config.image_name, titan_config.image_tag),
+                container_name: titan_config.container_name.clone(),
+                command: Vec::new(),
+                environment: titan_config.env(
+                    &bitcoin_config,
+                    &bitcoin_config.rpc_user,
+                    &bitcoin_config.rpc_password,
+                ),
+                ports: vec![titan_config.tcp_port, titan_config.http_port],
+                volumes: Vec::new(),
+                configs: Vec::new(),
+                depends_on: vec!["bitcoind"],
+            },
+            ComposeService {
+                name: "validator",
+                image: format!(
+                    "{}:{}",
+                    validator_config.image_name, validator_config.image_tag
+                ),
+                container_name: validator_config.container_name.clone(),
+                command: validator_config.local_validator_command(&titan_config),
+                environment: validator_config.env(),
+                ports: vec![validator_config.rpc_port, validator_config.websocket_port],
+                volumes: validator_config
+                    .data_dir
+                    .iter()
+                    .map(|data_dir| format!("{}:{}", data_dir.display(), CONTAINER_DATA_DIR))
+                    .collect(),
+                configs: Vec::new(),
+                depends_on: vec!["titan"],
+            },
+        ];
+
+        let mut yaml = String::new();
+        writeln!(yaml, "# Generated by TestRunnerConfig::to_compose_yaml")?;
+        writeln!(yaml, "name: {}", COMPOSE_PROJECT_NAME)?;
+        writeln!(yaml, "services:")?;
+        for service in &services {
+            write_service(&mut yaml, service)?;
+        }
+
+        if let Some(bitcoin_conf) = &bitcoin_config.bitcoin_conf {
+            writeln!(yaml, "configs:")?;
+            writeln!(yaml, "  {}:", BITCOIN_CONF_CONFIG_NAME)?;
+            writeln!(yaml, "    content: {}", quote(bitcoin_conf))?;
+        }
+
+        Ok(yaml)
+    }
+}
+
+fn write_service(yaml: &mut String, service: &ComposeService) -> std::fmt::Result {
+    writeln!(yaml, "  {}:", service.name)?;
+    writeln!(yaml, "    image: {}", quote(&service.image))?;
+    writeln!(
+        yaml,
+        "    container_name: {}",
+        quote(&service.container_name)
+    )?;
+
+    if !service.command.is_empty() {
+        writeln!(yaml, "    command:")?;
+        for arg in &service.command {
+            writeln!(yaml, "      - {}", quote(arg))?;
+        }
+    }
+
+    if !service.environment.is_empty() {
+        writeln!(yaml, "    environment:")?;
+        for (key, value) in &service.environment {
+            writeln!(yaml, "      {}: {}", quote(key), quote(value))?;
+        }
+    }
+
+    writeln!(yaml, "    ports:")?;
+    for port in &service.ports {
+        writeln!(yaml, "      - {}", quote(&format!("{}:{}", port, port)))?;
+    }
+
+    if !service.volumes.is_empty() {
+        writeln!(yaml, "    volumes:")?;
+        for volume in &service.volumes {
+            writeln!(yaml, "      - {}", quote(volume))?;
+        }
+    }
+
+    if !service.configs.is_empty() {
+        writeln!(yaml, "    configs:")?;
+        for (source, target) in &service.configs {
+            writeln!(yaml, "      - source: {}", source)?;
+            writeln!(yaml, "        target: {}", quote(target))?;
+        }
+    }
+
+    // the services address each other through the host, as they do under the runner
+    writeln!(yaml, "    extra_hosts:")?;
+    writeln!(yaml, "      - \"host.docker.internal:host-gateway\"")?;
+
+    if !service.depends_on.is_empty() {
+        writeln!(yaml, "    depends_on:")?;
+        for dependency in &service.depends_on {
+            writeln!(yaml, "      - {}", dependency)?;
+        }
+        // the runner waits for each service to be ready; compose only orders the starts
+        writeln!(yaml, "    restart: on-failure")?;
+    }
+
+    Ok(())
+}
+
+/// A double-quoted YAML scalar (JSON strings are valid YAML)
+fn quote(value: &str) -> String {
+    serde_json::Value::from(value).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose_yaml_matches_container_configs() {
+        let mut config = TestRunnerConfig::new().expect("Failed to create test config");
+        config
+            .titan_extra_env
+            .insert("COMMIT_INTERVAL".to_string(), "1".to_string());
+        config.bitcoin_conf = Some("[regtest]\ndbcache=100\n".to_string());
+
+        let yaml = config
+            .to_compose_yaml()
+            .expect("Failed to render compose file");
+
+        assert!(yaml.contains("  bitcoind:\n    image: \"bitcoin/bitcoin:29.0\""));
+        assert!(yaml.contains("      - \"-rpcuser=bitcoind_username\""));
+        assert!(yaml.contains("      \"COMMIT_INTERVAL\": \"1\""));
+        assert!(yaml.contains("      - \"9002:9002\""));
+        assert!(yaml.contains("    depends_on:\n      - titan"));
+        assert!(yaml.contains("    content: \"[regtest]\\ndbcache=100\\n\""));
+    }
+
+    #[test]
+    fn test_compose_yaml_rejects_network_faults() {
+        let mut config = TestRunnerConfig::new().expect("Failed to create test config");
+        config.network_faults = true;
+
+        assert!(config.to_compose_yaml().is_err());
+    }
+}
@@ -0,0 +1,135 @@
+//! Compute unit measurement, for perf-budget regression tests.
+//!
+//! Neither the SDK nor the validator image documents a simulation RPC, so measuring means
+//! sending the transaction: its effects are committed. For the same reason there's no
+//! `simulate_transaction`: a preflight that might silently commit (or 404) on some validator
+//! builds is worse than none. Where a validator build does serve one, call it through
+//! `ValidatorHandle::call`.
+
+use anyhow::{anyhow, ensure, Result};
+use arch_program::{instruction::Instruction, pubkey::Pubkey};
+use bitcoin::key::Keypair;
+
+use crate::{ProcessedTransactionExt, TestContext};
+
+/// Compute units consumed by each top-level instruction of a transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComputeUnits {
+    pub per_instruction: Vec<u64>,
+}
+
+impl ComputeUnits {
+    pub fn total(&self) -> u64 {
+        self.per_instruction.iter().sum()
+    }
+
+    /// Read consumption from program logs (`Program <id> consumed <n> of <m> compute units`),
+    /// counting only top-level invocations, since CPIs are included in their caller's total
+    pub fn from_logs(logs: &[String]) -> Self {
+        let mut depth = 0;
+        let mut per_instruction = Vec::new();
+
+        for log in logs {
+            let Some(rest) = log.strip_prefix("Program ") else {
+                continue;
+            };
+
+            if let Some(invoke_depth) = rest
+                .split_once(" invoke [")
+                .and_then(|(_, depth)| depth.trim_end_matches(']').parse().ok())
+            {
+                depth = invoke_depth;
+            } else if let Some((_, consumed)) = rest.split_once(" consumed ") {
+                let units = consumed
+                    .split_whitespace()
+                    .next()
+                    .and_then(|n| n.parse().ok());
+                if let (1, Some(units)) = (depth, units) {
+                    per_instruction.push(units);
+                }
+            } else if rest.ends_with(" success") || rest.contains(" failed") {
+                depth = depth.saturating_sub(1);
+            }
+        }
+
+        Self { per_instruction }
+    }
+
+    /// Assert the whole transaction stayed under `limit` compute units. Fails if no consumption
+    /// was read from the logs, rather than passing without checking anything.
+    pub fn assert_cu_under(&self, limit: u64) -> Result<()> {
+        ensure!(
+            !self.per_instruction.is_empty(),
+            "No compute unit consumption found in the transaction logs"
+        );
+        ensure!(
+            self.total() < limit,
+            "Transaction consumed {} compute units, limit is {} (per instruction: {:?})",
+            self.total(),
+            limit,
+            self.per_instruction
+        );
+
+        Ok(())
+    }
+}
+
+impl TestContext {
+    /// Compute units consumed by `instructions`, signed by `signers` (the first one pays), read
+    /// from the processed transaction: the transaction is sent and its effects are committed.
+    /// Fails if the transaction does, since a reverted call's consumption says nothing about
+    /// its cost.
+    pub async fn measure_compute_units(
+        &self,
+        instructions: &[Instruction],
+        signers: Vec<Keypair>,
+    ) -> Result<ComputeUnits> {
+        let payer = signers
+            .first()
+            .map(|payer| Pubkey::from_slice(&payer.x_only_public_key().0.serialize()));
+        let message = self.build_message(instructions, payer).await?;
+        let transaction = self.build_and_sign_transaction(message, signers).await?;
+
+        let txid = self.send_transaction(transaction).await?;
+        let processed_tx = self.wait_for_transaction(&txid).await?;
+        if let Some(failure) = processed_tx.failure() {
+            return Err(anyhow!(
+                "Transaction {} failed, compute units not measured: {}",
+                txid,
+                failure
+            ));
+        }
+
+        Ok(ComputeUnits::from_logs(&processed_tx.logs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_units_counts_top_level_instructions_only() {
+        let logs = [
+            "Program AAA invoke [1]",
+            "Program BBB invoke [2]",
+            "Program BBB consumed 100 of 9000 compute units",
+            "Program BBB success",
+            "Program AAA consumed 400 of 10000 compute units",
+            "Program AAA success",
+            "Program CCC invoke [1]",
+            "Program CCC consumed 50 of 10000 compute units",
+            "Program CCC success",
+        ]
+        .map(String::from);
+
+        let compute_units = ComputeUnits::from_logs(&logs);
+        assert_eq!(compute_units.per_instruction, vec![400, 50]);
+        assert_eq!(compute_units.total(), 450);
+        assert!(compute_units.assert_cu_under(451).is_ok());
+        assert!(compute_units.assert_cu_under(450).is_err());
+
+        let no_logs = ComputeUnits::from_logs(&[]);
+        assert!(no_logs.assert_cu_under(u64::MAX).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use bitcoin::{Address, Amount, Block, BlockHash, Transaction, Txid};
+use bitcoincore_rpc::{json::GetMempoolEntryResult, Client, RpcApi};
+use tokio::task::spawn_blocking;
+
+/// Async facade over the blocking `bitcoincore_rpc::Client`.
+///
+/// Every call runs on tokio's blocking pool, so consumers don't have to juggle `spawn_blocking`.
+/// Cheap to clone; clones share the underlying client.
+#[derive(Clone)]
+pub struct AsyncBitcoinClient {
+    client: Arc<Client>,
+}
+
+impl AsyncBitcoinClient {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client: Arc::new(client),
+        }
+    }
+
+    /// Run any blocking call against the underlying client, for RPCs not covered below
+    pub async fn with_client<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Client) -> bitcoincore_rpc::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let client = self.client.clone();
+
+        Ok(spawn_blocking(move || f(&client)).await??)
+    }
+
+    pub async fn get_block_count(&self) -> Result<u64> {
+        self.with_client(|client| client.get_block_count()).await
+    }
+
+    pub async fn get_best_block_hash(&self) -> Result<BlockHash> {
+        self.with_client(|client| client.get_best_block_hash())
+            .await
+    }
+
+    pub async fn get_block_hash(&self, height: u64) -> Result<BlockHash> {
+        self.with_client(move |client| client.get_block_hash(height))
+            .await
+    }
+
+    pub async fn get_block(&self, hash: BlockHash) -> Result<Block> {
+        self.with_client(move |client| client.get_block(&hash))
+            .await
+    }
+
+    pub async fn get_new_address(&self) -> Result<Address> {
+        Ok(self
+            .with_client(|client| client.get_new_address(None, None))
+            .await?
+            .assume_checked())
+    }
+
+    /// Mine `count` blocks to a fresh wallet address
+    pub async fn generate(&self, count: u64) -> Result<Vec<BlockHash>> {
+        let address = self.get_new_address().await?;
+        self.generate_to_address(count, &address).await
+    }
+
+    pub async fn generate_to_address(
+        &self,
+        count: u64,
+        address: &Address,
+    ) -> Result<Vec<BlockHash>> {
+        let address = address.clone();
+
+        self.with_client(move |client| client.generate_to_address(count, &address))
+            .await
+    }
+
+    pub async fn send_to_address(&self, address: &Address, amount: Amount) -> Result<Txid> {
+        let address = address.clone();
+
+        self.with_client(move |client| {
+            client.send_to_address(&address, amount, None, None, None, None, None, None)
+        })
+        .await
+    }
+
+    pub async fn send_raw_transaction(&self, transaction: Transaction) -> Result<Txid> {
+        self.with_client(move |client| client.send_raw_transaction(&transaction))
+            .await
+    }
+
+    /// Needs `-txindex=1` for confirmed transactions the wallet doesn't know about
+    pub async fn get_raw_transaction(&self, txid: Txid) -> Result<Transaction> {
+        self.with_client(move |client| client.get_raw_transaction(&txid, None))
+            .await
+    }
+
+    pub async fn get_raw_mempool(&self) -> Result<Vec<Txid>> {
+        self.with_client(|client| client.get_raw_mempool()).await
+    }
+
+    pub async fn get_mempool_entry(&self, txid: Txid) -> Result<GetMempoolEntryResult> {
+        self.with_client(move |client| client.get_mempool_entry(&txid))
+            .await
+    }
+}
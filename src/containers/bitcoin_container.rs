@@ -1,21 +1,96 @@
-use std::time::Duration;
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
 
 use anyhow::{Context, Result};
-use backoff::{retry, ExponentialBackoff};
-use bitcoincore_rpc::{Client, RpcApi};
+use backoff::retry;
+use bitcoin::{Address, Amount, Txid};
+use bitcoincore_rpc::{json::AddressType, Client, RpcApi};
 use testcontainers::{
-    core::{logs::LogFrame, ContainerPort},
+    core::{ContainerPort, Mount},
     runners::AsyncRunner,
     ContainerAsync, GenericImage, ImageExt,
 };
 use tokio::task::spawn_blocking;
 
+use super::{
+    async_bitcoin_client::AsyncBitcoinClient, container_log_consumer, exec_in_container,
+    RetryPolicy,
+};
+use crate::{
+    error::{ArchTestingError, ArchTestingResult},
+    exec::{command_args, ExecOutput},
+    network_mode::{ArchNetworkMode, SignetConfig},
+};
+
 pub const DEFAULT_CONTAINER_NAME: &str = "arch-testing-bitcoin-container";
 pub const DEFAULT_IMAGE_NAME: &str = "bitcoin/bitcoin";
 pub const DEFAULT_IMAGE_TAG: &str = "29.0";
 pub const DEFAULT_RPC_PORT: u16 = 18443;
 pub const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
 pub const DEFAULT_TCP_PORT: u16 = 18444;
+pub const DEFAULT_WALLET_NAME: &str = "testwallet";
+
+const DATA_DIR: &str = "/var/lib/bitcoin-core";
+const COOKIE_CONTAINER_DIR: &str = "/rpc-cookie";
+const COOKIE_FILE_NAME: &str = ".cookie";
+
+/// Outputs created per filler transaction when flooding the mempool
+const FILLER_FANOUT_AMOUNT: Amount = Amount::from_sat(100_000);
+const FILLER_AMOUNT: Amount = Amount::from_sat(10_000);
+
+/// Fee environment bitcoind runs under, so fee-estimation paths can be exercised under pressure
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FeeScenario {
+    /// Near-zero fallback fee, default relay floor (what this crate always used)
+    #[default]
+    Cheap,
+    /// Fees in the range of a normal mainnet day
+    Realistic,
+    /// High fees, a raised relay floor, and `filler_transactions` sitting in the mempool at startup
+    Congested { filler_transactions: usize },
+}
+
+impl FeeScenario {
+    /// `-fallbackfee`/`-minrelaytxfee` values (BTC/kvB)
+    pub fn bitcoind_args(&self) -> Vec<String> {
+        let (fallback_fee, min_relay_fee) = match self {
+            FeeScenario::Cheap => ("0.00000001", None),
+            FeeScenario::Realistic => ("0.0002", Some("0.00001")),
+            FeeScenario::Congested { .. } => ("0.001", Some("0.00005")),
+        };
+
+        let mut args = vec![format!("-fallbackfee={}", fallback_fee)];
+        if let Some(min_relay_fee) = min_relay_fee {
+            args.push(format!("-minrelaytxfee={}", min_relay_fee));
+        }
+
+        args
+    }
+
+    pub fn filler_transactions(&self) -> usize {
+        match self {
+            FeeScenario::Congested {
+                filler_transactions,
+            } => *filler_transactions,
+            _ => 0,
+        }
+    }
+}
+
+/// How clients authenticate against bitcoind's RPC server
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum BitcoinRpcAuth {
+    /// `-rpcuser`/`-rpcpassword`, using `rpc_user` and `rpc_password`
+    #[default]
+    UserPass,
+    /// bitcoind's cookie file, written to `host_dir` (bind-mounted into the container).
+    /// For hardened images where user/pass RPC auth is disabled.
+    CookieFile { host_dir: PathBuf },
+}
 
 #[derive(Debug, Clone)]
 pub struct BitcoinContainerConfig {
@@ -25,8 +100,29 @@ pub struct BitcoinContainerConfig {
     pub rpc_port: u16,
     pub rpc_user: String,
     pub rpc_password: String,
+    pub rpc_auth: BitcoinRpcAuth,
     pub tcp_port: u16,
     pub startup_timeout: Duration,
+    /// How the RPC readiness check is retried while bitcoind starts
+    pub retry_policy: RetryPolicy,
+    pub network_mode: ArchNetworkMode,
+    pub signet: SignetConfig,
+
+    /// Create the test wallet as a descriptor wallet (the only kind bitcoind 29+ creates by default)
+    pub descriptor_wallet: bool,
+    /// Address type used for premining and helper sends (and bitcoind's `-addresstype`/`-changetype`).
+    /// `None` leaves bitcoind's default (bech32) in place; use `AddressType::Bech32m` for taproot UTXOs.
+    pub address_type: Option<AddressType>,
+
+    pub fee_scenario: FeeScenario,
+
+    /// Contents of a `bitcoin.conf` copied into the datadir before bitcoind starts, for settings
+    /// such as `dbcache`, `maxmempool` or relay policy. Network specific options belong in a
+    /// `[regtest]` section. Command-line flags generated by this module take precedence.
+    pub bitcoin_conf: Option<String>,
+
+    /// Extra arguments appended to the generated `bitcoind` command, e.g. `-txindex=1`
+    pub extra_args: Vec<String>,
 }
 
 impl BitcoinContainerConfig {
@@ -38,17 +134,147 @@ impl BitcoinContainerConfig {
         format!("host.docker.internal:{}", self.tcp_port)
     }
 
+    /// The command bitcoind runs with inside the container
+    pub fn bitcoind_command(&self) -> Vec<String> {
+        let mut cmd_args = vec![
+            "bitcoind".to_string(),
+            format!("-datadir={}", DATA_DIR),
+            "-printtoconsole".to_string(),
+        ];
+
+        cmd_args.extend(self.fee_scenario.bitcoind_args());
+
+        // Add network flag only if it's not empty (mainnet has no flag)
+        let network_flag = self.bitcoin_network_flag();
+        if !network_flag.is_empty() {
+            cmd_args.push(network_flag.to_string());
+        }
+
+        if self.network_mode == ArchNetworkMode::Signet {
+            cmd_args.extend(self.signet.bitcoind_args());
+        }
+
+        cmd_args.extend_from_slice(&[
+            "-rpcallowip=0.0.0.0/0".to_string(),
+            "-rpcbind=0.0.0.0".to_string(),
+            format!("-rpcport={}", self.rpc_port),
+        ]);
+
+        match &self.rpc_auth {
+            BitcoinRpcAuth::UserPass => cmd_args.extend_from_slice(&[
+                format!("-rpcuser={}", self.rpc_user),
+                format!("-rpcpassword={}", self.rpc_password),
+            ]),
+            BitcoinRpcAuth::CookieFile { .. } => cmd_args.extend_from_slice(&[
+                format!("-rpccookiefile={}", self.container_cookie_file()),
+                // the cookie is read from the host, which doesn't share the container's uid
+                "-rpccookieperms=all".to_string(),
+            ]),
+        }
+
+        if let Some(address_type) = self.bitcoin_address_type_arg() {
+            cmd_args.push(format!("-addresstype={}", address_type));
+            cmd_args.push(format!("-changetype={}", address_type));
+        }
+
+        cmd_args.extend(self.extra_args.iter().cloned());
+
+        cmd_args
+    }
+
+    /// A `bitcoin-cli` invocation inside the container, pointed at this node, running `args`
+    pub fn bitcoin_cli_command(&self, args: impl IntoIterator<Item = String>) -> Vec<String> {
+        let mut cmd_args = vec!["bitcoin-cli".to_string()];
+
+        let network_flag = self.bitcoin_network_flag();
+        if !network_flag.is_empty() {
+            cmd_args.push(network_flag.to_string());
+        }
+
+        cmd_args.push(format!("-rpcport={}", self.rpc_port));
+
+        match &self.rpc_auth {
+            BitcoinRpcAuth::UserPass => cmd_args.extend_from_slice(&[
+                format!("-rpcuser={}", self.rpc_user),
+                format!("-rpcpassword={}", self.rpc_password),
+            ]),
+            BitcoinRpcAuth::CookieFile { .. } => {
+                cmd_args.push(format!("-rpccookiefile={}", self.container_cookie_file()))
+            }
+        }
+
+        cmd_args.extend(args);
+
+        cmd_args
+    }
+
+    /// Environment of the bitcoind container
+    pub fn env(&self) -> BTreeMap<String, String> {
+        BTreeMap::from([("BITCOIN_DATA".to_string(), DATA_DIR.to_string())])
+    }
+
+    /// Where `bitcoin_conf` is written inside the container
+    pub fn bitcoin_conf_path(&self) -> String {
+        format!("{}/bitcoin.conf", DATA_DIR)
+    }
+
+    fn container_cookie_file(&self) -> String {
+        format!("{}/{}", COOKIE_CONTAINER_DIR, COOKIE_FILE_NAME)
+    }
+
     pub fn local_network_rpc_url(&self) -> String {
         format!("http://127.0.0.1:{}", self.rpc_port)
     }
 
+    pub fn local_network_wallet_rpc_url(&self, wallet_name: &str) -> String {
+        format!("{}/wallet/{}", self.local_network_rpc_url(), wallet_name)
+    }
+
     pub fn local_network_tcp_address(&self) -> String {
         format!("127.0.0.1:{}", self.tcp_port)
     }
 
+    /// Host path of the RPC cookie file, when cookie auth is configured
+    pub fn rpc_cookie_file(&self) -> Option<PathBuf> {
+        match &self.rpc_auth {
+            BitcoinRpcAuth::UserPass => None,
+            BitcoinRpcAuth::CookieFile { host_dir } => Some(host_dir.join(COOKIE_FILE_NAME)),
+        }
+    }
+
+    /// Resolve the RPC username/password pair, reading the cookie file when cookie auth is configured.
+    ///
+    /// Titan and the validator only speak user/pass, so they are handed the cookie's contents.
+    /// The cookie only exists once bitcoind has started.
+    pub fn rpc_credentials(&self) -> Result<(String, String)> {
+        let Some(cookie_file) = self.rpc_cookie_file() else {
+            return Ok((self.rpc_user.clone(), self.rpc_password.clone()));
+        };
+
+        let cookie = std::fs::read_to_string(&cookie_file)
+            .with_context(|| format!("Failed to read rpc cookie {}", cookie_file.display()))?;
+
+        let (user, password) = cookie
+            .trim()
+            .split_once(':')
+            .with_context(|| format!("Malformed rpc cookie {}", cookie_file.display()))?;
+
+        Ok((user.to_string(), password.to_string()))
+    }
+
+    /// Map the configured address type to bitcoind's `-addresstype` value
+    pub fn bitcoin_address_type_arg(&self) -> Option<&'static str> {
+        self.address_type.map(|address_type| match address_type {
+            AddressType::Legacy => "legacy",
+            AddressType::P2shSegwit => "p2sh-segwit",
+            AddressType::Bech32 => "bech32",
+            AddressType::Bech32m => "bech32m",
+        })
+    }
+
     /// Map ArchNetworkMode to Bitcoin network flag
     pub fn bitcoin_network_flag(&self) -> &'static str {
-        "-regtest=1"
+        self.network_mode.bitcoin_network_flag()
     }
 }
 
@@ -61,64 +287,185 @@ impl Default for BitcoinContainerConfig {
             rpc_port: DEFAULT_RPC_PORT,
             rpc_user: "bitcoind_username".to_string(),
             rpc_password: "bitcoind_password".to_string(),
+            rpc_auth: BitcoinRpcAuth::default(),
             startup_timeout: DEFAULT_STARTUP_TIMEOUT,
+            retry_policy: RetryPolicy::default(),
             tcp_port: DEFAULT_TCP_PORT,
+            network_mode: ArchNetworkMode::default(),
+            signet: SignetConfig::default(),
+            descriptor_wallet: true,
+            address_type: None,
+            fee_scenario: FeeScenario::default(),
+            bitcoin_conf: None,
+            extra_args: Vec::new(),
         }
     }
 }
 
 impl From<&BitcoinContainerConfig> for bitcoincore_rpc::Auth {
     fn from(config: &BitcoinContainerConfig) -> Self {
-        bitcoincore_rpc::Auth::UserPass(config.rpc_user.clone(), config.rpc_password.clone())
+        match config.rpc_cookie_file() {
+            Some(cookie_file) => bitcoincore_rpc::Auth::CookieFile(cookie_file),
+            None => bitcoincore_rpc::Auth::UserPass(
+                config.rpc_user.clone(),
+                config.rpc_password.clone(),
+            ),
+        }
     }
 }
 
 pub struct BitcoinContainer {
     pub container: ContainerAsync<GenericImage>,
     pub client: Client,
+    pub async_client: AsyncBitcoinClient,
 
     config: BitcoinContainerConfig,
 }
 
 impl BitcoinContainer {
-    pub async fn start(config: &BitcoinContainerConfig) -> Result<Self> {
+    /// Run `cmd` in the container
+    pub async fn exec(
+        &self,
+        cmd: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<ExecOutput> {
+        exec_in_container(&self.container, command_args(cmd)).await
+    }
+
+    /// Run `bitcoin-cli` with `args` against this node and return its trimmed stdout
+    pub async fn cli(&self, args: impl IntoIterator<Item = impl Into<String>>) -> Result<String> {
+        exec_in_container(
+            &self.container,
+            self.config.bitcoin_cli_command(command_args(args)),
+        )
+        .await?
+        .into_stdout()
+    }
+
+    pub async fn start(config: &BitcoinContainerConfig) -> ArchTestingResult<Self> {
+        Self::try_start(config)
+            .await
+            .map_err(|e| ArchTestingError::container_start_failed("bitcoind", e))
+    }
+
+    async fn try_start(config: &BitcoinContainerConfig) -> Result<Self> {
         let container = start_container(config).await?;
 
         let rpc_url = config.local_network_rpc_url();
 
-        let client = Client::new(&rpc_url, config.into())
-            .with_context(|| format!("Failed to create rpc_client for {}", rpc_url))?;
-
+        // wait first: with cookie auth the client can't be built until bitcoind wrote its cookie
         wait_for_rpc_ready(&rpc_url, config).await?;
 
-        match client.create_wallet("testwallet", None, None, None, None) {
+        let node_client = Client::new(&rpc_url, config.into())
+            .with_context(|| format!("Failed to create rpc_client for {}", rpc_url))?;
+
+        match call_createwallet(&node_client, DEFAULT_WALLET_NAME, config.descriptor_wallet) {
             Ok(_) => {
-                tracing::info!("Successfully created testwallet");
+                tracing::info!("Successfully created {}", DEFAULT_WALLET_NAME);
             }
             Err(e) => {
-                tracing::error!("Failed to create testwallet: {}", e);
+                tracing::error!("Failed to create {}: {}", DEFAULT_WALLET_NAME, e);
                 tracing::error!("Error details: {:?}", e);
-                return Err(anyhow::anyhow!("Failed to create testwallet: {}", e));
+                return Err(anyhow::anyhow!(
+                    "Failed to create {}: {}",
+                    DEFAULT_WALLET_NAME,
+                    e
+                ));
             }
         }
 
-        let address = client
-            .get_new_address(None, None)
-            .context("Failed to get new address")?
-            .assume_checked();
+        // Scope the default client to the test wallet, so it keeps working once more wallets are loaded
+        let client = wallet_client(config, DEFAULT_WALLET_NAME)?;
 
-        client
-            .generate_to_address(100, &address)
-            .with_context(|| format!("Failed to generate to address: {}", address))?;
+        let async_client = AsyncBitcoinClient::new(wallet_client(config, DEFAULT_WALLET_NAME)?);
 
-        Ok(Self {
+        let bitcoin_container = Self {
             container,
             client,
+            async_client,
             config: config.clone(),
-        })
+        };
+
+        if config.network_mode.can_generate_blocks() {
+            let address = bitcoin_container.new_address()?;
+
+            bitcoin_container
+                .client
+                .generate_to_address(100, &address)
+                .with_context(|| format!("Failed to generate to address: {}", address))?;
+
+            let filler_transactions = config.fee_scenario.filler_transactions();
+            if filler_transactions > 0 {
+                bitcoin_container.flood_mempool(filler_transactions)?;
+            }
+        }
+
+        Ok(bitcoin_container)
+    }
+
+    /// Fill the mempool with `count` small filler transactions paying the wallet's fee.
+    ///
+    /// Fans out (and confirms) one UTXO per filler first, so the fillers don't form a single
+    /// unconfirmed chain that would run into the mempool's ancestor limits.
+    pub fn flood_mempool(&self, count: usize) -> Result<Vec<Txid>> {
+        let address = self.new_address()?;
+
+        // the premined coinbases only start maturing at height 101
+        self.client
+            .generate_to_address(1, &address)
+            .with_context(|| format!("Failed to generate to address: {}", address))?;
+
+        let mut fanout = serde_json::Map::new();
+        for _ in 0..count {
+            fanout.insert(
+                self.new_address()?.to_string(),
+                FILLER_FANOUT_AMOUNT.to_btc().into(),
+            );
+        }
+
+        self.client
+            .call::<Txid>("sendmany", &["".into(), fanout.into()])
+            .context("Failed to fan out filler utxos")?;
+        self.client
+            .generate_to_address(1, &address)
+            .with_context(|| format!("Failed to generate to address: {}", address))?;
+
+        let fillers = (0..count)
+            .map(|_| {
+                let address = self.new_address()?;
+                self.client
+                    .send_to_address(&address, FILLER_AMOUNT, None, None, None, None, None, None)
+                    .context("Failed to send filler transaction")
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        tracing::info!(
+            "Flooded the mempool with {} filler transactions",
+            fillers.len()
+        );
+
+        Ok(fillers)
+    }
+
+    /// Create (and load) another wallet, returning an RPC client scoped to it.
+    ///
+    /// Useful for modelling distinct actors (miner, user, attacker) with separate funds.
+    pub fn create_wallet(&self, name: &str) -> Result<Client> {
+        call_createwallet(&self.client, name, self.config.descriptor_wallet)
+            .with_context(|| format!("Failed to create wallet {}", name))?;
+
+        wallet_client(&self.config, name)
+    }
+
+    /// Get a new address from the test wallet, using the configured address type
+    pub fn new_address(&self) -> Result<Address> {
+        Ok(self
+            .client
+            .get_new_address(None, self.config.address_type)
+            .context("Failed to get new address")?
+            .assume_checked())
     }
 
-    pub async fn shutdown(&self) -> Result<()> {
+    pub async fn shutdown(&self) -> ArchTestingResult<()> {
         tracing::trace!(
             "Stopping bitcoin container: {} (image: {}:{})",
             self.config.container_name,
@@ -127,12 +474,32 @@ impl BitcoinContainer {
         );
 
         self.container.stop().await.map_err(|shutdown_err| {
-            anyhow::anyhow!(
+            let source = anyhow::anyhow!(
                 "Failed to stop bitcoin container: {} (image: {}:{})\nShutdown error: {}",
                 self.config.container_name,
                 self.config.image_name,
                 self.config.image_tag,
                 shutdown_err
+            );
+            ArchTestingError::teardown_failed("bitcoind", source)
+        })
+    }
+
+    /// Freeze every process in the container (`docker pause`) without stopping it
+    pub async fn pause(&self) -> Result<()> {
+        self.container.pause().await.with_context(|| {
+            format!(
+                "Failed to pause bitcoin container: {}",
+                self.config.container_name
+            )
+        })
+    }
+
+    pub async fn unpause(&self) -> Result<()> {
+        self.container.unpause().await.with_context(|| {
+            format!(
+                "Failed to unpause bitcoin container: {}",
+                self.config.container_name
             )
         })
     }
@@ -147,50 +514,40 @@ async fn start_container(config: &BitcoinContainerConfig) -> Result<ContainerAsy
     );
 
     // PLEASE DO NOT REMOVE THIS LOG CONSUMER (yet)
-    let log_consumer = |log_frame: &LogFrame| match log_frame {
-        LogFrame::StdOut(bytes) => {
-            let output = String::from_utf8_lossy(bytes);
-            tracing::info!("bitcoind> {}", output.trim());
-        }
-        LogFrame::StdErr(bytes) => {
-            let output = String::from_utf8_lossy(bytes);
-            tracing::info!("bitcoind> {}", output.trim());
-        }
-    };
-
-    // Build command args conditionally based on network mode
-    let mut cmd_args = vec![
-        "bitcoind".to_string(),
-        "-datadir=/var/lib/bitcoin-core".to_string(),
-        "-fallbackfee=0.00000001".to_string(),
-        "-printtoconsole".to_string(),
-    ];
-
-    // Add network flag only if it's not empty (mainnet has no flag)
-    let network_flag = config.bitcoin_network_flag();
-    if !network_flag.is_empty() {
-        cmd_args.push(network_flag.to_string());
-    }
-
-    cmd_args.extend_from_slice(&[
-        "-rpcallowip=0.0.0.0/0".to_string(),
-        "-rpcbind=0.0.0.0".to_string(),
-        format!("-rpcport={}", config.rpc_port),
-        format!("-rpcuser={}", config.rpc_user),
-        format!("-rpcpassword={}", config.rpc_password),
-    ]);
-
-    let container = GenericImage::new(&config.image_name, &config.image_tag)
+    let container_id = Arc::new(OnceLock::new());
+    let log_consumer = container_log_consumer("bitcoind", container_id.clone());
+
+    let mut request = GenericImage::new(&config.image_name, &config.image_tag)
         .with_mapped_port(config.rpc_port, ContainerPort::Tcp(config.rpc_port))
         .with_container_name(&config.container_name)
         .with_startup_timeout(config.startup_timeout)
         .with_log_consumer(log_consumer)
-        .with_env_var("BITCOIN_DATA", "/var/lib/bitcoin-core")
-        .with_cmd(cmd_args)
+        .with_cmd(config.bitcoind_command());
+
+    for (key, value) in config.env() {
+        request = request.with_env_var(key, value);
+    }
+
+    if let Some(bitcoin_conf) = &config.bitcoin_conf {
+        request =
+            request.with_copy_to(config.bitcoin_conf_path(), bitcoin_conf.as_bytes().to_vec());
+    }
+
+    if let BitcoinRpcAuth::CookieFile { host_dir } = &config.rpc_auth {
+        prepare_cookie_dir(host_dir)?;
+        request = request.with_mount(Mount::bind_mount(
+            host_dir.to_string_lossy(),
+            COOKIE_CONTAINER_DIR,
+        ));
+    }
+
+    let container = request
         .start()
         .await
         .context("Failed to start Bitcoin container")?;
 
+    let _ = container_id.set(container.id().to_string());
+
     tracing::debug!(
         "Started bitcoin container: {} (image: {}:{})",
         config.container_name,
@@ -201,10 +558,62 @@ async fn start_container(config: &BitcoinContainerConfig) -> Result<ContainerAsy
     Ok(container)
 }
 
+/// Create the host side of the cookie mount, removing any stale cookie from a previous run
+fn prepare_cookie_dir(host_dir: &std::path::Path) -> Result<()> {
+    std::fs::create_dir_all(host_dir)
+        .with_context(|| format!("Failed to create cookie dir {}", host_dir.display()))?;
+
+    let stale_cookie = host_dir.join(COOKIE_FILE_NAME);
+    if stale_cookie.exists() {
+        std::fs::remove_file(&stale_cookie)
+            .with_context(|| format!("Failed to remove stale cookie {}", stale_cookie.display()))?;
+    }
+
+    // bitcoind runs as an unprivileged user inside the container and must be able to write here
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(host_dir, std::fs::Permissions::from_mode(0o777))
+            .with_context(|| format!("Failed to open up cookie dir {}", host_dir.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Create a wallet via the raw `createwallet` call, since `RpcApi::create_wallet`
+/// doesn't expose the `descriptors` argument.
+fn call_createwallet(
+    client: &Client,
+    name: &str,
+    descriptors: bool,
+) -> bitcoincore_rpc::Result<()> {
+    client.call::<serde_json::Value>(
+        "createwallet",
+        &[
+            name.into(),        // wallet_name
+            false.into(),       // disable_private_keys
+            false.into(),       // blank
+            "".into(),          // passphrase
+            false.into(),       // avoid_reuse
+            descriptors.into(), // descriptors
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Build an RPC client scoped to a single wallet (`/wallet/<name>`)
+fn wallet_client(config: &BitcoinContainerConfig, wallet_name: &str) -> Result<Client> {
+    let rpc_url = config.local_network_wallet_rpc_url(wallet_name);
+
+    Client::new(&rpc_url, config.into())
+        .with_context(|| format!("Failed to create rpc_client for {}", rpc_url))
+}
+
 /// Wait for the RPC server to be ready using exponential backoff
 // TODO why can't we just accept a client here?
 async fn wait_for_rpc_ready(rpc_url: &str, config: &BitcoinContainerConfig) -> Result<()> {
-    let backoff = ExponentialBackoff::default();
+    let backoff = config.retry_policy.backoff();
     let rpc_url = rpc_url.to_string();
     let auth = bitcoincore_rpc::Auth::from(config);
 
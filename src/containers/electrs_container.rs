@@ -0,0 +1,167 @@
+//! electrs (the Esplora flavour) indexing the environment's bitcoind, for wallets under test
+//! that speak Esplora HTTP or Electrum rather than Titan. Enabled with
+//! `TestRunnerConfig::electrs`, reached with `TestContext::electrs`.
+
+use anyhow::{Context, Result};
+use backoff::future::retry;
+use futures::future::BoxFuture;
+use testcontainers::{core::ContainerPort, ContainerRequest, GenericImage, ImageExt};
+
+use super::RetryPolicy;
+use crate::{
+    network_mode::ArchNetworkMode, ServiceEndpoints, ServiceHandle, TestRunnerConfig, TestService,
+};
+
+pub const DEFAULT_ELECTRS_SERVICE_NAME: &str = "electrs";
+/// An image with the Esplora flavour of electrs as `electrs` on its PATH
+pub const DEFAULT_ELECTRS_IMAGE_NAME: &str = "mempool/electrs";
+pub const DEFAULT_ELECTRS_IMAGE_TAG: &str = "latest";
+pub const DEFAULT_ELECTRS_HTTP_PORT: u16 = 3002;
+pub const DEFAULT_ELECTRS_ELECTRUM_PORT: u16 = 60401;
+
+const DB_DIR: &str = "/electrs";
+
+#[derive(Debug, Clone)]
+pub struct ElectrsContainer {
+    pub name: String,
+    pub image_name: String,
+    pub image_tag: String,
+    /// Esplora HTTP API port, on the host and in the container
+    pub http_port: u16,
+    /// Electrum protocol port, on the host and in the container
+    pub electrum_port: u16,
+    pub network_mode: ArchNetworkMode,
+    /// How the HTTP readiness check is retried while electrs starts
+    pub retry_policy: RetryPolicy,
+    /// Extra arguments appended to the generated `electrs` command
+    pub extra_args: Vec<String>,
+}
+
+impl Default for ElectrsContainer {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_ELECTRS_SERVICE_NAME.to_string(),
+            image_name: DEFAULT_ELECTRS_IMAGE_NAME.to_string(),
+            image_tag: DEFAULT_ELECTRS_IMAGE_TAG.to_string(),
+            http_port: DEFAULT_ELECTRS_HTTP_PORT,
+            electrum_port: DEFAULT_ELECTRS_ELECTRUM_PORT,
+            network_mode: ArchNetworkMode::default(),
+            retry_policy: RetryPolicy::default(),
+            extra_args: Vec::new(),
+        }
+    }
+}
+
+impl From<TestRunnerConfig> for ElectrsContainer {
+    fn from(config: TestRunnerConfig) -> Self {
+        Self {
+            network_mode: config.network_mode,
+            retry_policy: config.retry_policy,
+            ..ElectrsContainer::default()
+        }
+    }
+}
+
+impl ElectrsContainer {
+    /// Esplora HTTP API base URL, e.g. `{esplora_url}/blocks/tip/height`
+    pub fn esplora_url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.http_port)
+    }
+
+    pub fn electrum_address(&self) -> String {
+        format!("127.0.0.1:{}", self.electrum_port)
+    }
+
+    pub fn docker_network_esplora_url(&self) -> String {
+        format!("http://host.docker.internal:{}", self.http_port)
+    }
+
+    pub fn docker_network_electrum_address(&self) -> String {
+        format!("host.docker.internal:{}", self.electrum_port)
+    }
+
+    /// electrs' `--network` value
+    pub fn electrs_network(&self) -> &'static str {
+        match self.network_mode.bitcoin_network() {
+            bitcoin::Network::Bitcoin => "mainnet",
+            bitcoin::Network::Testnet => "testnet",
+            bitcoin::Network::Testnet4 => "testnet4",
+            bitcoin::Network::Signet => "signet",
+            _ => "regtest",
+        }
+    }
+
+    /// The `electrs` arguments, indexing the bitcoind of `endpoints` over RPC
+    pub fn electrs_args(&self, endpoints: &ServiceEndpoints) -> Vec<String> {
+        let bitcoind = &endpoints.docker;
+        let daemon_rpc_addr = bitcoind
+            .bitcoin_rpc_url
+            .trim_start_matches("http://")
+            .to_string();
+
+        let mut args = vec![
+            format!("--network={}", self.electrs_network()),
+            format!("--daemon-rpc-addr={}", daemon_rpc_addr),
+            format!(
+                "--cookie={}:{}",
+                bitcoind.bitcoin_rpc_user, bitcoind.bitcoin_rpc_password
+            ),
+            // there's no blocks dir to read from, so blocks come over RPC
+            "--jsonrpc-import".to_string(),
+            format!("--db-dir={}", DB_DIR),
+            format!("--http-addr=0.0.0.0:{}", self.http_port),
+            format!("--electrum-rpc-addr=0.0.0.0:{}", self.electrum_port),
+            "-vv".to_string(),
+        ];
+        args.extend(self.extra_args.iter().cloned());
+
+        args
+    }
+
+    async fn wait_for_http_ready(&self) -> Result<()> {
+        let http_client = reqwest::Client::new();
+        let tip_url = format!("{}/blocks/tip/height", self.esplora_url());
+
+        retry(self.retry_policy.backoff(), || async {
+            match http_client
+                .get(&tip_url)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+            {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    tracing::debug!("Electrs not ready yet: {}", e);
+                    Err(backoff::Error::transient(anyhow::anyhow!(
+                        "Electrs not ready: {}",
+                        e
+                    )))
+                }
+            }
+        })
+        .await
+        .context("Electrs failed to become ready within timeout")
+    }
+}
+
+impl TestService for ElectrsContainer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn container(&self, endpoints: &ServiceEndpoints) -> Result<ContainerRequest<GenericImage>> {
+        Ok(GenericImage::new(&self.image_name, &self.image_tag)
+            .with_entrypoint("electrs")
+            .with_mapped_port(self.http_port, ContainerPort::Tcp(self.http_port))
+            .with_mapped_port(self.electrum_port, ContainerPort::Tcp(self.electrum_port))
+            .with_cmd(self.electrs_args(endpoints)))
+    }
+
+    fn wait_until_ready<'a>(&'a self, _service: &'a ServiceHandle) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            self.wait_for_http_ready().await?;
+            tracing::info!("Electrs is ready at {}", self.esplora_url());
+            Ok(())
+        })
+    }
+}
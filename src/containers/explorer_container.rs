@@ -0,0 +1,135 @@
+//! A block explorer UI over the environment's bitcoind (and electrs, when enabled), for
+//! debugging sessions: it only runs in `TestRunner::hold` and `keep_on_failure` sessions, with
+//! its URL printed next to the other endpoints. Enabled with `TestRunnerConfig::explorer`.
+//!
+//! The Arch side has no explorer image yet; use the printed validator and Titan endpoints.
+
+use anyhow::{Context, Result};
+use backoff::future::retry;
+use futures::future::BoxFuture;
+use testcontainers::{core::ContainerPort, ContainerRequest, GenericImage, ImageExt};
+
+use super::{ElectrsContainer, RetryPolicy};
+use crate::{ServiceEndpoints, ServiceHandle, TestRunnerConfig, TestService};
+
+pub const DEFAULT_EXPLORER_SERVICE_NAME: &str = "explorer";
+pub const DEFAULT_EXPLORER_IMAGE_NAME: &str = "getumbrel/btc-rpc-explorer";
+pub const DEFAULT_EXPLORER_IMAGE_TAG: &str = "v3.4.0";
+pub const DEFAULT_EXPLORER_PORT: u16 = 3003;
+
+#[derive(Debug, Clone)]
+pub struct ExplorerContainer {
+    pub name: String,
+    pub image_name: String,
+    pub image_tag: String,
+    /// UI port, on the host and in the container
+    pub port: u16,
+    /// Electrum server for address pages, as reached from the container; `None` leaves them out
+    pub electrum_address: Option<String>,
+    /// How the UI readiness check is retried while the explorer starts
+    pub retry_policy: RetryPolicy,
+}
+
+impl Default for ExplorerContainer {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_EXPLORER_SERVICE_NAME.to_string(),
+            image_name: DEFAULT_EXPLORER_IMAGE_NAME.to_string(),
+            image_tag: DEFAULT_EXPLORER_IMAGE_TAG.to_string(),
+            port: DEFAULT_EXPLORER_PORT,
+            electrum_address: None,
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+}
+
+impl From<TestRunnerConfig> for ExplorerContainer {
+    fn from(config: TestRunnerConfig) -> Self {
+        Self {
+            electrum_address: config
+                .electrs
+                .then(|| ElectrsContainer::from(config.clone()).docker_network_electrum_address()),
+            retry_policy: config.retry_policy,
+            ..ExplorerContainer::default()
+        }
+    }
+}
+
+impl ExplorerContainer {
+    pub fn url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+
+    pub fn docker_network_url(&self) -> String {
+        format!("http://host.docker.internal:{}", self.port)
+    }
+
+    async fn wait_for_ui_ready(&self) -> Result<()> {
+        let http_client = reqwest::Client::new();
+
+        retry(self.retry_policy.backoff(), || async {
+            match http_client
+                .get(self.url())
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+            {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    tracing::debug!("Explorer not ready yet: {}", e);
+                    Err(backoff::Error::transient(anyhow::anyhow!(
+                        "Explorer not ready: {}",
+                        e
+                    )))
+                }
+            }
+        })
+        .await
+        .context("Explorer failed to become ready within timeout")
+    }
+}
+
+impl TestService for ExplorerContainer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn container(&self, endpoints: &ServiceEndpoints) -> Result<ContainerRequest<GenericImage>> {
+        let bitcoind = &endpoints.docker;
+        let (scheme, address) = bitcoind
+            .bitcoin_rpc_url
+            .split_once("://")
+            .context("Malformed bitcoind RPC URL")?;
+        let bitcoind_uri = format!(
+            "{}://{}:{}@{}",
+            scheme, bitcoind.bitcoin_rpc_user, bitcoind.bitcoin_rpc_password, address
+        );
+
+        let mut request = GenericImage::new(&self.image_name, &self.image_tag)
+            .with_mapped_port(self.port, ContainerPort::Tcp(self.port))
+            .with_env_var("BTCEXP_HOST", "0.0.0.0")
+            .with_env_var("BTCEXP_PORT", self.port.to_string())
+            .with_env_var("BTCEXP_BITCOIND_URI", bitcoind_uri)
+            .with_env_var("BTCEXP_NO_RATES", "true")
+            .with_env_var("BTCEXP_PRIVACY_MODE", "true");
+
+        if let Some(electrum_address) = &self.electrum_address {
+            request = request
+                .with_env_var("BTCEXP_ADDRESS_API", "electrum")
+                .with_env_var(
+                    "BTCEXP_ELECTRUM_SERVERS",
+                    format!("tcp://{}", electrum_address),
+                );
+        }
+
+        Ok(request)
+    }
+
+    fn wait_until_ready<'a>(&'a self, _service: &'a ServiceHandle) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            self.wait_for_ui_ready().await?;
+            tracing::info!("Explorer is ready at {}", self.url());
+            Ok(())
+        })
+    }
+}
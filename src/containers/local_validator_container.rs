@@ -1,15 +1,27 @@
-use std::time::Duration;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
 
 use anyhow::{Context, Result};
 use arch_sdk::AsyncArchRpcClient;
-use backoff::{future::retry, ExponentialBackoff};
+use backoff::future::retry;
 use testcontainers::{
-    core::{logs::LogFrame, ContainerPort},
+    core::{ContainerPort, Mount},
     runners::AsyncRunner,
     ContainerAsync, GenericImage, ImageExt,
 };
 
-use super::titan_container::TitanContainerConfig;
+use super::{
+    container_log_consumer, exec_in_container, titan_container::TitanContainerConfig, RetryPolicy,
+};
+use crate::{
+    error::{ArchTestingError, ArchTestingResult},
+    exec::{command_args, ExecOutput},
+    network_mode::ArchNetworkMode,
+};
 
 pub const DEFAULT_CONTAINER_NAME: &str = "arch-testing-local-validator-container";
 pub const DEFAULT_IMAGE_NAME: &str = "ghcr.io/arch-network/local_validator";
@@ -18,6 +30,9 @@ pub const DEFAULT_RPC_PORT: u16 = 9002;
 pub const DEFAULT_WEBSOCKET_PORT: u16 = 29002;
 pub const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
 
+/// Where the validator keeps its data inside the container, when `data_dir` is set
+pub const CONTAINER_DATA_DIR: &str = "/arch_data";
+
 #[derive(Debug, Clone)]
 pub struct LocalValidatorContainerConfig {
     pub container_name: String,
@@ -26,6 +41,18 @@ pub struct LocalValidatorContainerConfig {
     pub rpc_port: u16,
     pub websocket_port: u16,
     pub startup_timeout: Duration,
+    /// How the RPC readiness check is retried while the validator starts
+    pub retry_policy: RetryPolicy,
+    pub network_mode: ArchNetworkMode,
+
+    /// Extra arguments appended to the generated `local_validator` command (feature flags etc.)
+    pub extra_args: Vec<String>,
+    /// Extra environment variables, merged over the defaults (e.g. `RUST_LOG`)
+    pub extra_env: HashMap<String, String>,
+
+    /// Host directory bind-mounted as the validator's data directory, so its on-disk state
+    /// outlives the container
+    pub data_dir: Option<PathBuf>,
 }
 
 impl Default for LocalValidatorContainerConfig {
@@ -37,6 +64,11 @@ impl Default for LocalValidatorContainerConfig {
             rpc_port: DEFAULT_RPC_PORT,
             websocket_port: DEFAULT_WEBSOCKET_PORT,
             startup_timeout: DEFAULT_STARTUP_TIMEOUT,
+            retry_policy: RetryPolicy::default(),
+            network_mode: ArchNetworkMode::default(),
+            extra_args: Vec::new(),
+            extra_env: HashMap::new(),
+            data_dir: None,
         }
     }
 }
@@ -57,6 +89,46 @@ impl LocalValidatorContainerConfig {
     pub fn docker_network_websocket_url(&self) -> String {
         format!("ws://host.docker.internal:{}", self.websocket_port)
     }
+
+    /// The command the validator runs with inside the container, pointed at `titan_config`'s
+    /// Titan
+    pub fn local_validator_command(&self, titan_config: &TitanContainerConfig) -> Vec<String> {
+        let mut cmd_args = vec![
+            "/bin/local_validator".to_string(),
+            format!(
+                "--network-mode={}",
+                self.network_mode.validator_network_mode()
+            ),
+            "--rpc-bind-ip=0.0.0.0".to_string(),
+            format!("--rpc-bind-port={}", self.rpc_port),
+            format!(
+                "--titan-endpoint={}",
+                titan_config.docker_network_http_url()
+            ),
+            format!(
+                "--titan-socket-endpoint={}",
+                titan_config.docker_network_tcp_address()
+            ),
+        ];
+        if self.data_dir.is_some() {
+            cmd_args.push(format!("--data-dir={}", CONTAINER_DATA_DIR));
+        }
+        cmd_args.extend(self.extra_args.iter().cloned());
+
+        cmd_args
+    }
+
+    /// Environment of the validator container
+    pub fn env(&self) -> BTreeMap<String, String> {
+        let mut env = BTreeMap::from([("RUST_BACKTRACE".to_string(), "full".to_string())]);
+        env.extend(
+            self.extra_env
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+
+        env
+    }
 }
 
 pub struct LocalValidatorContainer {
@@ -69,12 +141,21 @@ impl LocalValidatorContainer {
     pub async fn start(
         config: &LocalValidatorContainerConfig,
         titan_config: &TitanContainerConfig,
+    ) -> ArchTestingResult<Self> {
+        Self::try_start(config, titan_config)
+            .await
+            .map_err(|e| ArchTestingError::container_start_failed("validator", e))
+    }
+
+    async fn try_start(
+        config: &LocalValidatorContainerConfig,
+        titan_config: &TitanContainerConfig,
     ) -> Result<Self> {
         let container = start_local_validator_container(config, titan_config).await?;
         let config = config.clone();
         let client = AsyncArchRpcClient::new(&config.local_network_rpc_url());
 
-        wait_for_rpc_ready(&client).await?;
+        wait_for_rpc_ready(&client, &config.retry_policy).await?;
 
         Ok(Self {
             container,
@@ -83,7 +164,7 @@ impl LocalValidatorContainer {
         })
     }
 
-    pub async fn shutdown(&self) -> Result<()> {
+    pub async fn shutdown(&self) -> ArchTestingResult<()> {
         tracing::trace!(
             "Stopping local validator container: {} (image: {}:{})",
             self.config.container_name,
@@ -92,12 +173,40 @@ impl LocalValidatorContainer {
         );
 
         self.container.stop().await.map_err(|shutdown_err| {
-            anyhow::anyhow!(
+            let source = anyhow::anyhow!(
                 "Failed to stop local validator container: {} (image: {}:{})\nShutdown error: {}",
                 self.config.container_name,
                 self.config.image_name,
                 self.config.image_tag,
                 shutdown_err
+            );
+            ArchTestingError::teardown_failed("validator", source)
+        })
+    }
+
+    /// Freeze every process in the container (`docker pause`) without stopping it
+    pub async fn pause(&self) -> Result<()> {
+        self.container.pause().await.with_context(|| {
+            format!(
+                "Failed to pause local validator container: {}",
+                self.config.container_name
+            )
+        })
+    }
+
+    /// Run `cmd` in the container
+    pub async fn exec(
+        &self,
+        cmd: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<ExecOutput> {
+        exec_in_container(&self.container, command_args(cmd)).await
+    }
+
+    pub async fn unpause(&self) -> Result<()> {
+        self.container.unpause().await.with_context(|| {
+            format!(
+                "Failed to unpause local validator container: {}",
+                self.config.container_name
             )
         })
     }
@@ -123,21 +232,10 @@ pub(super) async fn start_local_validator_container(
     );
 
     // PLEASE DO NOT REMOVE THIS LOG CONSUMER (yet)
-    let log_consumer = |log_frame: &LogFrame| match log_frame {
-        LogFrame::StdOut(bytes) => {
-            let output = String::from_utf8_lossy(bytes);
-            tracing::info!("local_validator> {}", output.trim());
-        }
-        LogFrame::StdErr(bytes) => {
-            let output = String::from_utf8_lossy(bytes);
-            tracing::info!("local_validator> {}", output.trim());
-        }
-    };
+    let container_id = Arc::new(OnceLock::new());
+    let log_consumer = container_log_consumer("local_validator", container_id.clone());
 
-    let titan_endpoint = titan_config.docker_network_http_url();
-    let titan_socket_endpoint = titan_config.docker_network_tcp_address();
-
-    let container = GenericImage::new(&config.image_name, &config.image_tag)
+    let mut request = GenericImage::new(&config.image_name, &config.image_tag)
         .with_mapped_port(config.rpc_port, ContainerPort::Tcp(config.rpc_port))
         .with_mapped_port(
             config.websocket_port,
@@ -146,19 +244,36 @@ pub(super) async fn start_local_validator_container(
         .with_startup_timeout(config.startup_timeout)
         .with_container_name(&config.container_name)
         .with_log_consumer(log_consumer)
-        .with_env_var("RUST_BACKTRACE", "full")
-        .with_cmd([
-            "/bin/local_validator".to_string(),
-            "--network-mode=localnet".to_string(),
-            "--rpc-bind-ip=0.0.0.0".to_string(),
-            format!("--rpc-bind-port={}", config.rpc_port),
-            format!("--titan-endpoint={}", titan_endpoint),
-            format!("--titan-socket-endpoint={}", titan_socket_endpoint),
-        ])
+        .with_cmd(config.local_validator_command(titan_config));
+
+    for (key, value) in config.env() {
+        request = request.with_env_var(key, value);
+    }
+
+    if let Some(data_dir) = &config.data_dir {
+        std::fs::create_dir_all(data_dir).with_context(|| {
+            format!("Failed to create validator data dir {}", data_dir.display())
+        })?;
+        // docker only bind-mounts absolute paths
+        let data_dir = data_dir.canonicalize().with_context(|| {
+            format!(
+                "Failed to resolve validator data dir {}",
+                data_dir.display()
+            )
+        })?;
+        request = request.with_mount(Mount::bind_mount(
+            data_dir.to_string_lossy(),
+            CONTAINER_DATA_DIR,
+        ));
+    }
+
+    let container = request
         .start()
         .await
         .context("Failed to start local validator container")?;
 
+    let _ = container_id.set(container.id().to_string());
+
     tracing::trace!(
         "Started local validator container: {} (image: {}:{})",
         config.container_name,
@@ -169,8 +284,8 @@ pub(super) async fn start_local_validator_container(
     Ok(container)
 }
 
-async fn wait_for_rpc_ready(client: &AsyncArchRpcClient) -> Result<()> {
-    retry(ExponentialBackoff::default(), || async {
+async fn wait_for_rpc_ready(client: &AsyncArchRpcClient, retry_policy: &RetryPolicy) -> Result<()> {
+    retry(retry_policy.backoff(), || async {
         match client.get_block_count().await {
             Ok(_) => {
                 tracing::info!("LocalValidator RPC server is ready!");
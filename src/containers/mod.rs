@@ -1,7 +1,95 @@
+use std::sync::{Arc, OnceLock};
+
+use anyhow::{Context, Result};
+use testcontainers::{
+    core::{logs::LogFrame, CmdWaitFor, ExecCommand},
+    ContainerAsync, GenericImage,
+};
+
+use crate::ExecOutput;
+
+pub mod async_bitcoin_client;
 pub mod bitcoin_container;
+pub mod electrs_container;
+pub mod explorer_container;
 pub mod local_validator_container;
+pub mod postgres_container;
+pub mod retry_policy;
 pub mod titan_container;
+pub mod toxiproxy_container;
 
-pub use bitcoin_container::{BitcoinContainer, BitcoinContainerConfig};
+pub use async_bitcoin_client::AsyncBitcoinClient;
+pub use bitcoin_container::{
+    BitcoinContainer, BitcoinContainerConfig, BitcoinRpcAuth, FeeScenario,
+};
+pub use electrs_container::ElectrsContainer;
+pub use explorer_container::ExplorerContainer;
 pub use local_validator_container::{LocalValidatorContainer, LocalValidatorContainerConfig};
+pub use postgres_container::PostgresContainer;
+pub use retry_policy::RetryPolicy;
 pub use titan_container::{TitanContainer, TitanContainerConfig};
+pub use toxiproxy_container::{ToxiproxyContainer, ToxiproxyContainerConfig};
+
+/// Build a log consumer that forwards container output to tracing.
+///
+/// Every line is tagged with the `component` and, once the container has started, its
+/// `container_id`. The span that is current when the consumer is built (the test run span,
+/// carrying the `run_id`) is re-entered for each line, since logs arrive on a background task.
+pub(crate) fn container_log_consumer(
+    component: impl Into<String>,
+    container_id: Arc<OnceLock<String>>,
+) -> impl Fn(&LogFrame) + Send + Sync + 'static {
+    let component = component.into();
+    let span = tracing::Span::current();
+
+    move |log_frame: &LogFrame| {
+        let bytes = match log_frame {
+            LogFrame::StdOut(bytes) => bytes,
+            LogFrame::StdErr(bytes) => bytes,
+        };
+        let output = String::from_utf8_lossy(bytes);
+        let container_id = container_id.get().map(String::as_str).unwrap_or_default();
+
+        span.in_scope(|| {
+            tracing::info!(
+                component = component.as_str(),
+                container_id,
+                "{}> {}",
+                component,
+                output.trim()
+            );
+        });
+    }
+}
+
+/// Run `cmd` inside `container` and wait for it to exit
+pub(crate) async fn exec_in_container(
+    container: &ContainerAsync<GenericImage>,
+    cmd: Vec<String>,
+) -> Result<ExecOutput> {
+    let command = cmd.join(" ");
+    let mut result = container
+        .exec(ExecCommand::new(cmd).with_cmd_ready_condition(CmdWaitFor::exit()))
+        .await
+        .with_context(|| format!("Failed to exec {} in container {}", command, container.id()))?;
+
+    let stdout = result
+        .stdout_to_vec()
+        .await
+        .with_context(|| format!("Failed to read stdout of {}", command))?;
+    let stderr = result
+        .stderr_to_vec()
+        .await
+        .with_context(|| format!("Failed to read stderr of {}", command))?;
+    let exit_code = result
+        .exit_code()
+        .await
+        .with_context(|| format!("Failed to read exit code of {}", command))?;
+
+    Ok(ExecOutput {
+        command,
+        exit_code,
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+    })
+}
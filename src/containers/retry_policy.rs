@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use backoff::{ExponentialBackoff, ExponentialBackoffBuilder};
+
+pub const DEFAULT_RETRY_INITIAL_INTERVAL: Duration = Duration::from_millis(250);
+pub const DEFAULT_RETRY_MAX_INTERVAL: Duration = Duration::from_secs(5);
+pub const DEFAULT_RETRY_MAX_ELAPSED: Duration = Duration::from_secs(60);
+
+/// How a container's readiness check is retried while it starts.
+///
+/// `backoff`'s own default keeps retrying for 15 minutes with up to a minute between attempts,
+/// long past any startup or setup timeout; keep `max_elapsed` within `startup_timeout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Delay before the first retry, doubling (with jitter) after each failed attempt
+    pub initial_interval: Duration,
+    /// Upper bound on the delay between attempts
+    pub max_interval: Duration,
+    /// Give up once this much time has passed since the first attempt
+    pub max_elapsed: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_interval: DEFAULT_RETRY_INITIAL_INTERVAL,
+            max_interval: DEFAULT_RETRY_MAX_INTERVAL,
+            max_elapsed: DEFAULT_RETRY_MAX_ELAPSED,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn backoff(&self) -> ExponentialBackoff {
+        ExponentialBackoffBuilder::new()
+            .with_initial_interval(self.initial_interval)
+            .with_max_interval(self.max_interval)
+            .with_max_elapsed_time(Some(self.max_elapsed))
+            .build()
+    }
+}
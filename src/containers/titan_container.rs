@@ -1,14 +1,23 @@
-use std::time::Duration;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
 
 use anyhow::{Context, Result};
 use testcontainers::{
-    core::{logs::LogFrame, ContainerPort, WaitFor},
+    core::{ContainerPort, WaitFor},
     runners::AsyncRunner,
     ContainerAsync, GenericImage, ImageExt,
 };
 use titan_client::TitanClient;
 
-use super::bitcoin_container::BitcoinContainerConfig;
+use super::{bitcoin_container::BitcoinContainerConfig, container_log_consumer, exec_in_container};
+use crate::{
+    error::{ArchTestingError, ArchTestingResult},
+    exec::{command_args, ExecOutput},
+    network_mode::ArchNetworkMode,
+};
 
 pub const DEFAULT_CONTAINER_NAME: &str = "arch-testing-titan-container";
 pub const DEFAULT_IMAGE_NAME: &str = "ghcr.io/saturnbtc/titan";
@@ -25,6 +34,10 @@ pub struct TitanContainerConfig {
     pub http_port: u16,
     pub tcp_port: u16,
     pub startup_timeout: Duration,
+    pub network_mode: ArchNetworkMode,
+
+    /// Extra environment variables, merged over the defaults (index options, `COMMIT_INTERVAL`, ...)
+    pub extra_env: HashMap<String, String>,
 }
 
 impl Default for TitanContainerConfig {
@@ -36,6 +49,8 @@ impl Default for TitanContainerConfig {
             http_port: DEFAULT_HTTP_PORT,
             tcp_port: DEFAULT_TCP_PORT,
             startup_timeout: DEFAULT_STARTUP_TIMEOUT,
+            network_mode: ArchNetworkMode::default(),
+            extra_env: HashMap::new(),
         }
     }
 }
@@ -67,7 +82,45 @@ impl TitanContainerConfig {
 
     /// Map ArchNetworkMode to Titan chain name
     pub fn titan_chain(&self) -> &'static str {
-        "regtest"
+        self.network_mode.titan_chain()
+    }
+
+    /// Environment of the Titan container, connecting to bitcoind with the given credentials
+    /// (see `BitcoinContainerConfig::rpc_credentials`)
+    pub fn env(
+        &self,
+        bitcoin_config: &BitcoinContainerConfig,
+        bitcoin_rpc_user: &str,
+        bitcoin_rpc_password: &str,
+    ) -> BTreeMap<String, String> {
+        let mut env = BTreeMap::from([
+            (
+                "BITCOIN_RPC_PASSWORD".to_string(),
+                bitcoin_rpc_password.to_string(),
+            ),
+            (
+                "BITCOIN_RPC_URL".to_string(),
+                bitcoin_config.docker_network_rpc_url(),
+            ),
+            (
+                "BITCOIN_RPC_USERNAME".to_string(),
+                bitcoin_rpc_user.to_string(),
+            ),
+            ("CHAIN".to_string(), self.titan_chain().to_string()),
+            ("COMMIT_INTERVAL".to_string(), "5".to_string()),
+            ("HTTP_LISTEN".to_string(), self.docker_network_http_bind()),
+            ("RUST_BACKTRACE".to_string(), "full".to_string()),
+            ("TCP_ADDRESS".to_string(), self.docker_network_tcp_bind()),
+        ]);
+
+        // merged last, so these override the defaults above
+        env.extend(
+            self.extra_env
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+
+        env
     }
 }
 
@@ -82,6 +135,15 @@ impl TitanContainer {
     pub async fn start(
         bitcoin_config: &BitcoinContainerConfig,
         titan_config: &TitanContainerConfig,
+    ) -> ArchTestingResult<Self> {
+        Self::try_start(bitcoin_config, titan_config)
+            .await
+            .map_err(|e| ArchTestingError::container_start_failed("titan", e))
+    }
+
+    async fn try_start(
+        bitcoin_config: &BitcoinContainerConfig,
+        titan_config: &TitanContainerConfig,
     ) -> Result<Self> {
         let container = start_titan_container(bitcoin_config, titan_config).await?;
         let client = TitanClient::new(&titan_config.local_network_http_url());
@@ -94,7 +156,7 @@ impl TitanContainer {
         })
     }
 
-    pub async fn shutdown(&self) -> Result<()> {
+    pub async fn shutdown(&self) -> ArchTestingResult<()> {
         tracing::trace!(
             "Stopping titan container: {} (image: {}:{})",
             self.config.container_name,
@@ -103,12 +165,40 @@ impl TitanContainer {
         );
 
         self.container.stop().await.map_err(|shutdown_err| {
-            anyhow::anyhow!(
+            let source = anyhow::anyhow!(
                 "Failed to stop titan container: {} (image: {}:{})\nShutdown error: {}",
                 self.config.container_name,
                 self.config.image_name,
                 self.config.image_tag,
                 shutdown_err
+            );
+            ArchTestingError::teardown_failed("titan", source)
+        })
+    }
+
+    /// Freeze every process in the container (`docker pause`) without stopping it
+    pub async fn pause(&self) -> Result<()> {
+        self.container.pause().await.with_context(|| {
+            format!(
+                "Failed to pause titan container: {}",
+                self.config.container_name
+            )
+        })
+    }
+
+    /// Run `cmd` in the container
+    pub async fn exec(
+        &self,
+        cmd: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<ExecOutput> {
+        exec_in_container(&self.container, command_args(cmd)).await
+    }
+
+    pub async fn unpause(&self) -> Result<()> {
+        self.container.unpause().await.with_context(|| {
+            format!(
+                "Failed to unpause titan container: {}",
+                self.config.container_name
             )
         })
     }
@@ -126,23 +216,17 @@ pub(super) async fn start_titan_container(
     );
 
     // PLEASE DO NOT REMOVE THIS LOG CONSUMER (yet)
-    let log_consumer = |log_frame: &LogFrame| match log_frame {
-        LogFrame::StdOut(bytes) => {
-            let output = String::from_utf8_lossy(bytes);
-            tracing::info!("titand> {}", output.trim());
-        }
-        LogFrame::StdErr(bytes) => {
-            let output = String::from_utf8_lossy(bytes);
-            tracing::info!("titand> {}", output.trim());
-        }
-    };
+    let container_id = Arc::new(OnceLock::new());
+    let log_consumer = container_log_consumer("titand", container_id.clone());
+
+    let (bitcoin_rpc_user, bitcoin_rpc_password) = bitcoin_config.rpc_credentials()?;
 
     // consider introducing an enum so callers can decide what to wait for
     let wait_for_synced_to_tip = WaitFor::message_on_stdout(
         "Synced to tip", // logged by titan when it's caught up with bitcoind
     );
 
-    let container = GenericImage::new(&titan_config.image_name, &titan_config.image_tag)
+    let mut request = GenericImage::new(&titan_config.image_name, &titan_config.image_tag)
         .with_wait_for(wait_for_synced_to_tip)
         .with_mapped_port(
             titan_config.tcp_port,
@@ -154,19 +238,19 @@ pub(super) async fn start_titan_container(
         )
         .with_startup_timeout(titan_config.startup_timeout)
         .with_container_name(&titan_config.container_name)
-        .with_log_consumer(log_consumer)
-        .with_env_var("BITCOIN_RPC_PASSWORD", &bitcoin_config.rpc_password)
-        .with_env_var("BITCOIN_RPC_URL", &bitcoin_config.docker_network_rpc_url())
-        .with_env_var("BITCOIN_RPC_USERNAME", &bitcoin_config.rpc_user)
-        .with_env_var("CHAIN", titan_config.titan_chain())
-        .with_env_var("COMMIT_INTERVAL", "5")
-        .with_env_var("HTTP_LISTEN", &titan_config.docker_network_http_bind())
-        .with_env_var("RUST_BACKTRACE", "full")
-        .with_env_var("TCP_ADDRESS", &titan_config.docker_network_tcp_bind())
+        .with_log_consumer(log_consumer);
+
+    for (key, value) in titan_config.env(bitcoin_config, &bitcoin_rpc_user, &bitcoin_rpc_password) {
+        request = request.with_env_var(key, value);
+    }
+
+    let container = request
         .start()
         .await
         .context("Failed to start Titan container")?;
 
+    let _ = container_id.set(container.id().to_string());
+
     tracing::trace!(
         "Started titan container: {} (image: {}:{})",
         titan_config.container_name,
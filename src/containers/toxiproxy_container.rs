@@ -0,0 +1,295 @@
+use std::{
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use backoff::future::retry;
+use testcontainers::{
+    core::ContainerPort, runners::AsyncRunner, ContainerAsync, GenericImage, ImageExt,
+};
+
+use super::{
+    bitcoin_container::BitcoinContainerConfig, container_log_consumer, exec_in_container,
+    titan_container::TitanContainerConfig, RetryPolicy,
+};
+use crate::{
+    error::{ArchTestingError, ArchTestingResult},
+    exec::{command_args, ExecOutput},
+};
+
+pub const DEFAULT_CONTAINER_NAME: &str = "arch-testing-toxiproxy-container";
+pub const DEFAULT_IMAGE_NAME: &str = "ghcr.io/shopify/toxiproxy";
+pub const DEFAULT_IMAGE_TAG: &str = "2.9.0";
+pub const DEFAULT_API_PORT: u16 = 8474;
+pub const DEFAULT_BITCOIN_RPC_PROXY_PORT: u16 = 28443;
+pub const DEFAULT_TITAN_HTTP_PROXY_PORT: u16 = 23030;
+pub const DEFAULT_TITAN_TCP_PROXY_PORT: u16 = 28080;
+pub const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Proxy names, as registered with toxiproxy
+pub const BITCOIN_RPC_PROXY: &str = "bitcoin_rpc";
+pub const TITAN_HTTP_PROXY: &str = "titan_http";
+pub const TITAN_TCP_PROXY: &str = "titan_tcp";
+
+/// Toxiproxy sits between the validator and Titan, and between Titan and bitcoind, so faults
+/// can be injected on those links (see `TestContext::network_faults`)
+#[derive(Debug, Clone)]
+pub struct ToxiproxyContainerConfig {
+    pub container_name: String,
+    pub image_name: String,
+    pub image_tag: String,
+    pub api_port: u16,
+    pub bitcoin_rpc_proxy_port: u16,
+    pub titan_http_proxy_port: u16,
+    pub titan_tcp_proxy_port: u16,
+    pub startup_timeout: Duration,
+    /// How the API readiness check is retried while toxiproxy starts
+    pub retry_policy: RetryPolicy,
+}
+
+impl Default for ToxiproxyContainerConfig {
+    fn default() -> Self {
+        Self {
+            container_name: DEFAULT_CONTAINER_NAME.to_string(),
+            image_name: DEFAULT_IMAGE_NAME.to_string(),
+            image_tag: DEFAULT_IMAGE_TAG.to_string(),
+            api_port: DEFAULT_API_PORT,
+            bitcoin_rpc_proxy_port: DEFAULT_BITCOIN_RPC_PROXY_PORT,
+            titan_http_proxy_port: DEFAULT_TITAN_HTTP_PROXY_PORT,
+            titan_tcp_proxy_port: DEFAULT_TITAN_TCP_PROXY_PORT,
+            startup_timeout: DEFAULT_STARTUP_TIMEOUT,
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+}
+
+impl ToxiproxyContainerConfig {
+    pub fn local_network_api_url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.api_port)
+    }
+
+    pub fn docker_network_api_url(&self) -> String {
+        format!("http://host.docker.internal:{}", self.api_port)
+    }
+
+    /// `bitcoin_config` as Titan should see it: bitcoind RPC reached through the proxy
+    pub fn proxied_bitcoin_config(
+        &self,
+        bitcoin_config: &BitcoinContainerConfig,
+    ) -> BitcoinContainerConfig {
+        BitcoinContainerConfig {
+            rpc_port: self.bitcoin_rpc_proxy_port,
+            ..bitcoin_config.clone()
+        }
+    }
+
+    /// `titan_config` as the validator should see it: both Titan endpoints reached through
+    /// the proxy
+    pub fn proxied_titan_config(
+        &self,
+        titan_config: &TitanContainerConfig,
+    ) -> TitanContainerConfig {
+        TitanContainerConfig {
+            http_port: self.titan_http_proxy_port,
+            tcp_port: self.titan_tcp_proxy_port,
+            ..titan_config.clone()
+        }
+    }
+}
+
+pub struct ToxiproxyContainer {
+    pub container: ContainerAsync<GenericImage>,
+
+    config: ToxiproxyContainerConfig,
+}
+
+impl ToxiproxyContainer {
+    /// Start toxiproxy and register its proxies. Upstreams are connected lazily, so this can
+    /// (and must) happen before Titan and the validator start.
+    pub async fn start(
+        config: &ToxiproxyContainerConfig,
+        bitcoin_config: &BitcoinContainerConfig,
+        titan_config: &TitanContainerConfig,
+    ) -> ArchTestingResult<Self> {
+        Self::try_start(config, bitcoin_config, titan_config)
+            .await
+            .map_err(|e| ArchTestingError::container_start_failed("toxiproxy", e))
+    }
+
+    async fn try_start(
+        config: &ToxiproxyContainerConfig,
+        bitcoin_config: &BitcoinContainerConfig,
+        titan_config: &TitanContainerConfig,
+    ) -> Result<Self> {
+        let container = start_toxiproxy_container(config).await?;
+        let config = config.clone();
+        let http_client = reqwest::Client::new();
+
+        wait_for_api_ready(&http_client, &config).await?;
+
+        let proxies = [
+            (
+                BITCOIN_RPC_PROXY,
+                config.bitcoin_rpc_proxy_port,
+                bitcoin_config.rpc_port,
+            ),
+            (
+                TITAN_HTTP_PROXY,
+                config.titan_http_proxy_port,
+                titan_config.http_port,
+            ),
+            (
+                TITAN_TCP_PROXY,
+                config.titan_tcp_proxy_port,
+                titan_config.tcp_port,
+            ),
+        ];
+
+        for (name, listen_port, upstream_port) in proxies {
+            let proxy = serde_json::json!({
+                "name": name,
+                "listen": format!("0.0.0.0:{}", listen_port),
+                "upstream": format!("host.docker.internal:{}", upstream_port),
+                "enabled": true,
+            });
+
+            http_client
+                .post(format!("{}/proxies", config.local_network_api_url()))
+                .json(&proxy)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .with_context(|| format!("Failed to create toxiproxy proxy {}", name))?;
+        }
+
+        Ok(Self { container, config })
+    }
+
+    pub async fn shutdown(&self) -> ArchTestingResult<()> {
+        tracing::trace!(
+            "Stopping toxiproxy container: {} (image: {}:{})",
+            self.config.container_name,
+            self.config.image_name,
+            self.config.image_tag
+        );
+
+        self.container.stop().await.map_err(|shutdown_err| {
+            let source = anyhow::anyhow!(
+                "Failed to stop toxiproxy container: {} (image: {}:{})\nShutdown error: {}",
+                self.config.container_name,
+                self.config.image_name,
+                self.config.image_tag,
+                shutdown_err
+            );
+            ArchTestingError::teardown_failed("toxiproxy", source)
+        })
+    }
+
+    /// Freeze every process in the container (`docker pause`) without stopping it
+    pub async fn pause(&self) -> Result<()> {
+        self.container.pause().await.with_context(|| {
+            format!(
+                "Failed to pause toxiproxy container: {}",
+                self.config.container_name
+            )
+        })
+    }
+
+    /// Run `cmd` in the container, e.g. `toxiproxy-cli`
+    pub async fn exec(
+        &self,
+        cmd: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<ExecOutput> {
+        exec_in_container(&self.container, command_args(cmd)).await
+    }
+
+    pub async fn unpause(&self) -> Result<()> {
+        self.container.unpause().await.with_context(|| {
+            format!(
+                "Failed to unpause toxiproxy container: {}",
+                self.config.container_name
+            )
+        })
+    }
+}
+
+pub(super) async fn start_toxiproxy_container(
+    config: &ToxiproxyContainerConfig,
+) -> Result<ContainerAsync<GenericImage>> {
+    tracing::trace!(
+        "Starting toxiproxy container: {} (image: {}:{})",
+        config.container_name,
+        config.image_name,
+        config.image_tag
+    );
+
+    let container_id = Arc::new(OnceLock::new());
+    let log_consumer = container_log_consumer("toxiproxy", container_id.clone());
+
+    let mut request = GenericImage::new(&config.image_name, &config.image_tag)
+        .with_mapped_port(config.api_port, ContainerPort::Tcp(config.api_port))
+        .with_startup_timeout(config.startup_timeout)
+        .with_container_name(&config.container_name)
+        .with_log_consumer(log_consumer)
+        .with_cmd(vec![
+            "-host=0.0.0.0".to_string(),
+            format!("-port={}", config.api_port),
+        ]);
+
+    for port in [
+        config.bitcoin_rpc_proxy_port,
+        config.titan_http_proxy_port,
+        config.titan_tcp_proxy_port,
+    ] {
+        request = request.with_mapped_port(port, ContainerPort::Tcp(port));
+    }
+
+    let container = request
+        .start()
+        .await
+        .context("Failed to start toxiproxy container")?;
+
+    let _ = container_id.set(container.id().to_string());
+
+    tracing::trace!(
+        "Started toxiproxy container: {} (image: {}:{})",
+        config.container_name,
+        config.image_name,
+        config.image_tag
+    );
+
+    Ok(container)
+}
+
+async fn wait_for_api_ready(
+    http_client: &reqwest::Client,
+    config: &ToxiproxyContainerConfig,
+) -> Result<()> {
+    let url = format!("{}/version", config.local_network_api_url());
+
+    retry(config.retry_policy.backoff(), || async {
+        match http_client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+        {
+            Ok(_) => {
+                tracing::info!("Toxiproxy API is ready!");
+                Ok(())
+            }
+            Err(e) => {
+                tracing::debug!("Toxiproxy API not ready yet: {}", e);
+                Err(backoff::Error::transient(anyhow!("API not ready: {}", e)))
+            }
+        }
+    })
+    .await
+    .with_context(|| {
+        format!(
+            "Toxiproxy API {} failed to become ready within timeout",
+            url
+        )
+    })
+}
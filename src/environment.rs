@@ -0,0 +1,161 @@
+//! Describing a running environment to things outside the test process: the `arch-test-env`
+//! binary, a developer's own tools, etc.
+
+use std::fmt;
+
+use anyhow::Result;
+
+use crate::{
+    BitcoinContainerConfig, ExplorerContainer, LocalValidatorContainerConfig, TestRunnerConfig,
+    TitanContainerConfig, ToxiproxyContainerConfig,
+};
+
+/// Where to reach each service of an environment started from a `TestRunnerConfig`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionDetails {
+    pub bitcoin_rpc_url: String,
+    pub bitcoin_rpc_user: String,
+    pub bitcoin_rpc_password: String,
+    pub titan_http_url: String,
+    pub titan_tcp_address: String,
+    pub validator_rpc_url: String,
+    pub validator_websocket_url: String,
+    /// Set when `network_faults` is enabled
+    pub toxiproxy_api_url: Option<String>,
+    /// Set when `explorer` is enabled; it only runs in hold-open and keep-on-failure sessions
+    pub explorer_url: Option<String>,
+}
+
+impl fmt::Display for ConnectionDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bitcoin_rpc_auth = format!("{}:{}", self.bitcoin_rpc_user, self.bitcoin_rpc_password);
+        let mut lines = vec![
+            ("bitcoind rpc", &self.bitcoin_rpc_url),
+            ("bitcoind rpc auth", &bitcoin_rpc_auth),
+            ("titan http", &self.titan_http_url),
+            ("titan tcp", &self.titan_tcp_address),
+            ("validator rpc", &self.validator_rpc_url),
+            ("validator websocket", &self.validator_websocket_url),
+        ];
+        if let Some(toxiproxy_api_url) = &self.toxiproxy_api_url {
+            lines.push(("toxiproxy api", toxiproxy_api_url));
+        }
+        if let Some(explorer_url) = &self.explorer_url {
+            lines.push(("explorer", explorer_url));
+        }
+
+        for (i, (label, value)) in lines.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{:<21}{}", format!("{}:", label), value)?;
+        }
+        Ok(())
+    }
+}
+
+impl TestRunnerConfig {
+    /// Host-side addresses and credentials of the services this configuration runs.
+    ///
+    /// With cookie auth the bitcoind credentials are read from the cookie file, so call this
+    /// once the environment is up.
+    pub fn connection_details(&self) -> Result<ConnectionDetails> {
+        let bitcoin_config = BitcoinContainerConfig::from(self.clone());
+        let titan_config = TitanContainerConfig::from(self.clone());
+        let validator_config = LocalValidatorContainerConfig::from(self.clone());
+        let (bitcoin_rpc_user, bitcoin_rpc_password) = bitcoin_config.rpc_credentials()?;
+
+        Ok(ConnectionDetails {
+            bitcoin_rpc_url: bitcoin_config.local_network_rpc_url(),
+            bitcoin_rpc_user,
+            bitcoin_rpc_password,
+            titan_http_url: titan_config.local_network_http_url(),
+            titan_tcp_address: titan_config.local_network_tcp_address(),
+            validator_rpc_url: validator_config.local_network_rpc_url(),
+            validator_websocket_url: validator_config.local_network_websocket_url(),
+            toxiproxy_api_url: self
+                .network_faults
+                .then(|| ToxiproxyContainerConfig::from(self.clone()).local_network_api_url()),
+            explorer_url: self
+                .explorer
+                .then(|| ExplorerContainer::from(self.clone()).url()),
+        })
+    }
+
+    /// `connection_details`, as reached from inside another container (through
+    /// `host.docker.internal`)
+    pub fn docker_network_connection_details(&self) -> Result<ConnectionDetails> {
+        let bitcoin_config = BitcoinContainerConfig::from(self.clone());
+        let titan_config = TitanContainerConfig::from(self.clone());
+        let validator_config = LocalValidatorContainerConfig::from(self.clone());
+        let (bitcoin_rpc_user, bitcoin_rpc_password) = bitcoin_config.rpc_credentials()?;
+
+        Ok(ConnectionDetails {
+            bitcoin_rpc_url: bitcoin_config.docker_network_rpc_url(),
+            bitcoin_rpc_user,
+            bitcoin_rpc_password,
+            titan_http_url: titan_config.docker_network_http_url(),
+            titan_tcp_address: titan_config.docker_network_tcp_address(),
+            validator_rpc_url: validator_config.docker_network_rpc_url(),
+            validator_websocket_url: validator_config.docker_network_websocket_url(),
+            toxiproxy_api_url: self
+                .network_faults
+                .then(|| ToxiproxyContainerConfig::from(self.clone()).docker_network_api_url()),
+            explorer_url: self
+                .explorer
+                .then(|| ExplorerContainer::from(self.clone()).docker_network_url()),
+        })
+    }
+
+    /// `(service, docker container name)` of every container this configuration starts
+    pub fn container_names(&self) -> Vec<(&'static str, String)> {
+        let mut names = vec![
+            (
+                "bitcoind",
+                BitcoinContainerConfig::from(self.clone()).container_name,
+            ),
+            (
+                "titan",
+                TitanContainerConfig::from(self.clone()).container_name,
+            ),
+            (
+                "validator",
+                LocalValidatorContainerConfig::from(self.clone()).container_name,
+            ),
+        ];
+
+        if self.network_faults {
+            names.push((
+                "toxiproxy",
+                ToxiproxyContainerConfig::from(self.clone()).container_name,
+            ));
+        }
+
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_container_names_follow_network_faults() {
+        let mut config = TestRunnerConfig::new().expect("Failed to create test config");
+        let services = |config: &TestRunnerConfig| {
+            config
+                .container_names()
+                .into_iter()
+                .map(|(service, _)| service)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(services(&config), ["bitcoind", "titan", "validator"]);
+
+        config.network_faults = true;
+        assert_eq!(
+            services(&config),
+            ["bitcoind", "titan", "validator", "toxiproxy"]
+        );
+    }
+}
@@ -0,0 +1,87 @@
+//! Typed errors for the test runner, containers and `TestContext`, so callers can match on
+//! what went wrong.
+//!
+//! Helper modules still use `anyhow` internally: their errors surface as `Other`, and an
+//! `ArchTestingError` converts into `anyhow::Error` (keeping its source chain) wherever a test
+//! returns `anyhow::Result`.
+//!
+//! ```ignore
+//! match TestRunner::try_run_with_config(config, test).await {
+//!     Err(ArchTestingError::SetupTimeout(timeout)) => eprintln!("slow docker? {:?}", timeout),
+//!     other => other?,
+//! }
+//! ```
+
+use std::time::Duration;
+
+/// Boxed source of an `ArchTestingError`
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+pub type ArchTestingResult<T> = std::result::Result<T, ArchTestingError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArchTestingError {
+    #[error("Setup timed out after {0:?}")]
+    SetupTimeout(Duration),
+
+    #[error("Test timed out after {0:?}")]
+    TestTimeout(Duration),
+
+    #[error("Failed to start {component} container")]
+    ContainerStartFailed {
+        component: &'static str,
+        #[source]
+        source: BoxError,
+    },
+
+    /// A request to `service` (validator, faucet, ...) failed before it produced an answer
+    #[error("{service} RPC request failed")]
+    RpcUnavailable {
+        service: &'static str,
+        #[source]
+        source: BoxError,
+    },
+
+    /// The transaction was rejected when submitted (`txid` is `None`) or wasn't processed in time
+    #[error("Transaction {} failed: {reason}", .txid.as_deref().unwrap_or("(not submitted)"))]
+    TransactionFailed {
+        txid: Option<String>,
+        reason: String,
+    },
+
+    #[error("Failed to stop {component} container")]
+    TeardownFailed {
+        component: &'static str,
+        #[source]
+        source: BoxError,
+    },
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl ArchTestingError {
+    pub(crate) fn container_start_failed(
+        component: &'static str,
+        source: impl Into<BoxError>,
+    ) -> Self {
+        Self::ContainerStartFailed {
+            component,
+            source: source.into(),
+        }
+    }
+
+    pub(crate) fn rpc_unavailable(service: &'static str, source: impl Into<BoxError>) -> Self {
+        Self::RpcUnavailable {
+            service,
+            source: source.into(),
+        }
+    }
+
+    pub(crate) fn teardown_failed(component: &'static str, source: impl Into<BoxError>) -> Self {
+        Self::TeardownFailed {
+            component,
+            source: source.into(),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Recording every validator websocket event of a run, to query afterwards or to read as a
+//! timeline when a test fails (`TestRunnerConfig::record_events`).
+//!
+//! ```ignore
+//! let recorder = ctx.event_recorder()?;
+//! // ...
+//! assert_eq!(recorder.events_for(account_pubkey).len(), 2);
+//! ```
+
+use std::{
+    fmt::Write,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::Result;
+use arch_program::pubkey::Pubkey;
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+use crate::{ValidatorEvent, ValidatorSubscription};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedEvent {
+    pub received_at: SystemTime,
+    /// Since the recorder started
+    pub elapsed: Duration,
+    pub event: ValidatorEvent,
+}
+
+/// Cheap to clone: clones share the same recording
+#[derive(Clone)]
+pub struct EventRecorder {
+    started: Instant,
+    events: Arc<Mutex<Vec<RecordedEvent>>>,
+    listener: Arc<JoinHandle<()>>,
+}
+
+impl EventRecorder {
+    /// Subscribe to every topic in `ALL_VALIDATOR_TOPICS` and record until `stop`
+    pub async fn start(websocket_url: &str) -> Result<Self> {
+        let started = Instant::now();
+        let events = Arc::new(Mutex::new(Vec::new()));
+
+        let mut subscription = ValidatorSubscription::connect_all(websocket_url).await?;
+
+        let recording = events.clone();
+        let listener = tokio::spawn(
+            async move {
+                while let Ok(event) = subscription.recv().await {
+                    recording
+                        .lock()
+                        .expect("event recording poisoned")
+                        .push(RecordedEvent {
+                            received_at: SystemTime::now(),
+                            elapsed: started.elapsed(),
+                            event,
+                        });
+                }
+            }
+            .instrument(tracing::Span::current()),
+        );
+
+        Ok(Self {
+            started,
+            events,
+            listener: Arc::new(listener),
+        })
+    }
+
+    /// Stop recording; what was recorded stays queryable
+    pub fn stop(&self) {
+        self.listener.abort();
+    }
+
+    /// Every event so far, in the order received
+    pub fn events(&self) -> Vec<RecordedEvent> {
+        self.events
+            .lock()
+            .expect("event recording poisoned")
+            .clone()
+    }
+
+    /// Events mentioning `pubkey` (as hex)
+    pub fn events_for(&self, pubkey: Pubkey) -> Vec<RecordedEvent> {
+        self.events_mentioning(&hex::encode(pubkey.0))
+    }
+
+    /// Events mentioning `needle` anywhere (a txid, block hash, ...)
+    pub fn events_mentioning(&self, needle: &str) -> Vec<RecordedEvent> {
+        self.events()
+            .into_iter()
+            .filter(|recorded| recorded.event.mentions(needle))
+            .collect()
+    }
+
+    /// One line per event: offset since the recorder started, topic and payload
+    pub fn timeline(&self) -> String {
+        let mut timeline = String::new();
+        for recorded in self.events() {
+            let _ = writeln!(
+                timeline,
+                "+{:>10.3}s {:<16} {}",
+                recorded.elapsed.as_secs_f64(),
+                recorded.event.topic().unwrap_or("?"),
+                recorded.event.data()
+            );
+        }
+        timeline
+    }
+
+    /// How long the recorder has been running
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
@@ -0,0 +1,163 @@
+//! Running commands inside the environment's containers (`bitcoin-cli`, validator tooling,
+//! `ls` of a data directory, ...) and capturing their output.
+//!
+//! The container wrappers exec through testcontainers; `TestContext` and its handles, which
+//! don't own the containers, go through `docker exec` by container name.
+//!
+//! ```ignore
+//! let info: serde_json::Value = serde_json::from_str(&ctx.bitcoin().cli(["getblockchaininfo"]).await?)?;
+//! let listing = ctx.exec(Component::Validator, ["ls", "-la", "/arch_data"]).await?;
+//! ```
+
+use std::{fmt, process::Command};
+
+use anyhow::{anyhow, Context, Result};
+use tokio::task::spawn_blocking;
+
+use crate::{BitcoinHandle, Component, TestContext, TitanHandle, ValidatorHandle};
+
+/// What an in-container command printed, and how it exited
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecOutput {
+    /// The command, joined with spaces
+    pub command: String,
+    /// `None` when docker didn't report one
+    pub exit_code: Option<i64>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl ExecOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    /// Trimmed stdout of a command that exited 0; otherwise an error carrying its stderr
+    pub fn into_stdout(self) -> Result<String> {
+        if self.success() {
+            Ok(self.stdout.trim().to_string())
+        } else {
+            Err(anyhow!("{}", self))
+        }
+    }
+}
+
+impl fmt::Display for ExecOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.exit_code {
+            Some(exit_code) => write!(f, "`{}` exited with {}", self.command, exit_code)?,
+            None => write!(f, "`{}` exited", self.command)?,
+        }
+        if !self.stderr.trim().is_empty() {
+            write!(f, ": {}", self.stderr.trim())?;
+        }
+        Ok(())
+    }
+}
+
+/// `docker exec` `cmd` in container `container_name`, waiting for it to exit
+pub(crate) async fn docker_exec(container_name: &str, cmd: Vec<String>) -> Result<ExecOutput> {
+    let container_name = container_name.to_string();
+
+    spawn_blocking(move || {
+        let command = cmd.join(" ");
+        let output = Command::new("docker")
+            .arg("exec")
+            .arg(&container_name)
+            .args(&cmd)
+            .output()
+            .with_context(|| format!("Failed to docker exec {} in {}", command, container_name))?;
+
+        Ok(ExecOutput {
+            command,
+            exit_code: output.status.code().map(i64::from),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    })
+    .await
+    .context("Failed to spawn blocking task")?
+}
+
+pub(crate) fn command_args(cmd: impl IntoIterator<Item = impl Into<String>>) -> Vec<String> {
+    cmd.into_iter().map(Into::into).collect()
+}
+
+impl BitcoinHandle {
+    /// Run `cmd` in the bitcoind container
+    pub async fn exec(
+        &self,
+        cmd: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<ExecOutput> {
+        docker_exec(&self.config.container_name, command_args(cmd)).await
+    }
+
+    /// Run `bitcoin-cli` with `args`, already pointed at this node (network, port, auth), and
+    /// return its trimmed stdout. Wallet calls need `-rpcwallet=...` once several wallets are
+    /// loaded.
+    pub async fn cli(&self, args: impl IntoIterator<Item = impl Into<String>>) -> Result<String> {
+        docker_exec(
+            &self.config.container_name,
+            self.config.bitcoin_cli_command(command_args(args)),
+        )
+        .await?
+        .into_stdout()
+    }
+}
+
+impl TitanHandle {
+    /// Run `cmd` in the Titan container
+    pub async fn exec(
+        &self,
+        cmd: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<ExecOutput> {
+        docker_exec(&self.config.container_name, command_args(cmd)).await
+    }
+}
+
+impl ValidatorHandle {
+    /// Run `cmd` in the validator container
+    pub async fn exec(
+        &self,
+        cmd: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<ExecOutput> {
+        docker_exec(&self.config.container_name, command_args(cmd)).await
+    }
+}
+
+impl TestContext {
+    /// Run `cmd` in `component`'s container
+    pub async fn exec(
+        &self,
+        component: Component,
+        cmd: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<ExecOutput> {
+        docker_exec(&self.container_name(component), command_args(cmd)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exec_output_into_stdout() {
+        let output = ExecOutput {
+            command: "bitcoin-cli getblockcount".to_string(),
+            exit_code: Some(0),
+            stdout: "101\n".to_string(),
+            stderr: String::new(),
+        };
+        assert_eq!(output.clone().into_stdout().unwrap(), "101");
+
+        let failed = ExecOutput {
+            exit_code: Some(1),
+            stderr: "error: timeout on transient error\n".to_string(),
+            ..output
+        };
+        assert_eq!(
+            failed.into_stdout().unwrap_err().to_string(),
+            "`bitcoin-cli getblockcount` exited with 1: error: timeout on transient error"
+        );
+    }
+}
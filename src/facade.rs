@@ -0,0 +1,375 @@
+//! Stable facade over `TestContext`.
+//!
+//! Shared test-helper libraries should be written against [`ArchTestContext`] rather than
+//! the concrete `TestContext`, so their own unit tests can swap in a mock that needs no
+//! containers — [`MockArchTestContext`], or their own. The trait only grows additively;
+//! `TestContext` internals may change freely.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use anyhow::{anyhow, Context};
+use arch_program::{
+    bpf_loader::BPF_LOADER_ID, hash::Hash, instruction::Instruction, pubkey::Pubkey,
+    sanitized::ArchMessage,
+};
+use arch_sdk::{
+    build_and_sign_transaction, generate_new_keypair, AccountInfo, ProcessedTransaction,
+    RuntimeTransaction,
+};
+use bitcoin::{key::Keypair, Address, Network};
+
+use crate::{error::ArchTestingResult, TestContext, PROGRAM_ACCOUNT_HEADER_LEN};
+
+/// Lamports `MockArchTestContext::fund_keypair_with_faucet` adds per call
+pub const MOCK_FAUCET_LAMPORTS: u64 = 1_000_000_000;
+
+pub trait ArchTestContext: Send + Sync {
+    fn network(&self) -> Network;
+
+    fn generate_new_keypair(&self) -> (Keypair, Pubkey, Address);
+
+    fn fund_keypair_with_faucet(
+        &self,
+        keypair: &Keypair,
+    ) -> impl Future<Output = ArchTestingResult<()>> + Send;
+
+    fn deploy_program(
+        &self,
+        program_kp: Keypair,
+        authority_kp: Keypair,
+        elf_bytes: &[u8],
+    ) -> impl Future<Output = ArchTestingResult<()>> + Send;
+
+    fn get_best_blockhash(&self) -> impl Future<Output = ArchTestingResult<Hash>> + Send;
+
+    fn build_and_sign_transaction(
+        &self,
+        message: ArchMessage,
+        signers: Vec<Keypair>,
+    ) -> impl Future<Output = ArchTestingResult<RuntimeTransaction>> + Send;
+
+    fn send_transaction(
+        &self,
+        transaction: RuntimeTransaction,
+    ) -> impl Future<Output = ArchTestingResult<String>> + Send;
+
+    fn wait_for_transaction(
+        &self,
+        txid: &str,
+    ) -> impl Future<Output = ArchTestingResult<ProcessedTransaction>> + Send;
+
+    fn read_account_info(
+        &self,
+        pubkey: Pubkey,
+    ) -> impl Future<Output = ArchTestingResult<AccountInfo>> + Send;
+
+    /// Provided in terms of the required methods, so mocks get it for free
+    fn generate_funded_keypair(
+        &self,
+    ) -> impl Future<Output = ArchTestingResult<(Keypair, Pubkey, Address)>> + Send {
+        async {
+            let (keypair, pubkey, address) = self.generate_new_keypair();
+            self.fund_keypair_with_faucet(&keypair).await?;
+            Ok((keypair, pubkey, address))
+        }
+    }
+
+    /// Provided in terms of the required methods, so mocks get it for free
+    fn build_message(
+        &self,
+        instructions: &[Instruction],
+        payer: Option<Pubkey>,
+    ) -> impl Future<Output = ArchTestingResult<ArchMessage>> + Send {
+        async move {
+            Ok(ArchMessage::new(
+                instructions,
+                payer,
+                self.get_best_blockhash().await?,
+            ))
+        }
+    }
+}
+
+impl ArchTestContext for TestContext {
+    fn network(&self) -> Network {
+        self.network
+    }
+
+    fn generate_new_keypair(&self) -> (Keypair, Pubkey, Address) {
+        TestContext::generate_new_keypair(self)
+    }
+
+    fn fund_keypair_with_faucet(
+        &self,
+        keypair: &Keypair,
+    ) -> impl Future<Output = ArchTestingResult<()>> + Send {
+        TestContext::fund_keypair_with_faucet(self, keypair)
+    }
+
+    fn deploy_program(
+        &self,
+        program_kp: Keypair,
+        authority_kp: Keypair,
+        elf_bytes: &[u8],
+    ) -> impl Future<Output = ArchTestingResult<()>> + Send {
+        async move { Ok(TestContext::deploy_program(self, program_kp, authority_kp, elf_bytes).await?) }
+    }
+
+    fn get_best_blockhash(&self) -> impl Future<Output = ArchTestingResult<Hash>> + Send {
+        TestContext::get_best_blockhash(self)
+    }
+
+    fn build_and_sign_transaction(
+        &self,
+        message: ArchMessage,
+        signers: Vec<Keypair>,
+    ) -> impl Future<Output = ArchTestingResult<RuntimeTransaction>> + Send {
+        TestContext::build_and_sign_transaction(self, message, signers)
+    }
+
+    fn send_transaction(
+        &self,
+        transaction: RuntimeTransaction,
+    ) -> impl Future<Output = ArchTestingResult<String>> + Send {
+        TestContext::send_transaction(self, transaction)
+    }
+
+    fn wait_for_transaction(
+        &self,
+        txid: &str,
+    ) -> impl Future<Output = ArchTestingResult<ProcessedTransaction>> + Send {
+        TestContext::wait_for_transaction(self, txid)
+    }
+
+    fn read_account_info(
+        &self,
+        pubkey: Pubkey,
+    ) -> impl Future<Output = ArchTestingResult<AccountInfo>> + Send {
+        TestContext::read_account_info(self, pubkey)
+    }
+}
+
+/// In-memory `ArchTestContext` for unit-testing helpers written against the facade: accounts
+/// live in a map, the faucet credits `MOCK_FAUCET_LAMPORTS`, deploys store the ELF in an
+/// executable account, and sent transactions are recorded rather than executed. Their results
+/// come from `set_processed_transaction`; waiting for any other txid fails.
+///
+/// Cheap to clone: clones share the same state
+#[derive(Clone)]
+pub struct MockArchTestContext {
+    network: Network,
+    blockhash: Hash,
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Default)]
+struct MockState {
+    accounts: HashMap<Pubkey, AccountInfo>,
+    sent: Vec<RuntimeTransaction>,
+    processed: HashMap<String, ProcessedTransaction>,
+}
+
+impl MockArchTestContext {
+    pub fn new(network: Network, blockhash: Hash) -> Self {
+        Self {
+            network,
+            blockhash,
+            state: Arc::default(),
+        }
+    }
+
+    /// Put `account` at `pubkey`, replacing whatever was there
+    pub fn set_account(&self, pubkey: Pubkey, account: AccountInfo) {
+        self.lock().accounts.insert(pubkey, account);
+    }
+
+    /// What `wait_for_transaction(txid)` returns
+    pub fn set_processed_transaction(&self, txid: &str, processed_tx: ProcessedTransaction) {
+        self.lock().processed.insert(txid.to_string(), processed_tx);
+    }
+
+    /// Every transaction sent so far, in order
+    pub fn sent_transactions(&self) -> Vec<RuntimeTransaction> {
+        self.lock().sent.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().expect("mock state poisoned")
+    }
+}
+
+impl ArchTestContext for MockArchTestContext {
+    fn network(&self) -> Network {
+        self.network
+    }
+
+    fn generate_new_keypair(&self) -> (Keypair, Pubkey, Address) {
+        generate_new_keypair(self.network)
+    }
+
+    fn fund_keypair_with_faucet(
+        &self,
+        keypair: &Keypair,
+    ) -> impl Future<Output = ArchTestingResult<()>> + Send {
+        let pubkey = Pubkey::from_slice(&keypair.x_only_public_key().0.serialize());
+
+        async move {
+            let mut state = self.lock();
+            let account = state.accounts.entry(pubkey).or_insert_with(|| AccountInfo {
+                lamports: 0,
+                owner: Pubkey::system_program(),
+                data: Vec::new(),
+                utxo: String::new(),
+                is_executable: false,
+            });
+            account.lamports += MOCK_FAUCET_LAMPORTS;
+            Ok(())
+        }
+    }
+
+    fn deploy_program(
+        &self,
+        program_kp: Keypair,
+        _authority_kp: Keypair,
+        elf_bytes: &[u8],
+    ) -> impl Future<Output = ArchTestingResult<()>> + Send {
+        let program_pubkey = Pubkey::from_slice(&program_kp.x_only_public_key().0.serialize());
+        let mut data = vec![0; PROGRAM_ACCOUNT_HEADER_LEN];
+        data.extend_from_slice(elf_bytes);
+
+        async move {
+            self.set_account(
+                program_pubkey,
+                AccountInfo {
+                    lamports: 0,
+                    owner: BPF_LOADER_ID,
+                    data,
+                    utxo: String::new(),
+                    is_executable: true,
+                },
+            );
+            Ok(())
+        }
+    }
+
+    fn get_best_blockhash(&self) -> impl Future<Output = ArchTestingResult<Hash>> + Send {
+        async move { Ok(self.blockhash) }
+    }
+
+    fn build_and_sign_transaction(
+        &self,
+        message: ArchMessage,
+        signers: Vec<Keypair>,
+    ) -> impl Future<Output = ArchTestingResult<RuntimeTransaction>> + Send {
+        async move {
+            Ok(build_and_sign_transaction(message, signers, self.network)
+                .context("Failed to sign transaction")?)
+        }
+    }
+
+    fn send_transaction(
+        &self,
+        transaction: RuntimeTransaction,
+    ) -> impl Future<Output = ArchTestingResult<String>> + Send {
+        async move {
+            let mut state = self.lock();
+            state.sent.push(transaction);
+            Ok(format!("{:064x}", state.sent.len()))
+        }
+    }
+
+    fn wait_for_transaction(
+        &self,
+        txid: &str,
+    ) -> impl Future<Output = ArchTestingResult<ProcessedTransaction>> + Send {
+        async move {
+            self.lock()
+                .processed
+                .get(txid)
+                .cloned()
+                .ok_or_else(|| anyhow!("No processed transaction set for {}", txid).into())
+        }
+    }
+
+    fn read_account_info(
+        &self,
+        pubkey: Pubkey,
+    ) -> impl Future<Output = ArchTestingResult<AccountInfo>> + Send {
+        async move {
+            self.lock()
+                .accounts
+                .get(&pubkey)
+                .cloned()
+                .ok_or_else(|| anyhow!("Account {} not found", pubkey).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arch_program::system_instruction;
+    use futures::executor::block_on;
+
+    use super::*;
+
+    fn blockhash(byte: u8) -> Hash {
+        hex::encode([byte; 32]).parse().unwrap()
+    }
+
+    /// A helper as a shared library would write it: only the facade, no `TestContext`
+    async fn fund_and_transfer(
+        ctx: &impl ArchTestContext,
+        lamports: u64,
+    ) -> ArchTestingResult<(Pubkey, String)> {
+        let (payer_kp, payer, _) = ctx.generate_funded_keypair().await?;
+        let (_, recipient, _) = ctx.generate_new_keypair();
+
+        let message = ctx
+            .build_message(
+                &[system_instruction::transfer(&payer, &recipient, lamports)],
+                Some(payer),
+            )
+            .await?;
+        let transaction = ctx
+            .build_and_sign_transaction(message, vec![payer_kp])
+            .await?;
+        let txid = ctx.send_transaction(transaction).await?;
+
+        Ok((payer, txid))
+    }
+
+    #[test]
+    fn test_mock_drives_the_facade() {
+        let ctx = MockArchTestContext::new(Network::Regtest, blockhash(7));
+
+        let (payer, txid) = block_on(fund_and_transfer(&ctx, 1_000)).unwrap();
+        let payer_account = block_on(ctx.read_account_info(payer)).unwrap();
+        assert_eq!(payer_account.lamports, MOCK_FAUCET_LAMPORTS);
+
+        let sent = ctx.sent_transactions();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].signatures.len(), 1);
+        assert!(block_on(ctx.wait_for_transaction(&txid)).is_err());
+
+        let (_, missing, _) = ctx.generate_new_keypair();
+        assert!(block_on(ctx.read_account_info(missing)).is_err());
+    }
+
+    #[test]
+    fn test_mock_deploy_program() {
+        let ctx = MockArchTestContext::new(Network::Regtest, blockhash(0));
+        let (program_kp, program, _) = ctx.generate_new_keypair();
+        let (authority_kp, _, _) = ctx.generate_new_keypair();
+
+        block_on(ctx.deploy_program(program_kp, authority_kp, b"\x7fELF")).unwrap();
+
+        let account = block_on(ctx.read_account_info(program)).unwrap();
+        assert!(account.is_executable);
+        assert_eq!(account.owner, BPF_LOADER_ID);
+        assert_eq!(&account.data[PROGRAM_ACCOUNT_HEADER_LEN..], b"\x7fELF");
+    }
+}
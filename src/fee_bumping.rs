@@ -0,0 +1,164 @@
+//! Fee acceleration helpers (RBF and CPFP), for Arch flows that must tolerate their
+//! anchor transaction being replaced or accelerated.
+
+use std::collections::HashMap;
+
+use anyhow::{ensure, Context, Result};
+use bitcoin::{Address, Amount, Txid};
+use bitcoincore_rpc::{json::CreateRawTransactionInput, RpcApi};
+
+use crate::TestContext;
+
+impl TestContext {
+    /// Send `amount` to `address` from the test wallet, signalling BIP125 replaceability
+    pub async fn send_replaceable(&self, address: &Address, amount: Amount) -> Result<Txid> {
+        let address = address.clone();
+
+        self.with_bitcoin_client(move |client| {
+            client.send_to_address(&address, amount, None, None, None, Some(true), None, None)
+        })
+        .await
+        .context("Failed to send replaceable transaction")
+    }
+
+    /// Replace `txid` with a higher-fee version via `bumpfee`, returning the replacement's txid
+    pub async fn bump_fee(&self, txid: Txid) -> Result<Txid> {
+        let result = self
+            .with_bitcoin_client(move |client| client.bump_fee(&txid, None))
+            .await
+            .with_context(|| format!("Failed to bump fee of {}", txid))?;
+
+        ensure!(
+            result.errors.is_empty(),
+            "Bumping fee of {} reported errors: {:?}",
+            txid,
+            result.errors
+        );
+
+        result
+            .txid
+            .with_context(|| format!("bumpfee of {} didn't return a replacement txid", txid))
+    }
+
+    /// Mine a block and assert that `expected` confirmed while `replaced` did not
+    pub async fn mine_and_assert_replacement(&self, expected: Txid, replaced: Txid) -> Result<()> {
+        self.bitcoin_mine_blocks(1).await?;
+
+        let expected_confirmations = self.wallet_confirmations(expected).await?;
+        ensure!(
+            expected_confirmations > 0,
+            "Expected {} to confirm, it has {} confirmations",
+            expected,
+            expected_confirmations
+        );
+
+        let replaced_confirmations = self.wallet_confirmations(replaced).await?;
+        ensure!(
+            replaced_confirmations <= 0,
+            "Expected {} to be replaced, but it confirmed ({} confirmations)",
+            replaced,
+            replaced_confirmations
+        );
+
+        Ok(())
+    }
+
+    /// Wallet view of a transaction's confirmations; negative once it has been conflicted
+    pub(crate) async fn wallet_confirmations(&self, txid: Txid) -> Result<i32> {
+        let tx = self
+            .with_bitcoin_client(move |client| client.get_transaction(&txid, None))
+            .await?;
+
+        Ok(tx.info.confirmations)
+    }
+
+    /// Send `amount` to `address` paying exactly `sat_per_vb`, e.g. a deliberately low fee
+    /// to produce a parent that needs accelerating
+    pub async fn send_with_fee_rate(
+        &self,
+        address: &Address,
+        amount: Amount,
+        sat_per_vb: f64,
+    ) -> Result<Txid> {
+        let address = address.to_string();
+
+        self.with_bitcoin_client(move |client| {
+            client.call(
+                "sendtoaddress",
+                &[
+                    address.into(),
+                    amount.to_btc().into(),
+                    serde_json::Value::Null, // comment
+                    serde_json::Value::Null, // comment_to
+                    false.into(),            // subtractfeefromamount
+                    true.into(),             // replaceable
+                    serde_json::Value::Null, // conf_target
+                    "unset".into(),          // estimate_mode
+                    serde_json::Value::Null, // avoid_reuse
+                    sat_per_vb.into(),       // fee_rate
+                ],
+            )
+        })
+        .await
+        .context("Failed to send transaction with explicit fee rate")
+    }
+
+    /// Accelerate `parent` by spending one of its wallet-owned outputs in a child paying `child_fee`
+    /// (child-pays-for-parent). Returns the child's txid; neither transaction is mined.
+    pub async fn cpfp(&self, parent: Txid, child_fee: Amount) -> Result<Txid> {
+        self.with_bitcoin_client(move |client| {
+            let parent_output = client
+                .list_unspent(Some(0), None, None, None, None)?
+                .into_iter()
+                .find(|utxo| utxo.txid == parent)
+                .ok_or_else(|| {
+                    bitcoincore_rpc::Error::ReturnedError(format!(
+                        "No wallet-owned output of {} to spend",
+                        parent
+                    ))
+                })?;
+
+            let destination = client.get_new_address(None, None)?.assume_checked();
+            let child_value = parent_output.amount.checked_sub(child_fee).ok_or_else(|| {
+                bitcoincore_rpc::Error::ReturnedError(format!(
+                    "Output {}:{} ({}) can't pay a child fee of {}",
+                    parent, parent_output.vout, parent_output.amount, child_fee
+                ))
+            })?;
+
+            let child = client.create_raw_transaction(
+                &[CreateRawTransactionInput {
+                    txid: parent,
+                    vout: parent_output.vout,
+                    sequence: None,
+                }],
+                &HashMap::from([(destination.to_string(), child_value)]),
+                None,
+                Some(true),
+            )?;
+
+            let signed = client.sign_raw_transaction_with_wallet(&child, None, None)?;
+            client.send_raw_transaction(&signed.transaction()?)
+        })
+        .await
+        .with_context(|| format!("Failed to attach CPFP child to {}", parent))
+    }
+
+    /// `cpfp`, then mine a block and assert both parent and child confirmed
+    pub async fn cpfp_and_confirm(&self, parent: Txid, child_fee: Amount) -> Result<Txid> {
+        let child = self.cpfp(parent, child_fee).await?;
+        self.bitcoin_mine_blocks(1).await?;
+
+        for txid in [parent, child] {
+            let confirmations = self.wallet_confirmations(txid).await?;
+            ensure!(
+                confirmations > 0,
+                "Expected {} to confirm after CPFP, it has {} confirmations",
+                txid,
+                confirmations
+            );
+        }
+
+        Ok(child)
+    }
+}
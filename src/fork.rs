@@ -0,0 +1,122 @@
+//! Forking remote state: copy the programs and accounts listed in `ForkConfig` from a remote
+//! Arch network into the local validator, so tests run against a faithful local copy. Nothing
+//! is discovered: accounts a program owns are only copied if they're listed too.
+//!
+//! Like cloned accounts, forked programs and accounts get new local pubkeys
+//! (`cloned_account` maps remote pubkeys to them). Owners are remapped: an account owned by a
+//! forked program is owned by that program's local copy. Pubkeys stored *inside* account data
+//! aren't rewritten.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use anyhow::{ensure, Context, Result};
+use arch_program::pubkey::Pubkey;
+use arch_sdk::AccountInfo;
+use futures::{stream, StreamExt, TryStreamExt};
+
+use crate::{
+    cloning::fetch_remote_account, ForkConfig, TestContext, DEFAULT_ACCOUNT_READ_CONCURRENCY,
+};
+
+/// Bytes of loader state ahead of the ELF in a program account (authority pubkey + status)
+pub const PROGRAM_ACCOUNT_HEADER_LEN: usize = 40;
+
+/// Remote state read by `fork_from`, all fetched before anything is created locally so the
+/// copy is as close to one point in time as the remote RPC allows
+#[derive(Debug, Clone)]
+pub struct ForkSnapshot {
+    pub rpc_url: String,
+    /// ELF of each program
+    pub programs: BTreeMap<Pubkey, Vec<u8>>,
+    pub accounts: BTreeMap<Pubkey, AccountInfo>,
+}
+
+impl TestContext {
+    /// Read `fork`'s programs and accounts from the remote network. Each pubkey is fetched
+    /// once; one listed as both a program and an account is forked as a program.
+    pub async fn snapshot_remote(&self, fork: &ForkConfig) -> Result<ForkSnapshot> {
+        let rpc_url = fork.rpc_url.as_str();
+        let program_pubkeys = fork.programs.iter().copied().collect::<BTreeSet<_>>();
+        let pubkeys = program_pubkeys
+            .iter()
+            .chain(&fork.accounts)
+            .copied()
+            .collect::<BTreeSet<_>>();
+
+        let mut remote: HashMap<Pubkey, AccountInfo> = stream::iter(pubkeys)
+            .map(|pubkey| async move {
+                Ok::<_, anyhow::Error>((pubkey, fetch_remote_account(rpc_url, pubkey).await?))
+            })
+            .buffer_unordered(DEFAULT_ACCOUNT_READ_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        let mut programs = BTreeMap::new();
+        for pubkey in &program_pubkeys {
+            let account = remote
+                .remove(pubkey)
+                .with_context(|| format!("Program {} wasn't fetched from {}", pubkey, rpc_url))?;
+            ensure!(
+                account.is_executable && account.data.len() > PROGRAM_ACCOUNT_HEADER_LEN,
+                "Account {} on {} isn't a program",
+                pubkey,
+                rpc_url
+            );
+            programs.insert(*pubkey, account.data[PROGRAM_ACCOUNT_HEADER_LEN..].to_vec());
+        }
+
+        Ok(ForkSnapshot {
+            rpc_url: fork.rpc_url.clone(),
+            programs,
+            accounts: remote.into_iter().collect(),
+        })
+    }
+
+    /// Snapshot `fork`'s programs and accounts from the remote network and recreate them
+    /// locally, programs first (see the module docs)
+    pub async fn fork_from(&self, fork: &ForkConfig) -> Result<ForkSnapshot> {
+        let snapshot = self.snapshot_remote(fork).await?;
+        let (authority_kp, _, _) = self.generate_funded_keypair().await?;
+
+        tracing::info!(
+            "Forking {} programs and {} accounts from {}",
+            snapshot.programs.len(),
+            snapshot.accounts.len(),
+            snapshot.rpc_url
+        );
+
+        let mut local_pubkeys = HashMap::new();
+
+        for (remote_pubkey, elf) in &snapshot.programs {
+            let (program_kp, program_pubkey, _) = self.generate_new_keypair();
+            self.deploy_program_cached(program_kp, authority_kp, elf, false)
+                .await
+                .with_context(|| format!("Failed to fork program {}", remote_pubkey))?;
+            local_pubkeys.insert(*remote_pubkey, program_pubkey);
+        }
+
+        for (remote_pubkey, account) in &snapshot.accounts {
+            let owner = local_pubkeys
+                .get(&account.owner)
+                .copied()
+                .unwrap_or(account.owner);
+            let (account_kp, _, _) = self.generate_new_keypair();
+
+            let local_pubkey = self
+                .create_account_with_data(
+                    authority_kp,
+                    account_kp,
+                    account.lamports,
+                    owner,
+                    &account.data,
+                )
+                .await
+                .with_context(|| format!("Failed to fork account {}", remote_pubkey))?;
+            local_pubkeys.insert(*remote_pubkey, local_pubkey);
+        }
+
+        self.cloned_accounts().lock().await.extend(local_pubkeys);
+
+        Ok(snapshot)
+    }
+}
@@ -0,0 +1,187 @@
+//! Exact-amount lamport funding.
+//!
+//! The faucet hands out a fixed amount, so exact balances are established by transferring from
+//! a shared funder keypair, which is itself topped up from the faucet as needed.
+
+use anyhow::{ensure, Context, Result};
+use arch_program::{pubkey::Pubkey, system_instruction};
+use arch_sdk::Status;
+use bitcoin::{key::Keypair, Address};
+use futures::{stream, StreamExt, TryStreamExt};
+
+use crate::TestContext;
+
+/// Faucet requests / funding transactions in flight at once
+pub const DEFAULT_FUNDING_CONCURRENCY: usize = 8;
+
+/// Transfer instructions packed into a single funding transaction
+const TRANSFERS_PER_TRANSACTION: usize = 8;
+
+/// Upper bound on faucet requests made to cover a single transfer
+const MAX_FAUCET_REQUESTS: usize = 100;
+
+impl TestContext {
+    /// Transfer exactly `amount` lamports to `keypair`'s account, asserting its balance grew by
+    /// that much. Returns the new balance.
+    pub async fn fund_lamports(&self, keypair: &Keypair, amount: u64) -> Result<u64> {
+        let pubkey = Pubkey::from_slice(&keypair.x_only_public_key().0.serialize());
+        let before = self.lamports_of(pubkey).await?;
+
+        self.transfer_from_funder(pubkey, amount).await?;
+
+        let after = self.lamports_of(pubkey).await?;
+        ensure!(
+            after == before + amount,
+            "Expected {} to hold {} lamports after funding, it holds {}",
+            pubkey,
+            before + amount,
+            after
+        );
+
+        Ok(after)
+    }
+
+    /// Bring `pubkey` up to exactly `target` lamports; accounts already at or above `target` are
+    /// left alone. Returns the resulting balance.
+    pub async fn top_up_to(&self, pubkey: &Pubkey, target: u64) -> Result<u64> {
+        let before = self.lamports_of(*pubkey).await?;
+        if before >= target {
+            return Ok(before);
+        }
+
+        self.transfer_from_funder(*pubkey, target - before).await?;
+
+        let after = self.lamports_of(*pubkey).await?;
+        ensure!(
+            after == target,
+            "Expected {} to hold {} lamports after top up, it holds {}",
+            pubkey,
+            target,
+            after
+        );
+
+        Ok(after)
+    }
+
+    /// Balance of `pubkey`; accounts that don't exist (yet) hold 0, failing to read one is an
+    /// error
+    pub(crate) async fn lamports_of(&self, pubkey: Pubkey) -> Result<u64> {
+        let account = self
+            .try_read_account_info(pubkey)
+            .await
+            .with_context(|| format!("Failed to read the balance of {}", pubkey))?;
+
+        Ok(account.map(|account| account.lamports).unwrap_or_default())
+    }
+
+    /// Generate `n` keypairs and faucet-fund them, `DEFAULT_FUNDING_CONCURRENCY` at a time
+    pub async fn generate_funded_keypairs(
+        &self,
+        n: usize,
+    ) -> Result<Vec<(Keypair, Pubkey, Address)>> {
+        Ok(stream::iter(0..n)
+            .map(|_| self.generate_funded_keypair())
+            .buffered(DEFAULT_FUNDING_CONCURRENCY)
+            .try_collect()
+            .await?)
+    }
+
+    /// Transfer `amount` lamports to each of `keypairs`, batching transfers into a few
+    /// transactions sent `DEFAULT_FUNDING_CONCURRENCY` at a time
+    pub async fn fund_many(&self, keypairs: &[Keypair], amount: u64) -> Result<()> {
+        let transfers = keypairs
+            .iter()
+            .map(|keypair| {
+                let pubkey = Pubkey::from_slice(&keypair.x_only_public_key().0.serialize());
+                (pubkey, amount)
+            })
+            .collect::<Vec<_>>();
+
+        let total = amount.checked_mul(keypairs.len() as u64).with_context(|| {
+            format!(
+                "Funding {} keypairs with {} lamports each overflows",
+                keypairs.len(),
+                amount
+            )
+        })?;
+
+        let mut funder = self.funder().lock().await;
+        let funder_kp = self.funder_with_balance(&mut funder, total).await?;
+
+        stream::iter(transfers.chunks(TRANSFERS_PER_TRANSACTION))
+            .map(|batch| self.transfer_from(&funder_kp, batch))
+            .buffer_unordered(DEFAULT_FUNDING_CONCURRENCY)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn transfer_from_funder(&self, to: Pubkey, amount: u64) -> Result<()> {
+        // held for the whole transfer, so concurrent callers don't race on the funder's balance
+        let mut funder = self.funder().lock().await;
+        let funder_kp = self.funder_with_balance(&mut funder, amount).await?;
+
+        self.transfer_from(&funder_kp, &[(to, amount)]).await
+    }
+
+    /// The funder keypair (created on first use), faucet-funded until it holds more than `needed`
+    async fn funder_with_balance(
+        &self,
+        funder: &mut Option<Keypair>,
+        needed: u64,
+    ) -> Result<Keypair> {
+        let funder_kp = match funder.as_ref() {
+            Some(funder_kp) => funder_kp.clone(),
+            None => {
+                let (funder_kp, _, _) = self.generate_new_keypair();
+                funder.insert(funder_kp).clone()
+            }
+        };
+        let funder_pubkey = Pubkey::from_slice(&funder_kp.x_only_public_key().0.serialize());
+
+        let mut faucet_requests = 0;
+        while self.lamports_of(funder_pubkey).await? <= needed {
+            ensure!(
+                faucet_requests < MAX_FAUCET_REQUESTS,
+                "Faucet couldn't cover {} lamports after {} requests",
+                needed,
+                faucet_requests
+            );
+            self.fund_keypair_with_faucet(&funder_kp).await?;
+            faucet_requests += 1;
+        }
+
+        Ok(funder_kp)
+    }
+
+    /// One transaction, paid for by `from_kp`, with a transfer instruction per `(to, amount)`
+    async fn transfer_from(&self, from_kp: &Keypair, transfers: &[(Pubkey, u64)]) -> Result<()> {
+        let from_pubkey = Pubkey::from_slice(&from_kp.x_only_public_key().0.serialize());
+        let instructions = transfers
+            .iter()
+            .map(|(to, amount)| system_instruction::transfer(&from_pubkey, to, *amount))
+            .collect::<Vec<_>>();
+
+        let message = self.build_message(&instructions, Some(from_pubkey)).await?;
+        let transaction = self
+            .build_and_sign_transaction(message, vec![from_kp.clone()])
+            .await?;
+
+        let txid = self.send_transaction(transaction).await?;
+        let processed_tx = self.wait_for_transaction(&txid).await?;
+
+        match processed_tx.status {
+            Status::Processed => Ok(()),
+            Status::Failed(e) => Err(anyhow::anyhow!(
+                "Transfer from {} failed: {}",
+                from_pubkey,
+                e
+            )),
+            Status::Queued => Err(anyhow::anyhow!(
+                "Transfer from {} still queued",
+                from_pubkey
+            )),
+        }
+    }
+}
@@ -0,0 +1,312 @@
+//! Fuzzing deployed programs with mutated instruction data.
+//!
+//! ```ignore
+//! let mut mutator = ByteMutator::new(valid_instruction_data, 42);
+//! let report = ctx
+//!     .fuzz(program_id, move |_| mutator.next_input())
+//!     .accounts(vec![AccountMeta::new(counter, false)])
+//!     .signers(vec![payer_kp])
+//!     .iterations(500)
+//!     .check("counter never exceeds max", |ctx, _input, _tx| async move {
+//!         let counter: Counter = ctx.read_account_as(counter, &program_id).await?;
+//!         ensure!(counter.value <= MAX);
+//!         Ok(())
+//!     })
+//!     .run()
+//!     .await?;
+//! report.assert_no_findings()?;
+//! ```
+
+use std::{fmt, future::Future};
+
+use anyhow::{ensure, Result};
+use arch_program::{account::AccountMeta, instruction::Instruction, pubkey::Pubkey};
+use arch_sdk::{ProcessedTransaction, Status};
+use bitcoin::key::Keypair;
+use futures::future::BoxFuture;
+
+use crate::TestContext;
+
+pub const DEFAULT_FUZZ_ITERATIONS: usize = 100;
+
+/// Invariant checker: `(ctx, input, processed_tx)`
+type Checker =
+    Box<dyn Fn(TestContext, Vec<u8>, ProcessedTransaction) -> BoxFuture<'static, Result<()>>>;
+
+/// Something a fuzz run found, with the instruction data that triggered it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FuzzFinding {
+    /// The validator stopped answering (or reported a panic) after this input
+    ValidatorPanic { input: Vec<u8>, message: String },
+    /// The input was processed successfully although every input was expected to be rejected
+    UnexpectedSuccess { input: Vec<u8> },
+    /// A checker failed after this input was processed
+    InvariantViolation {
+        input: Vec<u8>,
+        checker: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for FuzzFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FuzzFinding::ValidatorPanic { input, message } => {
+                write!(f, "validator panic on {}: {}", hex::encode(input), message)
+            }
+            FuzzFinding::UnexpectedSuccess { input } => {
+                write!(f, "unexpected success on {}", hex::encode(input))
+            }
+            FuzzFinding::InvariantViolation {
+                input,
+                checker,
+                message,
+            } => write!(
+                f,
+                "invariant '{}' violated after {}: {}",
+                checker,
+                hex::encode(input),
+                message
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzReport {
+    pub iterations: usize,
+    /// Inputs the program accepted
+    pub succeeded: usize,
+    /// Inputs the program (or validator) rejected
+    pub rejected: usize,
+    pub findings: Vec<FuzzFinding>,
+}
+
+impl FuzzReport {
+    pub fn assert_no_findings(&self) -> Result<()> {
+        ensure!(
+            self.findings.is_empty(),
+            "Fuzzing found {} problem(s) in {} iterations:\n  {}",
+            self.findings.len(),
+            self.iterations,
+            self.findings
+                .iter()
+                .map(|finding| finding.to_string())
+                .collect::<Vec<_>>()
+                .join("\n  ")
+        );
+        Ok(())
+    }
+}
+
+/// Deterministic byte-level mutations of a seed input: bit flips, byte overwrites, inserts,
+/// removals and truncation
+#[derive(Debug, Clone)]
+pub struct ByteMutator {
+    seed_input: Vec<u8>,
+    state: u64,
+}
+
+impl ByteMutator {
+    pub fn new(seed_input: Vec<u8>, seed: u64) -> Self {
+        Self {
+            seed_input,
+            state: seed,
+        }
+    }
+
+    /// splitmix64
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n.max(1) as u64) as usize
+    }
+
+    /// The seed input with 1-4 random mutations applied
+    pub fn next_input(&mut self) -> Vec<u8> {
+        let mut input = self.seed_input.clone();
+
+        for _ in 0..=self.below(4) {
+            let position = self.below(input.len());
+            let byte = self.next_u64() as u8;
+            match self.below(5) {
+                0 if !input.is_empty() => input[position] ^= 1 << self.below(8),
+                1 if !input.is_empty() => input[position] = byte,
+                2 => input.insert(self.below(input.len() + 1), byte),
+                3 if !input.is_empty() => {
+                    input.remove(position);
+                }
+                _ => input.truncate(position),
+            }
+        }
+
+        input
+    }
+}
+
+/// A fuzz run being configured, see `TestContext::fuzz`
+pub struct Fuzz<'a, M> {
+    ctx: &'a TestContext,
+    program_id: Pubkey,
+    mutator: M,
+    accounts: Vec<AccountMeta>,
+    signers: Vec<Keypair>,
+    iterations: usize,
+    expect_rejection: bool,
+    checkers: Vec<(String, Checker)>,
+}
+
+impl<M> Fuzz<'_, M>
+where
+    M: FnMut(usize) -> Vec<u8>,
+{
+    /// Accounts passed to every instruction
+    pub fn accounts(mut self, accounts: Vec<AccountMeta>) -> Self {
+        self.accounts = accounts;
+        self
+    }
+
+    /// Signers of every transaction; the first one pays. Defaults to a faucet-funded keypair.
+    pub fn signers(mut self, signers: Vec<Keypair>) -> Self {
+        self.signers = signers;
+        self
+    }
+
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Report every input the program accepts as a finding, for mutators that only produce
+    /// invalid data
+    pub fn expect_rejection(mut self) -> Self {
+        self.expect_rejection = true;
+        self
+    }
+
+    /// Run `checker` after every processed input (successful or not); failures are reported as
+    /// invariant violations named `name`
+    pub fn check<C, Fut>(mut self, name: impl Into<String>, checker: C) -> Self
+    where
+        C: Fn(TestContext, Vec<u8>, ProcessedTransaction) -> Fut + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.checkers.push((
+            name.into(),
+            Box::new(move |ctx, input, processed_tx| Box::pin(checker(ctx, input, processed_tx))),
+        ));
+        self
+    }
+
+    /// Submit `iterations` mutated transactions one at a time. Stops early if the validator goes
+    /// down, since nothing after that would be meaningful.
+    pub async fn run(mut self) -> Result<FuzzReport> {
+        if self.signers.is_empty() {
+            let (payer, _, _) = self.ctx.generate_funded_keypair().await?;
+            self.signers.push(payer);
+        }
+
+        let mut report = FuzzReport {
+            iterations: 0,
+            succeeded: 0,
+            rejected: 0,
+            findings: Vec::new(),
+        };
+
+        for i in 0..self.iterations {
+            let input = (self.mutator)(i);
+            report.iterations += 1;
+
+            let instruction = Instruction {
+                program_id: self.program_id,
+                accounts: self.accounts.clone(),
+                data: input.clone(),
+            };
+
+            let processed_tx = match self
+                .ctx
+                .send_instructions(&[instruction], self.signers.clone())
+                .await
+            {
+                Ok(processed_tx) => processed_tx,
+                Err(e) => {
+                    report.rejected += 1;
+                    if let Err(down) = self.ctx.arch_async_rpc_client.get_block_count().await {
+                        report.findings.push(FuzzFinding::ValidatorPanic {
+                            input,
+                            message: format!("{:#} (validator unreachable: {})", e, down),
+                        });
+                        break;
+                    }
+                    continue;
+                }
+            };
+
+            match &processed_tx.status {
+                Status::Failed(message) if message.contains("panic") => {
+                    report.rejected += 1;
+                    report.findings.push(FuzzFinding::ValidatorPanic {
+                        input: input.clone(),
+                        message: message.clone(),
+                    });
+                }
+                Status::Failed(_) => report.rejected += 1,
+                _ => {
+                    report.succeeded += 1;
+                    if self.expect_rejection {
+                        report.findings.push(FuzzFinding::UnexpectedSuccess {
+                            input: input.clone(),
+                        });
+                    }
+                }
+            }
+
+            for (name, checker) in &self.checkers {
+                if let Err(e) = checker(self.ctx.clone(), input.clone(), processed_tx.clone()).await
+                {
+                    report.findings.push(FuzzFinding::InvariantViolation {
+                        input: input.clone(),
+                        checker: name.clone(),
+                        message: format!("{:#}", e),
+                    });
+                }
+            }
+        }
+
+        tracing::info!(
+            "Fuzzed {} inputs: {} accepted, {} rejected, {} findings",
+            report.iterations,
+            report.succeeded,
+            report.rejected,
+            report.findings.len()
+        );
+
+        Ok(report)
+    }
+}
+
+impl TestContext {
+    /// Fuzz `program_id` with instruction data from `mutator(i)` (e.g. `ByteMutator::next_input`),
+    /// configured through the returned builder
+    pub fn fuzz<M>(&self, program_id: Pubkey, mutator: M) -> Fuzz<'_, M>
+    where
+        M: FnMut(usize) -> Vec<u8>,
+    {
+        Fuzz {
+            ctx: self,
+            program_id,
+            mutator,
+            accounts: Vec::new(),
+            signers: Vec::new(),
+            iterations: DEFAULT_FUZZ_ITERATIONS,
+            expect_rejection: false,
+            checkers: Vec::new(),
+        }
+    }
+}
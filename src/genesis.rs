@@ -0,0 +1,114 @@
+//! Accounts declared up front (`TestRunnerConfig::genesis_accounts`) and created during setup.
+//!
+//! The validator has no way to load accounts at genesis, so they're created with transactions:
+//! the account is created owned by the system program, its data written, then it's assigned to
+//! its final owner.
+
+use anyhow::{anyhow, Context, Result};
+use arch_program::{instruction::Instruction, pubkey::Pubkey, system_instruction};
+use bitcoin::key::Keypair;
+
+use crate::{GenesisAccount, ProcessedTransactionExt, TestContext};
+
+/// Account data bytes written per transaction
+const GENESIS_DATA_CHUNK_LEN: usize = 800;
+
+impl TestContext {
+    /// Create `accounts` (see `TestRunnerConfig::genesis_accounts`), paid for by one
+    /// faucet-funded authority
+    pub(crate) async fn create_genesis_accounts(&self, accounts: &[GenesisAccount]) -> Result<()> {
+        if accounts.is_empty() {
+            return Ok(());
+        }
+
+        let (authority_kp, _, _) = self.generate_funded_keypair().await?;
+
+        for account in accounts {
+            let data = match &account.data_path {
+                Some(path) => std::fs::read(path).with_context(|| {
+                    format!("Failed to read genesis account data {}", path.display())
+                })?,
+                None => Vec::new(),
+            };
+
+            self.create_account_with_data(
+                authority_kp,
+                account.keypair,
+                account.lamports,
+                account.owner,
+                &data,
+            )
+            .await
+            .context("Failed to create genesis account")?;
+        }
+
+        Ok(())
+    }
+
+    /// Create `account_kp`'s account holding exactly `lamports`, `data` and `owner`, paid for by
+    /// `authority_kp`
+    pub(crate) async fn create_account_with_data(
+        &self,
+        authority_kp: Keypair,
+        account_kp: Keypair,
+        lamports: u64,
+        owner: Pubkey,
+        data: &[u8],
+    ) -> Result<Pubkey> {
+        let (_, pubkey, _) = self
+            .create_anchored_account_for(
+                authority_kp,
+                account_kp,
+                lamports,
+                data.len() as u64,
+                Pubkey::system_program(),
+            )
+            .await?;
+
+        for (i, chunk) in data.chunks(GENESIS_DATA_CHUNK_LEN).enumerate() {
+            let offset = (i * GENESIS_DATA_CHUNK_LEN) as u32;
+            let instruction = system_instruction::write_bytes(
+                offset,
+                chunk.len() as u32,
+                chunk.to_vec(),
+                &pubkey,
+            );
+            self.send_account_setup_instruction(instruction, authority_kp, account_kp)
+                .await
+                .with_context(|| format!("Failed to write data of account {}", pubkey))?;
+        }
+
+        if owner != Pubkey::system_program() {
+            let instruction = system_instruction::assign(&pubkey, &owner);
+            self.send_account_setup_instruction(instruction, authority_kp, account_kp)
+                .await
+                .with_context(|| format!("Failed to assign account {} to {}", pubkey, owner))?;
+        }
+
+        tracing::debug!(
+            "Created account {} ({} lamports, {} bytes, owner {})",
+            pubkey,
+            lamports,
+            data.len(),
+            owner
+        );
+
+        Ok(pubkey)
+    }
+
+    async fn send_account_setup_instruction(
+        &self,
+        instruction: Instruction,
+        authority_kp: Keypair,
+        account_kp: Keypair,
+    ) -> Result<()> {
+        let processed_tx = self
+            .send_instructions(&[instruction], vec![authority_kp, account_kp])
+            .await?;
+
+        match processed_tx.failure() {
+            Some(failure) => Err(anyhow!("{}", failure)),
+            None => Ok(()),
+        }
+    }
+}
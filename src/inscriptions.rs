@@ -0,0 +1,236 @@
+//! Ordinals inscription helpers: commit + reveal a simple inscription on regtest, then query it
+//! back through Titan.
+//!
+//! The commit output is a taproot address whose only script leaf holds the inscription envelope;
+//! the reveal spends it through that leaf, which puts the envelope on chain and inscribes the
+//! first sat of the reveal's only output.
+
+use anyhow::{anyhow, ensure, Context, Result};
+use bitcoin::{
+    absolute::LockTime,
+    hashes::Hash,
+    key::Keypair,
+    opcodes::{
+        all::{OP_CHECKSIG, OP_ENDIF, OP_IF},
+        OP_FALSE,
+    },
+    script::{Builder, PushBytesBuf},
+    secp256k1::{Message, Secp256k1},
+    sighash::{Prevouts, SighashCache},
+    taproot::{self, LeafVersion, TaprootBuilder},
+    transaction::Version,
+    Address, Amount, OutPoint, ScriptBuf, Sequence, TapLeafHash, TapSighashType, Transaction, TxIn,
+    TxOut, Txid, Witness,
+};
+use bitcoincore_rpc::RpcApi;
+
+use crate::TestContext;
+
+/// Value of the inscribed output
+pub const INSCRIPTION_POSTAGE: Amount = Amount::from_sat(10_000);
+
+/// Lowest fee rate paid by reveal transactions (sat/vB); they pay the node's relay floor when
+/// that's higher, e.g. under `FeeScenario::Congested`
+const MIN_REVEAL_FEE_RATE: u64 = 2;
+
+/// Data pushes are limited to 520 bytes, so bodies are split into chunks
+const MAX_PUSH_SIZE: usize = 520;
+
+/// An inscription revealed by `TestContext::inscribe`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inscription {
+    /// Ordinals inscription id, `<reveal txid>i0`
+    pub id: String,
+    pub commit_txid: Txid,
+    pub reveal_txid: Txid,
+    /// Output holding the inscribed sat
+    pub outpoint: OutPoint,
+}
+
+impl TestContext {
+    /// Inscribe `body` with `content_type`, sending the inscribed sat to a fresh test wallet
+    /// address. Both transactions are mined, and Titan has indexed the reveal when this returns.
+    pub async fn inscribe(&self, content_type: &str, body: &[u8]) -> Result<Inscription> {
+        let secp = Secp256k1::new();
+        let (keypair, _, _) = self.generate_new_keypair();
+        let (internal_key, _) = keypair.x_only_public_key();
+
+        let reveal_script = inscription_script(&keypair, content_type, body)?;
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(0, reveal_script.clone())?
+            .finalize(&secp, internal_key)
+            .map_err(|_| anyhow!("Failed to finalize inscription taproot tree"))?;
+        let control_block = spend_info
+            .control_block(&(reveal_script.clone(), LeafVersion::TapScript))
+            .context("Inscription script missing from taproot tree")?;
+        let commit_address = Address::p2tr_tweaked(spend_info.output_key(), self.network);
+
+        // script + control block + signature, discounted as witness data, plus the fixed part
+        let reveal_vsize = 150 + (reveal_script.len() as u64 + 100) / 4;
+        let reveal_fee_rate = self.relay_fee_rate().await?.max(MIN_REVEAL_FEE_RATE);
+        let reveal_fee = Amount::from_sat(reveal_vsize * reveal_fee_rate);
+        let commit_amount = INSCRIPTION_POSTAGE + reveal_fee;
+
+        let funding_address = commit_address.clone();
+        let commit_txid = self
+            .with_bitcoin_client(move |client| {
+                client.send_to_address(
+                    &funding_address,
+                    commit_amount,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+            })
+            .await
+            .context("Failed to send inscription commit")?;
+
+        let commit_tx = self
+            .with_bitcoin_client(move |client| client.get_raw_transaction(&commit_txid, None))
+            .await?;
+        let commit_vout = commit_tx
+            .output
+            .iter()
+            .position(|output| output.script_pubkey == commit_address.script_pubkey())
+            .context("Commit transaction doesn't pay the inscription address")?;
+        let commit_output = commit_tx.output[commit_vout].clone();
+
+        self.confirm_and_index().await?;
+
+        let recipient = self.bitcoin().client.get_new_address().await?;
+        let mut reveal = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(commit_txid, commit_vout as u32),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: INSCRIPTION_POSTAGE,
+                script_pubkey: recipient.script_pubkey(),
+            }],
+        };
+
+        let leaf_hash = TapLeafHash::from_script(&reveal_script, LeafVersion::TapScript);
+        let sighash = SighashCache::new(&reveal).taproot_script_spend_signature_hash(
+            0,
+            &Prevouts::All(&[commit_output]),
+            leaf_hash,
+            TapSighashType::Default,
+        )?;
+        let signature = taproot::Signature {
+            signature: secp
+                .sign_schnorr_no_aux_rand(&Message::from_digest(sighash.to_byte_array()), &keypair),
+            sighash_type: TapSighashType::Default,
+        };
+
+        reveal.input[0].witness = Witness::from_slice(&[
+            signature.to_vec(),
+            reveal_script.to_bytes(),
+            control_block.serialize(),
+        ]);
+
+        let reveal_txid = self
+            .bitcoin()
+            .client
+            .send_raw_transaction(reveal)
+            .await
+            .context("Failed to send inscription reveal")?;
+
+        self.confirm_and_index().await?;
+
+        Ok(Inscription {
+            id: format!("{}i0", reveal_txid),
+            commit_txid,
+            reveal_txid,
+            outpoint: OutPoint::new(reveal_txid, 0),
+        })
+    }
+
+    /// The lowest fee rate (sat/vB) the node relays right now: the higher of its
+    /// `minrelaytxfee` and the mempool minimum, which rises as the mempool fills
+    async fn relay_fee_rate(&self) -> Result<u64> {
+        let mempool = self
+            .with_bitcoin_client(|client| client.get_mempool_info())
+            .await
+            .context("Failed to read the node's relay fee floor")?;
+        let per_kvb = mempool.mempool_min_fee.max(mempool.min_relay_tx_fee);
+
+        Ok(per_kvb.to_sat().div_ceil(1000))
+    }
+
+    /// Titan's view of an inscription: its content type and body
+    pub async fn fetch_inscription(&self, inscription_id: &str) -> Result<(String, Vec<u8>)> {
+        let url = format!("{}/inscription/{}", self.titan().http_url(), inscription_id);
+
+        let response = reqwest::get(&url)
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| {
+                format!("Failed to fetch inscription {} from titan", inscription_id)
+            })?;
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let body = response.bytes().await?.to_vec();
+
+        Ok((content_type, body))
+    }
+
+    /// Assert that Titan indexed `inscription` with the given content type and body
+    pub async fn assert_inscription(
+        &self,
+        inscription: &Inscription,
+        content_type: &str,
+        body: &[u8],
+    ) -> Result<()> {
+        let (indexed_content_type, indexed_body) = self.fetch_inscription(&inscription.id).await?;
+
+        ensure!(
+            indexed_content_type == content_type,
+            "Expected inscription {} to have content type {}, titan reports {}",
+            inscription.id,
+            content_type,
+            indexed_content_type
+        );
+        ensure!(
+            indexed_body == body,
+            "Inscription {} body differs from what titan indexed ({} vs {} bytes)",
+            inscription.id,
+            body.len(),
+            indexed_body.len()
+        );
+
+        Ok(())
+    }
+}
+
+/// `<pubkey> OP_CHECKSIG OP_FALSE OP_IF "ord" 1 <content type> 0 <body...> OP_ENDIF`
+fn inscription_script(keypair: &Keypair, content_type: &str, body: &[u8]) -> Result<ScriptBuf> {
+    let (pubkey, _) = keypair.x_only_public_key();
+
+    let mut builder = Builder::new()
+        .push_x_only_key(&pubkey)
+        .push_opcode(OP_CHECKSIG)
+        .push_opcode(OP_FALSE)
+        .push_opcode(OP_IF)
+        .push_slice(b"ord")
+        .push_slice([1u8]) // content type tag
+        .push_slice(PushBytesBuf::try_from(content_type.as_bytes().to_vec())?)
+        .push_slice(PushBytesBuf::new()); // body separator
+
+    for chunk in body.chunks(MAX_PUSH_SIZE) {
+        builder = builder.push_slice(PushBytesBuf::try_from(chunk.to_vec())?);
+    }
+
+    Ok(builder.push_opcode(OP_ENDIF).into_script())
+}
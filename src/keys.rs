@@ -0,0 +1,172 @@
+//! Keypair helpers beyond `generate_new_keypair`: deterministic, reproducible identities.
+
+use std::{path::Path, str::FromStr};
+
+use anyhow::{anyhow, Context, Result};
+use arch_program::pubkey::Pubkey;
+use bip39::Mnemonic;
+use bitcoin::{
+    bip32::{DerivationPath, Xpriv},
+    hashes::{sha256, Hash},
+    key::Keypair,
+    secp256k1::{Secp256k1, SecretKey},
+    Address, Network,
+};
+
+use crate::TestContext;
+
+/// The BIP39 test vector mnemonic, for wallet-flow tests that don't care which mnemonic they use
+pub const TEST_MNEMONIC: &str =
+    "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+/// Derive a keypair from `seed` (a label like `"alice"`, or raw bytes); the same seed always
+/// yields the same keypair, pubkey and address
+pub fn keypair_from_seed(
+    seed: impl AsRef<[u8]>,
+    network: Network,
+) -> Result<(Keypair, Pubkey, Address)> {
+    let secret = sha256::Hash::hash(seed.as_ref());
+    let secret_key = SecretKey::from_slice(secret.as_byte_array())?;
+
+    Ok(keypair_parts(
+        Keypair::from_secret_key(&Secp256k1::new(), &secret_key),
+        network,
+    ))
+}
+
+/// Derive the keypair at BIP32 `path` (e.g. `m/86'/1'/0'/0/0`) from a BIP39 `mnemonic` (no
+/// passphrase), the same way a wallet would
+pub fn keypair_from_mnemonic(
+    mnemonic: &str,
+    path: &str,
+    network: Network,
+) -> Result<(Keypair, Pubkey, Address)> {
+    let secp = Secp256k1::new();
+    let seed = Mnemonic::parse(mnemonic)
+        .context("Invalid BIP39 mnemonic")?
+        .to_seed("");
+    let path = DerivationPath::from_str(path)
+        .with_context(|| format!("Invalid derivation path {}", path))?;
+
+    let xpriv = Xpriv::new_master(network, &seed)?.derive_priv(&secp, &path)?;
+
+    Ok(keypair_parts(xpriv.to_keypair(&secp), network))
+}
+
+/// Load a keypair fixture from a JSON file. Accepted formats:
+///
+/// - a byte array, either the 32 byte secret key or 64 bytes (secret key then x-only public key)
+/// - a hex string with the 32 byte secret key
+/// - an object with the hex secret key under `secret_key` or `private_key`
+pub fn load_keypair(
+    path: impl AsRef<Path>,
+    network: Network,
+) -> Result<(Keypair, Pubkey, Address)> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read keypair file {}", path.display()))?;
+    let json: serde_json::Value = serde_json::from_str(&contents)
+        .with_context(|| format!("Keypair file {} isn't valid JSON", path.display()))?;
+
+    let secret = match &json {
+        serde_json::Value::Array(bytes) => bytes
+            .iter()
+            .map(|byte| byte.as_u64().and_then(|byte| u8::try_from(byte).ok()))
+            .collect::<Option<Vec<u8>>>()
+            .context("Keypair byte array must only contain bytes")?,
+        serde_json::Value::String(secret) => hex::decode(secret)?,
+        serde_json::Value::Object(fields) => fields
+            .get("secret_key")
+            .or_else(|| fields.get("private_key"))
+            .and_then(|secret| secret.as_str())
+            .map(hex::decode)
+            .context("Keypair object has no `secret_key` or `private_key` hex string")??,
+        _ => return Err(anyhow!("Unsupported keypair format in {}", path.display())),
+    };
+
+    let secret_key = match secret.len() {
+        32 | 64 => SecretKey::from_slice(&secret[..32])?,
+        len => {
+            return Err(anyhow!(
+                "Keypair file {} holds {} bytes, expected 32 or 64",
+                path.display(),
+                len
+            ))
+        }
+    };
+
+    let keypair = Keypair::from_secret_key(&Secp256k1::new(), &secret_key);
+
+    // a 64 byte keypair carries its public key; refuse fixtures whose halves don't match
+    if secret.len() == 64 && secret[32..] != keypair.x_only_public_key().0.serialize() {
+        return Err(anyhow!(
+            "Public key in {} doesn't match its secret key",
+            path.display()
+        ));
+    }
+
+    Ok(keypair_parts(keypair, network))
+}
+
+/// The `(keypair, pubkey, address)` triple `generate_new_keypair` returns, for a given keypair
+pub(crate) fn keypair_parts(keypair: Keypair, network: Network) -> (Keypair, Pubkey, Address) {
+    let (x_only_pubkey, _) = keypair.x_only_public_key();
+    let pubkey = Pubkey::from_slice(&x_only_pubkey.serialize());
+    let address = Address::p2tr(&Secp256k1::new(), x_only_pubkey, None, network);
+
+    (keypair, pubkey, address)
+}
+
+impl TestContext {
+    /// Deterministic alternative to `generate_new_keypair`, see `keypair_from_seed`
+    pub fn keypair_from_seed(&self, seed: impl AsRef<[u8]>) -> Result<(Keypair, Pubkey, Address)> {
+        keypair_from_seed(seed, self.network)
+    }
+
+    /// Derive a wallet keypair, see `keypair_from_mnemonic`
+    pub fn keypair_from_mnemonic(
+        &self,
+        mnemonic: &str,
+        path: &str,
+    ) -> Result<(Keypair, Pubkey, Address)> {
+        keypair_from_mnemonic(mnemonic, path, self.network)
+    }
+
+    /// Load a keypair fixture, see `load_keypair`
+    pub fn load_keypair(&self, path: impl AsRef<Path>) -> Result<(Keypair, Pubkey, Address)> {
+        load_keypair(path, self.network)
+    }
+
+    /// The keypair for `name`, created on first use and the same for the rest of the test.
+    /// Derived from the name, so actors are also stable across runs.
+    pub async fn actor(&self, name: &str) -> Result<(Keypair, Pubkey, Address)> {
+        let mut actors = self.actors().lock().await;
+
+        let keypair = match actors.get(name) {
+            Some((keypair, _)) => *keypair,
+            None => {
+                let (keypair, _, _) = self.keypair_from_seed(name)?;
+                actors.insert(name.to_string(), (keypair, false));
+                keypair
+            }
+        };
+
+        Ok(keypair_parts(keypair, self.network))
+    }
+
+    /// Like `actor`, but faucet-funds the keypair the first time it's requested this way
+    pub async fn funded_actor(&self, name: &str) -> Result<(Keypair, Pubkey, Address)> {
+        let actor = self.actor(name).await?;
+
+        // held while funding, so concurrent callers don't fund the same actor twice
+        let mut actors = self.actors().lock().await;
+        if let Some((keypair, funded)) = actors.get_mut(name) {
+            if !*funded {
+                self.fund_keypair_with_faucet(keypair).await?;
+                *funded = true;
+            }
+        }
+
+        Ok(actor)
+    }
+}
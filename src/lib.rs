@@ -1,15 +1,116 @@
+mod accounts;
+mod anchoring;
+mod artifacts;
+mod assertions;
+mod chain_sync;
+mod cloning;
+mod commitment;
+mod components;
+mod compose;
+mod compute_units;
 mod containers;
+mod environment;
+mod error;
+mod event_recorder;
+mod exec;
+pub mod facade;
+mod fee_bumping;
+mod fork;
+mod funding;
+mod fuzz;
+mod genesis;
+mod inscriptions;
+mod keys;
+mod load;
+mod malformed;
+mod mempool;
+mod multisig;
+mod network_faults;
+mod network_mode;
+mod pda;
+mod perf;
+mod programs;
+mod rent;
+mod reorg;
+mod rpc_capture;
+mod rpc_cassette;
+mod rpc_flood;
+mod runes;
+mod seeded_accounts;
+mod snapshots;
+pub mod strategies;
+mod tamper;
+mod taproot;
 mod test_config;
 mod test_context;
 mod test_runner;
+mod test_service;
+mod timelock;
+mod titan_subscription;
+mod token_balances;
+mod tokens;
+mod transactions;
+mod validator_data;
+mod validator_subscription;
 
+pub use accounts::*;
+pub use anchoring::*;
+pub use assertions::*;
+pub use chain_sync::*;
+pub use cloning::*;
+pub use commitment::*;
+pub use components::*;
+pub use compose::*;
+pub use compute_units::*;
 pub use containers::*;
+pub use environment::*;
+pub use error::*;
+pub use event_recorder::*;
+pub use exec::*;
+pub use facade::{ArchTestContext, MockArchTestContext, MOCK_FAUCET_LAMPORTS};
+pub use fork::*;
+pub use fuzz::*;
+pub use inscriptions::*;
+pub use keys::*;
+pub use load::*;
+pub use malformed::*;
+pub use mempool::*;
+pub use multisig::*;
+pub use network_faults::*;
+pub use network_mode::*;
+pub use perf::*;
+pub use programs::*;
+pub use rent::*;
+pub use reorg::*;
+pub use rpc_capture::*;
+pub use rpc_cassette::*;
+pub use rpc_flood::*;
+pub use runes::*;
+pub use snapshots::*;
+pub use tamper::*;
+pub use taproot::*;
 pub use test_config::*;
 pub use test_context::*;
 pub use test_runner::*;
+pub use test_service::*;
+pub use timelock::*;
+pub use titan_subscription::*;
+pub use token_balances::*;
+pub use transactions::*;
+pub use validator_data::*;
+pub use validator_subscription::*;
+
+// re-exported for `assert_account_snapshot!`, so callers don't need a matching insta version
+#[doc(hidden)]
+pub use insta;
+
+// re-exported for `strategies` and `TestContext::run_proptest`
+pub use proptest;
 
 /// Initialize tracing for integration tests.
-fn init_tracing() {
+///
+/// Only the first call takes effect; the format requested by later runs is ignored.
+fn init_tracing(format: TracingFormat) {
     static INIT: std::sync::Once = std::sync::Once::new();
     INIT.call_once(|| {
         use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
@@ -17,9 +118,18 @@ fn init_tracing() {
         let env_filter = EnvFilter::try_from_default_env() //
             .unwrap_or_else(|_| EnvFilter::new("info"));
 
-        tracing_subscriber::registry()
-            .with(env_filter)
-            .with(tracing_subscriber::fmt::layer())
-            .init();
+        let registry = tracing_subscriber::registry().with(env_filter);
+
+        match format {
+            TracingFormat::Text => registry.with(tracing_subscriber::fmt::layer()).init(),
+            TracingFormat::Json => registry
+                .with(
+                    tracing_subscriber::fmt::layer()
+                        .json()
+                        .with_current_span(true)
+                        .with_span_list(false),
+                )
+                .init(),
+        }
     });
 }
@@ -0,0 +1,285 @@
+//! Throughput / load testing against the environment.
+//!
+//! ```ignore
+//! let report = LoadTest::new(|i| {
+//!     let ctx = ctx.clone();
+//!     async move { build_transfer(&ctx, i).await }
+//! })
+//! .duration(Duration::from_secs(30))
+//! .concurrency(16)
+//! .warmup(20)
+//! .run(&ctx)
+//! .await?;
+//! report.assert_min_tps(50.0)?;
+//! ```
+
+use std::{
+    fmt,
+    future::Future,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use anyhow::{ensure, Result};
+use arch_sdk::{RuntimeTransaction, Status};
+
+use crate::TestContext;
+
+pub const DEFAULT_LOAD_CONCURRENCY: usize = 8;
+pub const DEFAULT_LOAD_TRANSACTION_COUNT: usize = 100;
+
+/// Failure messages kept in a `LoadReport`
+const MAX_REPORTED_ERRORS: usize = 10;
+
+/// When a `LoadTest` stops sending
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadLimit {
+    /// Send exactly this many transactions
+    Count(usize),
+    /// Keep sending until this much time has passed (in-flight transactions still finish)
+    Duration(Duration),
+}
+
+/// Builder for a load test: `generator(i)` produces the `i`th transaction to send
+pub struct LoadTest<G> {
+    generator: G,
+    limit: LoadLimit,
+    concurrency: usize,
+    warmup: usize,
+}
+
+impl<G, Fut> LoadTest<G>
+where
+    G: Fn(usize) -> Fut,
+    Fut: Future<Output = Result<RuntimeTransaction>>,
+{
+    pub fn new(generator: G) -> Self {
+        Self {
+            generator,
+            limit: LoadLimit::Count(DEFAULT_LOAD_TRANSACTION_COUNT),
+            concurrency: DEFAULT_LOAD_CONCURRENCY,
+            warmup: 0,
+        }
+    }
+
+    /// Send `count` transactions (the default, with `DEFAULT_LOAD_TRANSACTION_COUNT`)
+    pub fn count(mut self, count: usize) -> Self {
+        self.limit = LoadLimit::Count(count);
+        self
+    }
+
+    /// Keep sending for `duration`
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.limit = LoadLimit::Duration(duration);
+        self
+    }
+
+    /// Transactions in flight at once
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Send `count` transactions first and leave them out of the report
+    pub fn warmup(mut self, count: usize) -> Self {
+        self.warmup = count;
+        self
+    }
+
+    pub async fn run(&self, ctx: &TestContext) -> Result<LoadReport> {
+        if self.warmup > 0 {
+            tracing::info!("Load test warmup: {} transactions", self.warmup);
+            self.run_phase(ctx, 0, LoadLimit::Count(self.warmup)).await;
+        }
+
+        tracing::info!(
+            "Load test: {:?} at concurrency {}",
+            self.limit,
+            self.concurrency
+        );
+        let started = Instant::now();
+        let samples = self.run_phase(ctx, self.warmup, self.limit).await;
+
+        let report = LoadReport::new(samples, started.elapsed());
+        tracing::info!("Load test finished: {}", report);
+
+        Ok(report)
+    }
+
+    /// Run `concurrency` workers pulling transaction indexes (from `first_index`) until `limit`
+    async fn run_phase(
+        &self,
+        ctx: &TestContext,
+        first_index: usize,
+        limit: LoadLimit,
+    ) -> Vec<LoadSample> {
+        let next = &AtomicUsize::new(0);
+        let deadline = match limit {
+            LoadLimit::Duration(duration) => Some(Instant::now() + duration),
+            LoadLimit::Count(_) => None,
+        };
+
+        let workers = (0..self.concurrency).map(|_| async move {
+            let mut samples = Vec::new();
+
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let done = match (limit, deadline) {
+                    (LoadLimit::Count(count), _) => i >= count,
+                    (_, Some(deadline)) => Instant::now() >= deadline,
+                    (_, None) => true,
+                };
+                if done {
+                    break;
+                }
+
+                samples.push(self.send_one(ctx, first_index + i).await);
+            }
+
+            samples
+        });
+
+        futures::future::join_all(workers)
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    async fn send_one(&self, ctx: &TestContext, i: usize) -> LoadSample {
+        let started = Instant::now();
+
+        let error = match (self.generator)(i).await {
+            Ok(transaction) => match ctx.send_transaction(transaction).await {
+                Ok(txid) => match ctx.wait_for_transaction(&txid).await {
+                    Ok(processed_tx) => match processed_tx.status {
+                        Status::Failed(message) => Some(format!("{}: {}", txid, message)),
+                        _ => None,
+                    },
+                    Err(e) => Some(format!("{:#}", e)),
+                },
+                Err(e) => Some(format!("Failed to send: {:#}", e)),
+            },
+            Err(e) => Some(format!("Failed to generate transaction {}: {:#}", i, e)),
+        };
+
+        LoadSample {
+            latency: started.elapsed(),
+            error,
+        }
+    }
+}
+
+struct LoadSample {
+    latency: Duration,
+    error: Option<String>,
+}
+
+/// What a `LoadTest` measured; latencies are from generating a transaction to it being processed
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadReport {
+    pub sent: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub elapsed: Duration,
+    /// Successful transactions per second
+    pub tps: f64,
+    pub latency_p50: Duration,
+    pub latency_p90: Duration,
+    pub latency_p99: Duration,
+    pub latency_max: Duration,
+    /// The first few failure messages
+    pub errors: Vec<String>,
+}
+
+impl LoadReport {
+    fn new(samples: Vec<LoadSample>, elapsed: Duration) -> Self {
+        let mut latencies = samples
+            .iter()
+            .map(|sample| sample.latency)
+            .collect::<Vec<_>>();
+        latencies.sort();
+
+        let errors = samples
+            .iter()
+            .filter_map(|sample| sample.error.clone())
+            .collect::<Vec<_>>();
+        let succeeded = samples.len() - errors.len();
+
+        Self {
+            sent: samples.len(),
+            succeeded,
+            failed: errors.len(),
+            elapsed,
+            tps: succeeded as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            latency_p50: percentile(&latencies, 50.0),
+            latency_p90: percentile(&latencies, 90.0),
+            latency_p99: percentile(&latencies, 99.0),
+            latency_max: latencies.last().copied().unwrap_or_default(),
+            errors: errors.into_iter().take(MAX_REPORTED_ERRORS).collect(),
+        }
+    }
+
+    pub fn assert_min_tps(&self, min_tps: f64) -> Result<()> {
+        ensure!(
+            self.tps >= min_tps,
+            "Load test reached {:.1} tx/s, expected at least {:.1} ({})",
+            self.tps,
+            min_tps,
+            self
+        );
+        Ok(())
+    }
+
+    pub fn assert_no_failures(&self) -> Result<()> {
+        ensure!(
+            self.failed == 0,
+            "{} of {} load test transactions failed, first errors:\n  {}",
+            self.failed,
+            self.sent,
+            self.errors.join("\n  ")
+        );
+        Ok(())
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} succeeded in {:?} ({:.1} tx/s), latency p50 {:?} / p90 {:?} / p99 {:?} / max {:?}",
+            self.succeeded,
+            self.sent,
+            self.elapsed,
+            self.tps,
+            self.latency_p50,
+            self.latency_p90,
+            self.latency_p99,
+            self.latency_max
+        )
+    }
+}
+
+/// Nearest-rank percentile of sorted `values`
+pub(crate) fn percentile(values: &[Duration], p: f64) -> Duration {
+    if values.is_empty() {
+        return Duration::ZERO;
+    }
+
+    let rank = (p / 100.0 * values.len() as f64).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile_uses_nearest_rank() {
+        let values = (1..=10).map(Duration::from_millis).collect::<Vec<_>>();
+        assert_eq!(percentile(&values, 50.0), Duration::from_millis(5));
+        assert_eq!(percentile(&values, 90.0), Duration::from_millis(9));
+        assert_eq!(percentile(&values, 99.0), Duration::from_millis(10));
+        assert_eq!(percentile(&[], 50.0), Duration::ZERO);
+    }
+}
@@ -0,0 +1,155 @@
+//! Deliberately invalid transactions, for asserting the validator rejects each class of mistake.
+//!
+//! ```ignore
+//! for malformation in Malformation::ALL {
+//!     let tx = ctx
+//!         .build_malformed_transaction(malformation, &instructions, signers.clone())
+//!         .await?;
+//!     ctx.assert_transaction_rejected(tx, "").await?;
+//! }
+//! ```
+
+use std::fmt;
+
+use anyhow::{ensure, Context, Result};
+use arch_program::{instruction::Instruction, pubkey::Pubkey, sanitized::ArchMessage};
+use arch_sdk::{build_and_sign_transaction, generate_new_keypair, RuntimeTransaction, Status};
+use bitcoin::key::Keypair;
+
+use crate::TestContext;
+
+/// Bytes appended to the first instruction's data by `Malformation::OversizedData`, well past
+/// any transaction size limit
+pub const OVERSIZED_INSTRUCTION_DATA_LEN: usize = 64 * 1024;
+
+/// What's wrong with a transaction built by `build_malformed_transaction`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Malformation {
+    /// The last signer's signature is made by an unrelated keypair
+    WrongSigner,
+    /// The last account meta of the first instruction is left out
+    MissingAccount,
+    /// The first instruction's data is padded by `OVERSIZED_INSTRUCTION_DATA_LEN` bytes
+    OversizedData,
+    /// The message references the genesis block instead of a recent one
+    StaleBlockhash,
+    /// The last signature is a copy of the first (the payer's)
+    DuplicateSignature,
+}
+
+impl Malformation {
+    pub const ALL: [Malformation; 5] = [
+        Malformation::WrongSigner,
+        Malformation::MissingAccount,
+        Malformation::OversizedData,
+        Malformation::StaleBlockhash,
+        Malformation::DuplicateSignature,
+    ];
+}
+
+impl fmt::Display for Malformation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Malformation::WrongSigner => "wrong signer",
+            Malformation::MissingAccount => "missing account",
+            Malformation::OversizedData => "oversized data",
+            Malformation::StaleBlockhash => "stale blockhash",
+            Malformation::DuplicateSignature => "duplicate signature",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl TestContext {
+    /// Build and sign a transaction of `instructions` (the first signer pays) that's invalid in
+    /// the way `malformation` describes
+    pub async fn build_malformed_transaction(
+        &self,
+        malformation: Malformation,
+        instructions: &[Instruction],
+        mut signers: Vec<Keypair>,
+    ) -> Result<RuntimeTransaction> {
+        ensure!(
+            !instructions.is_empty() && !signers.is_empty(),
+            "A malformed transaction needs at least one instruction and one signer"
+        );
+
+        let payer = Pubkey::from_slice(&signers[0].x_only_public_key().0.serialize());
+        let mut instructions = instructions.to_vec();
+
+        let recent_blockhash = match malformation {
+            Malformation::StaleBlockhash => {
+                let genesis_hash = self.arch_async_rpc_client.get_block_hash(0).await?;
+                genesis_hash
+                    .parse()
+                    .context("Failed to parse genesis block hash")?
+            }
+            _ => self.get_best_blockhash().await?,
+        };
+
+        match malformation {
+            Malformation::WrongSigner => {
+                let (impostor, _, _) = generate_new_keypair(self.network);
+                *signers.last_mut().expect("signers isn't empty") = impostor;
+            }
+            Malformation::MissingAccount => {
+                ensure!(
+                    instructions[0].accounts.pop().is_some(),
+                    "The first instruction has no accounts to leave out"
+                );
+            }
+            Malformation::OversizedData => instructions[0]
+                .data
+                .extend(std::iter::repeat(0xff).take(OVERSIZED_INSTRUCTION_DATA_LEN)),
+            Malformation::StaleBlockhash | Malformation::DuplicateSignature => {}
+        }
+
+        let message = ArchMessage::new(&instructions, Some(payer), recent_blockhash);
+        let mut transaction = build_and_sign_transaction(message, signers, self.network)?;
+
+        if malformation == Malformation::DuplicateSignature {
+            let first = transaction.signatures[0].clone();
+            match transaction.signatures.len() {
+                1 => transaction.signatures.push(first),
+                len => transaction.signatures[len - 1] = first,
+            }
+        }
+
+        Ok(transaction)
+    }
+
+    /// Send `transaction` and assert the validator rejects it, either when it's submitted or
+    /// when it's processed, with an error containing `expected_error` (case-insensitive; `""`
+    /// accepts any error). Returns the error.
+    pub async fn assert_transaction_rejected(
+        &self,
+        transaction: RuntimeTransaction,
+        expected_error: &str,
+    ) -> Result<String> {
+        let error = match self.send_transaction(transaction).await {
+            Err(e) => format!("{:#}", e),
+            Ok(txid) => match self.wait_for_transaction(&txid).await {
+                Err(e) => format!("{:#}", e),
+                Ok(processed_tx) => match processed_tx.status {
+                    Status::Failed(message) => message,
+                    status => anyhow::bail!(
+                        "Expected transaction {} to be rejected, its status is {:?}",
+                        txid,
+                        status
+                    ),
+                },
+            },
+        };
+
+        ensure!(
+            error
+                .to_lowercase()
+                .contains(&expected_error.to_lowercase()),
+            "Transaction was rejected, but with '{}' rather than '{}'",
+            error,
+            expected_error
+        );
+
+        Ok(error)
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{ensure, Result};
+use bitcoin::Txid;
+use bitcoincore_rpc::{json::GetMempoolEntryResult, RpcApi};
+
+use crate::TestContext;
+
+pub const DEFAULT_MEMPOOL_TIMEOUT: Duration = Duration::from_secs(30);
+pub const MEMPOOL_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+impl TestContext {
+    /// `getrawmempool`
+    pub async fn bitcoin_mempool(&self) -> Result<Vec<Txid>> {
+        self.with_bitcoin_client(|client| client.get_raw_mempool())
+            .await
+    }
+
+    /// `getmempoolentry`; fails if `txid` isn't in the mempool
+    pub async fn bitcoin_mempool_entry(&self, txid: Txid) -> Result<GetMempoolEntryResult> {
+        self.with_bitcoin_client(move |client| client.get_mempool_entry(&txid))
+            .await
+    }
+
+    pub async fn assert_in_mempool(&self, txid: Txid) -> Result<GetMempoolEntryResult> {
+        let mempool = self.bitcoin_mempool().await?;
+        ensure!(
+            mempool.contains(&txid),
+            "Expected {} in the mempool, found {} other transaction(s)",
+            txid,
+            mempool.len()
+        );
+
+        self.bitcoin_mempool_entry(txid).await
+    }
+
+    pub async fn assert_not_in_mempool(&self, txid: Txid) -> Result<()> {
+        let mempool = self.bitcoin_mempool().await?;
+        ensure!(
+            !mempool.contains(&txid),
+            "Expected {} not to be in the mempool",
+            txid
+        );
+
+        Ok(())
+    }
+
+    /// Wait until the mempool holds exactly `size` transactions
+    pub async fn wait_for_mempool_size(&self, size: usize) -> Result<Vec<Txid>> {
+        let deadline = Instant::now() + DEFAULT_MEMPOOL_TIMEOUT;
+
+        loop {
+            let mempool = self.bitcoin_mempool().await?;
+            if mempool.len() == size {
+                return Ok(mempool);
+            }
+
+            ensure!(
+                Instant::now() < deadline,
+                "Timed out waiting for mempool size {} (currently {})",
+                size,
+                mempool.len()
+            );
+            tokio::time::sleep(MEMPOOL_POLL_INTERVAL).await;
+        }
+    }
+}
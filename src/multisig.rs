@@ -0,0 +1,211 @@
+//! Multisig flows: M-of-N token authorities, and transactions signed piecemeal by several
+//! parties before being assembled and sent.
+//!
+//! There's no system-level multisig account to create: the system program has no M-of-N
+//! authority, so a system account needing several signatures is a transaction with several
+//! required signers, which is what `PartialTransaction` collects.
+//!
+//! ```ignore
+//! let (_, multisig) = ctx.create_token_multisig(payer_kp, &[alice, bob, carol], 2).await?;
+//!
+//! let mut tx = ctx.partial_transaction(&[instruction], alice).await?;
+//! tx.sign(&alice_kp)?;
+//! tx.sign(&bob_kp)?;
+//! let txid = ctx.send_transaction(tx.into_transaction()?).await?;
+//! ```
+
+use anyhow::{anyhow, ensure, Result};
+use arch_program::{
+    instruction::Instruction, program_pack::Pack, pubkey::Pubkey, rent::minimum_rent,
+    sanitized::ArchMessage, system_instruction,
+};
+use arch_sdk::{sign_message_bip322, RuntimeTransaction, Signature};
+use bitcoin::{key::Keypair, Network};
+
+use crate::{tokens::token_instruction, TestContext};
+
+/// A transaction whose required signatures are collected one keypair at a time, e.g. when each
+/// signer of a multisig authority signs separately. `into_transaction` assembles it once every
+/// required signer has signed.
+#[derive(Debug, Clone)]
+pub struct PartialTransaction {
+    pub message: ArchMessage,
+    /// One slot per required signer, in the message's signer order
+    pub signatures: Vec<Option<Signature>>,
+    network: Network,
+}
+
+impl PartialTransaction {
+    pub fn new(message: ArchMessage, network: Network) -> Self {
+        let signatures = vec![None; message.header.num_required_signatures as usize];
+
+        Self {
+            message,
+            signatures,
+            network,
+        }
+    }
+
+    /// The pubkeys that must sign, in the message's signer order
+    pub fn required_signers(&self) -> &[Pubkey] {
+        &self.message.account_keys[..self.signatures.len()]
+    }
+
+    /// The required signers that haven't signed yet
+    pub fn missing_signers(&self) -> Vec<Pubkey> {
+        self.required_signers()
+            .iter()
+            .zip(&self.signatures)
+            .filter(|(_, signature)| signature.is_none())
+            .map(|(pubkey, _)| *pubkey)
+            .collect()
+    }
+
+    /// Add `keypair`'s signature; fails if it isn't a required signer. Signing twice replaces
+    /// the earlier signature.
+    pub fn sign(&mut self, keypair: &Keypair) -> Result<()> {
+        let pubkey = Pubkey::from_slice(&keypair.x_only_public_key().0.serialize());
+        let index = self
+            .required_signers()
+            .iter()
+            .position(|signer| *signer == pubkey)
+            .ok_or_else(|| anyhow!("{} isn't a required signer of this transaction", pubkey))?;
+
+        let signature = sign_message_bip322(keypair, &self.message.hash(), self.network);
+        self.signatures[index] = Some(Signature(signature.to_vec()));
+
+        Ok(())
+    }
+
+    /// `sign` with each of `keypairs`
+    pub fn sign_all(&mut self, keypairs: &[Keypair]) -> Result<()> {
+        keypairs.iter().try_for_each(|keypair| self.sign(keypair))
+    }
+
+    /// The signed transaction; fails, naming them, if any required signer hasn't signed
+    pub fn into_transaction(self) -> Result<RuntimeTransaction> {
+        let missing = self.missing_signers();
+        ensure!(
+            missing.is_empty(),
+            "Transaction is missing {} of {} signatures, from {}",
+            missing.len(),
+            self.signatures.len(),
+            missing
+                .iter()
+                .map(Pubkey::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        Ok(RuntimeTransaction {
+            version: 0,
+            signatures: self.signatures.into_iter().flatten().collect(),
+            message: self.message,
+        })
+    }
+}
+
+impl TestContext {
+    /// An unsigned transaction of `instructions` paid for by `payer`, with a fresh blockhash,
+    /// to be signed with `PartialTransaction::sign`
+    pub async fn partial_transaction(
+        &self,
+        instructions: &[Instruction],
+        payer: Pubkey,
+    ) -> Result<PartialTransaction> {
+        let message = self.build_message(instructions, Some(payer)).await?;
+        Ok(PartialTransaction::new(message, self.network))
+    }
+
+    /// Create and initialize an `m`-of-`signers` token multisig authority, paid for by
+    /// `payer_kp`. Returns the multisig keypair and pubkey; use the pubkey as a mint or token
+    /// account authority, and pass the signers to the token instruction.
+    pub async fn create_token_multisig(
+        &self,
+        payer_kp: Keypair,
+        signers: &[Pubkey],
+        m: u8,
+    ) -> Result<(Keypair, Pubkey)> {
+        ensure!(
+            m >= 1 && usize::from(m) <= signers.len(),
+            "A {}-of-{} multisig can't be satisfied",
+            m,
+            signers.len()
+        );
+
+        let (multisig_kp, multisig_pubkey, _) = self.generate_new_keypair();
+        let payer_pubkey = Pubkey::from_slice(&payer_kp.x_only_public_key().0.serialize());
+        let signer_refs = signers.iter().collect::<Vec<_>>();
+
+        let instructions = [
+            system_instruction::create_account(
+                &payer_pubkey,
+                &multisig_pubkey,
+                minimum_rent(apl_token::state::Multisig::LEN),
+                apl_token::state::Multisig::LEN as u64,
+                &apl_token::id(),
+            ),
+            token_instruction(
+                "initialize_multisig",
+                apl_token::instruction::initialize_multisig(
+                    &apl_token::id(),
+                    &multisig_pubkey,
+                    &signer_refs,
+                    m,
+                ),
+            )?,
+        ];
+        self.send_token_instructions(
+            &instructions,
+            vec![payer_kp, multisig_kp],
+            "create token multisig",
+        )
+        .await?;
+
+        tracing::debug!(
+            "Created {}-of-{} token multisig {}",
+            m,
+            signers.len(),
+            multisig_pubkey
+        );
+        Ok((multisig_kp, multisig_pubkey))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arch_program::{hash::Hash, instruction::AccountMeta};
+    use arch_sdk::generate_new_keypair;
+
+    use super::*;
+
+    #[test]
+    fn test_partial_transaction() {
+        let (alice_kp, alice, _) = generate_new_keypair(Network::Regtest);
+        let (bob_kp, bob, _) = generate_new_keypair(Network::Regtest);
+        let (carol_kp, _, _) = generate_new_keypair(Network::Regtest);
+
+        let instruction = Instruction {
+            program_id: Pubkey::system_program(),
+            accounts: vec![AccountMeta::new(alice, true), AccountMeta::new(bob, true)],
+            data: vec![],
+        };
+        let blockhash: Hash = hex::encode([1u8; 32]).parse().unwrap();
+        let message = ArchMessage::new(&[instruction], Some(alice), blockhash);
+
+        let mut tx = PartialTransaction::new(message, Network::Regtest);
+        assert_eq!(tx.required_signers(), &[alice, bob]);
+        assert_eq!(tx.missing_signers(), vec![alice, bob]);
+
+        tx.sign(&alice_kp).unwrap();
+        assert_eq!(tx.missing_signers(), vec![bob]);
+        assert!(tx.sign(&carol_kp).is_err());
+
+        let error = tx.clone().into_transaction().unwrap_err().to_string();
+        assert!(error.contains(&bob.to_string()), "{}", error);
+
+        tx.sign(&bob_kp).unwrap();
+        assert!(tx.missing_signers().is_empty());
+        assert_eq!(tx.into_transaction().unwrap().signatures.len(), 2);
+    }
+}
@@ -0,0 +1,267 @@
+//! Network fault injection on the links between services, through the toxiproxy container
+//! started when `TestRunnerConfig::network_faults` is set.
+//!
+//! ```ignore
+//! let faults = ctx.network_faults()?;
+//! faults
+//!     .add_latency(ProxiedLink::ValidatorToTitanHttp, Duration::from_millis(500), Duration::ZERO)
+//!     .await?;
+//! faults.partition(ProxiedLink::TitanToBitcoinRpc).await?;
+//! // ...
+//! faults.reset().await?;
+//! ```
+//!
+//! Common scenarios have one-call helpers on `TestContext`:
+//!
+//! ```ignore
+//! let healed = ctx.partition_validator_from_titan(Duration::from_secs(10)).await?;
+//! // ... the validator can't reach Titan for 10s
+//! healed.await??;
+//! ```
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use tokio::task::JoinHandle;
+
+use crate::{
+    containers::{
+        toxiproxy_container::{BITCOIN_RPC_PROXY, TITAN_HTTP_PROXY, TITAN_TCP_PROXY},
+        ToxiproxyContainerConfig,
+    },
+    TestContext,
+};
+
+/// Makes toxic names unique within the process
+static NEXT_TOXIC_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A connection between two services that runs through toxiproxy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProxiedLink {
+    /// Titan's bitcoind RPC connection
+    TitanToBitcoinRpc,
+    /// The validator's Titan HTTP API connection
+    ValidatorToTitanHttp,
+    /// The validator's Titan TCP subscription
+    ValidatorToTitanTcp,
+}
+
+impl ProxiedLink {
+    pub const ALL: [ProxiedLink; 3] = [
+        ProxiedLink::TitanToBitcoinRpc,
+        ProxiedLink::ValidatorToTitanHttp,
+        ProxiedLink::ValidatorToTitanTcp,
+    ];
+
+    fn proxy_name(&self) -> &'static str {
+        match self {
+            ProxiedLink::TitanToBitcoinRpc => BITCOIN_RPC_PROXY,
+            ProxiedLink::ValidatorToTitanHttp => TITAN_HTTP_PROXY,
+            ProxiedLink::ValidatorToTitanTcp => TITAN_TCP_PROXY,
+        }
+    }
+}
+
+impl fmt::Display for ProxiedLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ProxiedLink::TitanToBitcoinRpc => "titan -> bitcoind rpc",
+            ProxiedLink::ValidatorToTitanHttp => "validator -> titan http",
+            ProxiedLink::ValidatorToTitanTcp => "validator -> titan tcp",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Client for the toxiproxy API. Faults apply to new and open connections until removed;
+/// toxics are applied to the downstream (response) direction.
+#[derive(Clone)]
+pub struct NetworkFaults {
+    config: ToxiproxyContainerConfig,
+    http_client: reqwest::Client,
+}
+
+impl NetworkFaults {
+    pub fn new(config: ToxiproxyContainerConfig) -> Self {
+        Self {
+            config,
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn api_url(&self) -> String {
+        self.config.local_network_api_url()
+    }
+
+    /// Delay every response on `link` by `latency` (+/- `jitter`). Returns the toxic's name,
+    /// for `remove_fault`.
+    pub async fn add_latency(
+        &self,
+        link: ProxiedLink,
+        latency: Duration,
+        jitter: Duration,
+    ) -> Result<String> {
+        self.add_toxic(
+            link,
+            "latency",
+            serde_json::json!({
+                "latency": latency.as_millis() as u64,
+                "jitter": jitter.as_millis() as u64,
+            }),
+        )
+        .await
+    }
+
+    /// Cap `link`'s throughput at `kilobytes_per_second`. Returns the toxic's name.
+    pub async fn limit_bandwidth(
+        &self,
+        link: ProxiedLink,
+        kilobytes_per_second: u64,
+    ) -> Result<String> {
+        self.add_toxic(
+            link,
+            "bandwidth",
+            serde_json::json!({ "rate": kilobytes_per_second }),
+        )
+        .await
+    }
+
+    /// Stop all data on `link` and close connections after `timeout` (`Duration::ZERO` keeps
+    /// them hanging). Unlike `partition`, connections are accepted. Returns the toxic's name.
+    pub async fn add_timeout(&self, link: ProxiedLink, timeout: Duration) -> Result<String> {
+        self.add_toxic(
+            link,
+            "timeout",
+            serde_json::json!({ "timeout": timeout.as_millis() as u64 }),
+        )
+        .await
+    }
+
+    /// Remove a fault added by `add_latency`, `limit_bandwidth` or `add_timeout`
+    pub async fn remove_fault(&self, link: ProxiedLink, name: &str) -> Result<()> {
+        self.http_client
+            .delete(format!(
+                "{}/proxies/{}/toxics/{}",
+                self.api_url(),
+                link.proxy_name(),
+                name
+            ))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to remove fault {} from {}", name, link))?;
+
+        tracing::info!("Removed fault {} from {}", name, link);
+        Ok(())
+    }
+
+    /// Cut `link` completely: open connections are closed and new ones refused
+    pub async fn partition(&self, link: ProxiedLink) -> Result<()> {
+        self.set_enabled(link, false).await?;
+        tracing::info!("Partitioned {}", link);
+        Ok(())
+    }
+
+    /// Undo `partition`
+    pub async fn heal(&self, link: ProxiedLink) -> Result<()> {
+        self.set_enabled(link, true).await?;
+        tracing::info!("Healed {}", link);
+        Ok(())
+    }
+
+    /// Heal every link and remove every fault
+    pub async fn reset(&self) -> Result<()> {
+        self.http_client
+            .post(format!("{}/reset", self.api_url()))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .context("Failed to reset network faults")?;
+
+        tracing::info!("Reset network faults");
+        Ok(())
+    }
+
+    async fn add_toxic(
+        &self,
+        link: ProxiedLink,
+        toxic_type: &str,
+        attributes: serde_json::Value,
+    ) -> Result<String> {
+        let name = format!(
+            "{}_{}",
+            toxic_type,
+            NEXT_TOXIC_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        let toxic = serde_json::json!({
+            "name": name,
+            "type": toxic_type,
+            "stream": "downstream",
+            "toxicity": 1.0,
+            "attributes": attributes,
+        });
+
+        self.http_client
+            .post(format!(
+                "{}/proxies/{}/toxics",
+                self.api_url(),
+                link.proxy_name()
+            ))
+            .json(&toxic)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to add {} fault to {}", toxic_type, link))?;
+
+        tracing::info!("Added fault {} ({}) to {}", name, toxic, link);
+        Ok(name)
+    }
+
+    async fn set_enabled(&self, link: ProxiedLink, enabled: bool) -> Result<()> {
+        self.http_client
+            .post(format!("{}/proxies/{}", self.api_url(), link.proxy_name()))
+            .json(&serde_json::json!({ "enabled": enabled }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to set {} enabled={}", link, enabled))?;
+        Ok(())
+    }
+}
+
+impl TestContext {
+    /// Cut the validator off from Titan (both the HTTP API and the TCP subscription), healing
+    /// the partition in the background after `duration`. Await the returned handle to know
+    /// when it's healed.
+    pub async fn partition_validator_from_titan(
+        &self,
+        duration: Duration,
+    ) -> Result<JoinHandle<Result<()>>> {
+        let faults = self.network_faults()?.clone();
+
+        faults.partition(ProxiedLink::ValidatorToTitanHttp).await?;
+        faults.partition(ProxiedLink::ValidatorToTitanTcp).await?;
+
+        Ok(tokio::spawn(async move {
+            tokio::time::sleep(duration).await;
+            faults.heal(ProxiedLink::ValidatorToTitanHttp).await?;
+            faults.heal(ProxiedLink::ValidatorToTitanTcp).await
+        }))
+    }
+
+    /// Delay every bitcoind RPC response Titan receives by `millis`. Returns the fault's name;
+    /// undo with `network_faults()?.remove_fault(ProxiedLink::TitanToBitcoinRpc, &name)`.
+    pub async fn delay_bitcoin_rpc(&self, millis: u64) -> Result<String> {
+        self.network_faults()?
+            .add_latency(
+                ProxiedLink::TitanToBitcoinRpc,
+                Duration::from_millis(millis),
+                Duration::ZERO,
+            )
+            .await
+    }
+}
@@ -0,0 +1,119 @@
+use bitcoin::Network;
+
+/// Which Arch network the stack emulates.
+///
+/// Every component derives its network settings from this one value, so bitcoind, Titan,
+/// the validator and `TestContext` can't disagree about which chain they're on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArchNetworkMode {
+    #[default]
+    Localnet,
+    Devnet,
+    /// Arch testnet, backed by Bitcoin testnet4
+    Testnet,
+    /// Bitcoin signet (the default signet, or a custom one via `SignetConfig`), for more
+    /// realistic block timing and fee behavior than regtest
+    Signet,
+    Mainnet,
+}
+
+/// Custom signet parameters, e.g. for mutinynet-style images.
+/// Only used when running in `ArchNetworkMode::Signet`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SignetConfig {
+    /// Hex encoded block challenge script (`-signetchallenge`); `None` for the default signet
+    pub challenge: Option<String>,
+    /// Peers to bootstrap from (`-signetseednode`)
+    pub seed_nodes: Vec<String>,
+}
+
+impl SignetConfig {
+    pub fn bitcoind_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if let Some(challenge) = &self.challenge {
+            args.push(format!("-signetchallenge={}", challenge));
+        }
+
+        for seed_node in &self.seed_nodes {
+            args.push(format!("-signetseednode={}", seed_node));
+        }
+
+        args
+    }
+}
+
+impl ArchNetworkMode {
+    /// The `bitcoin::Network` used for addresses and signing
+    pub fn bitcoin_network(&self) -> Network {
+        match self {
+            ArchNetworkMode::Localnet | ArchNetworkMode::Devnet => Network::Regtest,
+            ArchNetworkMode::Testnet => Network::Testnet4,
+            ArchNetworkMode::Signet => Network::Signet,
+            ArchNetworkMode::Mainnet => Network::Bitcoin,
+        }
+    }
+
+    /// bitcoind's network flag (mainnet has none)
+    pub fn bitcoin_network_flag(&self) -> &'static str {
+        match self.bitcoin_network() {
+            Network::Bitcoin => "",
+            Network::Testnet => "-testnet=1",
+            Network::Testnet4 => "-testnet4=1",
+            Network::Signet => "-signet=1",
+            _ => "-regtest=1",
+        }
+    }
+
+    /// Titan's `CHAIN` value
+    pub fn titan_chain(&self) -> &'static str {
+        match self.bitcoin_network() {
+            Network::Bitcoin => "mainnet",
+            Network::Testnet => "testnet",
+            Network::Testnet4 => "testnet4",
+            Network::Signet => "signet",
+            _ => "regtest",
+        }
+    }
+
+    /// The local validator's `--network-mode` value
+    pub fn validator_network_mode(&self) -> &'static str {
+        match self {
+            ArchNetworkMode::Localnet => "localnet",
+            ArchNetworkMode::Devnet => "devnet",
+            ArchNetworkMode::Testnet => "testnet",
+            // the validator has no signet mode; devnet is the closest match
+            ArchNetworkMode::Signet => "devnet",
+            ArchNetworkMode::Mainnet => "mainnet",
+        }
+    }
+
+    /// Whether blocks can be generated on demand (and the test wallet premined)
+    pub fn can_generate_blocks(&self) -> bool {
+        self.bitcoin_network() == Network::Regtest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_mode_maps_consistently() {
+        let localnet = ArchNetworkMode::default();
+        assert_eq!(localnet.bitcoin_network(), Network::Regtest);
+        assert_eq!(localnet.bitcoin_network_flag(), "-regtest=1");
+        assert_eq!(localnet.titan_chain(), "regtest");
+        assert_eq!(localnet.validator_network_mode(), "localnet");
+
+        let mainnet = ArchNetworkMode::Mainnet;
+        assert_eq!(mainnet.bitcoin_network(), Network::Bitcoin);
+        assert_eq!(mainnet.bitcoin_network_flag(), "");
+        assert_eq!(mainnet.titan_chain(), "mainnet");
+        assert!(!mainnet.can_generate_blocks());
+
+        let signet = ArchNetworkMode::Signet;
+        assert_eq!(signet.bitcoin_network_flag(), "-signet=1");
+        assert_eq!(signet.titan_chain(), "signet");
+    }
+}
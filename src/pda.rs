@@ -0,0 +1,88 @@
+//! Program derived addresses: derivation, and creating PDA accounts through the program that
+//! owns them.
+//!
+//! A PDA can't sign, so only its program can create it (with `invoke_signed` on the system
+//! program); `create_pda_account` sends the program's instruction and checks the result.
+//!
+//! ```ignore
+//! let (vault, bump) = ctx
+//!     .create_pda_account(payer_kp, &[b"vault", user.as_ref()], &program_id, |vault, bump| {
+//!         Instruction::new_with_borsh(program_id, &VaultInstruction::Init { bump }, vec![
+//!             AccountMeta::new(payer, true),
+//!             AccountMeta::new(vault, false),
+//!             AccountMeta::new_readonly(Pubkey::system_program(), false),
+//!         ])
+//!     })
+//!     .await?;
+//! ```
+
+use anyhow::{anyhow, ensure, Context, Result};
+use arch_program::{instruction::Instruction, pubkey::Pubkey};
+use bitcoin::key::Keypair;
+
+use crate::{ProcessedTransactionExt, TestContext};
+
+impl TestContext {
+    /// The PDA of `seeds` under `program_id`, and its bump seed
+    pub fn find_program_address(&self, seeds: &[&[u8]], program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(seeds, program_id)
+    }
+
+    /// Derive the PDA of `seeds` under `program_id` and have the program create it: `build`
+    /// gets the PDA and its bump and returns the program's instruction that creates it, which
+    /// is sent signed (and paid for) by `payer_kp`. Checks the PDA didn't exist before and is
+    /// owned by `program_id` after. Returns the PDA and its bump.
+    pub async fn create_pda_account<F>(
+        &self,
+        payer_kp: Keypair,
+        seeds: &[&[u8]],
+        program_id: &Pubkey,
+        build: F,
+    ) -> Result<(Pubkey, u8)>
+    where
+        F: FnOnce(Pubkey, u8) -> Instruction,
+    {
+        let (pda, bump) = self.find_program_address(seeds, program_id);
+
+        let existing = self
+            .try_read_account_info(pda)
+            .await
+            .with_context(|| format!("Failed to check whether PDA {} exists", pda))?;
+        ensure!(
+            existing.is_none(),
+            "PDA {} of program {} already exists",
+            pda,
+            program_id
+        );
+
+        let instruction = build(pda, bump);
+        ensure!(
+            instruction.accounts.iter().any(|meta| meta.pubkey == pda),
+            "The instruction creating PDA {} doesn't pass it as an account",
+            pda
+        );
+
+        let processed_tx = self
+            .send_instructions(&[instruction], vec![payer_kp])
+            .await
+            .with_context(|| format!("Failed to create PDA {}", pda))?;
+        if let Some(failure) = processed_tx.failure() {
+            return Err(anyhow!("Failed to create PDA {}: {}", pda, failure));
+        }
+
+        let account = self
+            .read_account_info(pda)
+            .await
+            .with_context(|| format!("PDA {} wasn't created", pda))?;
+        ensure!(
+            account.owner == *program_id,
+            "PDA {} is owned by {}, expected its program {}",
+            pda,
+            account.owner,
+            program_id
+        );
+
+        tracing::debug!("Created PDA {} (bump {}) of {}", pda, bump, program_id);
+        Ok((pda, bump))
+    }
+}
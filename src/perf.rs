@@ -0,0 +1,274 @@
+//! Performance regression tracking across runs.
+//!
+//! Results are kept in a JSON file per benchmark: the baseline, plus the latest result of each
+//! git commit. `PerfBaseline::check` records the current result and fails if it's worse than the
+//! baseline by more than the tolerance. The first result becomes the baseline; set
+//! `UPDATE_PERF_BASELINE_ENV` to replace it on purpose.
+//!
+//! ```ignore
+//! let report = LoadTest::new(generator).run(&ctx).await?;
+//! // sends the transaction: its effects are committed
+//! let compute_units = ctx.measure_compute_units(&instructions, signers).await?;
+//!
+//! PerfBaseline::new("perf/transfer.json")
+//!     .tolerance(0.15)
+//!     .check(&PerfMetrics::from(&report).merge(PerfMetrics::from(&compute_units)))?;
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{ComputeUnits, LoadReport};
+
+/// Allowed relative regression before `PerfBaseline::check` fails (10%)
+pub const DEFAULT_PERF_TOLERANCE: f64 = 0.1;
+
+/// When set (to anything but `0`), `PerfBaseline::check` replaces the baseline instead of
+/// comparing against it
+pub const UPDATE_PERF_BASELINE_ENV: &str = "UPDATE_PERF_BASELINE";
+
+/// Which direction of change is a regression
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Better {
+    /// e.g. throughput
+    Higher,
+    /// e.g. latency, compute units
+    Lower,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PerfMetric {
+    pub value: f64,
+    pub better: Better,
+}
+
+/// Named measurements of one run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PerfMetrics {
+    pub metrics: BTreeMap<String, PerfMetric>,
+}
+
+impl PerfMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn higher_is_better(mut self, name: impl Into<String>, value: f64) -> Self {
+        self.metrics.insert(
+            name.into(),
+            PerfMetric {
+                value,
+                better: Better::Higher,
+            },
+        );
+        self
+    }
+
+    pub fn lower_is_better(mut self, name: impl Into<String>, value: f64) -> Self {
+        self.metrics.insert(
+            name.into(),
+            PerfMetric {
+                value,
+                better: Better::Lower,
+            },
+        );
+        self
+    }
+
+    /// Combine with `other`'s metrics (`other` wins on name clashes)
+    pub fn merge(mut self, other: PerfMetrics) -> Self {
+        self.metrics.extend(other.metrics);
+        self
+    }
+
+    /// Descriptions of every metric that's worse than `baseline` by more than `tolerance`
+    /// (relative). Metrics missing from either side aren't compared.
+    pub fn regressions_from(&self, baseline: &PerfMetrics, tolerance: f64) -> Vec<String> {
+        self.metrics
+            .iter()
+            .filter_map(|(name, current)| {
+                let baseline = baseline.metrics.get(name)?;
+                let regressed = match current.better {
+                    Better::Higher => current.value < baseline.value * (1.0 - tolerance),
+                    Better::Lower => current.value > baseline.value * (1.0 + tolerance),
+                };
+
+                regressed.then(|| {
+                    format!(
+                        "{}: {} (baseline {}, {:+.1}%)",
+                        name,
+                        current.value,
+                        baseline.value,
+                        (current.value / baseline.value - 1.0) * 100.0
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+impl From<&LoadReport> for PerfMetrics {
+    fn from(report: &LoadReport) -> Self {
+        PerfMetrics::new()
+            .higher_is_better("tps", report.tps)
+            .lower_is_better("latency_p50_ms", report.latency_p50.as_secs_f64() * 1000.0)
+            .lower_is_better("latency_p99_ms", report.latency_p99.as_secs_f64() * 1000.0)
+            .lower_is_better("failed", report.failed as f64)
+    }
+}
+
+impl From<&ComputeUnits> for PerfMetrics {
+    fn from(compute_units: &ComputeUnits) -> Self {
+        PerfMetrics::new().lower_is_better("compute_units", compute_units.total() as f64)
+    }
+}
+
+/// One run's metrics, and where they came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PerfRecord {
+    pub commit: String,
+    /// Unix seconds
+    pub recorded_at: u64,
+    pub metrics: PerfMetrics,
+}
+
+/// Contents of a `PerfBaseline` file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PerfHistory {
+    pub baseline: Option<PerfRecord>,
+    /// Latest record per git commit
+    pub runs: BTreeMap<String, PerfRecord>,
+}
+
+/// A benchmark's results file, see the module docs
+#[derive(Debug, Clone)]
+pub struct PerfBaseline {
+    path: PathBuf,
+    tolerance: f64,
+}
+
+impl PerfBaseline {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            tolerance: DEFAULT_PERF_TOLERANCE,
+        }
+    }
+
+    /// Allowed relative regression, e.g. `0.05` for 5%
+    pub fn tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Record `metrics` under the current git commit and compare them to the baseline
+    pub fn check(&self, metrics: &PerfMetrics) -> Result<()> {
+        let mut history = self.load()?;
+        let record = PerfRecord {
+            commit: current_commit(),
+            recorded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since_epoch| since_epoch.as_secs())
+                .unwrap_or_default(),
+            metrics: metrics.clone(),
+        };
+
+        history.runs.insert(record.commit.clone(), record.clone());
+
+        let update_baseline =
+            std::env::var(UPDATE_PERF_BASELINE_ENV).is_ok_and(|value| value != "0");
+        let regressions = match &history.baseline {
+            Some(baseline) if !update_baseline => {
+                metrics.regressions_from(&baseline.metrics, self.tolerance)
+            }
+            _ => {
+                tracing::info!(
+                    "Recording perf baseline {} at {}",
+                    self.path.display(),
+                    record.commit
+                );
+                history.baseline = Some(record);
+                Vec::new()
+            }
+        };
+
+        self.save(&history)?;
+
+        ensure!(
+            regressions.is_empty(),
+            "Performance regressed beyond {:.0}% of the baseline ({}, commit {}):\n  {}",
+            self.tolerance * 100.0,
+            self.path.display(),
+            history
+                .baseline
+                .as_ref()
+                .map_or("unknown", |baseline| baseline.commit.as_str()),
+            regressions.join("\n  ")
+        );
+
+        Ok(())
+    }
+
+    pub fn load(&self) -> Result<PerfHistory> {
+        if !self.path.exists() {
+            return Ok(PerfHistory::default());
+        }
+
+        let json = std::fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read {}", self.path.display()))?;
+        serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse perf history {}", self.path.display()))
+    }
+
+    fn save(&self, history: &PerfHistory) -> Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| dir != &Path::new("")) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+
+        std::fs::write(&self.path, serde_json::to_string_pretty(history)?)
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+/// `git rev-parse HEAD`, or `"unknown"` outside a git checkout
+fn current_commit() -> String {
+    Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|| String::from("unknown"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regressions_respect_direction_and_tolerance() {
+        let baseline = PerfMetrics::new()
+            .higher_is_better("tps", 100.0)
+            .lower_is_better("compute_units", 1000.0);
+
+        let within = PerfMetrics::new()
+            .higher_is_better("tps", 95.0)
+            .lower_is_better("compute_units", 1050.0);
+        assert!(within.regressions_from(&baseline, 0.1).is_empty());
+
+        let beyond = PerfMetrics::new()
+            .higher_is_better("tps", 80.0)
+            .lower_is_better("compute_units", 1200.0)
+            .lower_is_better("new_metric", 1.0);
+        assert_eq!(beyond.regressions_from(&baseline, 0.1).len(), 2);
+    }
+}
@@ -0,0 +1,495 @@
+//! Deploying programs (from bytes, or built from source).
+//!
+//! Deployment talks to the loader directly over the async RPC client: the program account is
+//! created (or retracted, if it's being redeployed), sized to the ELF, written in chunks,
+//! deployed, and finally checked to be executable and hold the ELF.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+};
+
+use anyhow::{anyhow, ensure, Context, Result};
+use arch_program::{
+    bpf_loader::BPF_LOADER_ID, instruction::Instruction, loader_instruction, pubkey::Pubkey,
+    rent::minimum_rent, system_instruction,
+};
+use bitcoin::{
+    hashes::{sha256, Hash},
+    key::Keypair,
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use tokio::{sync::OnceCell, task::spawn_blocking};
+
+use crate::{
+    fork::PROGRAM_ACCOUNT_HEADER_LEN, PreloadedProgram, ProcessedTransactionExt, TestContext,
+};
+
+/// ELF bytes written per loader transaction
+const DEPLOY_CHUNK_LEN: usize = 800;
+
+/// Chunk writes in flight at once; chunks land at distinct offsets, so order doesn't matter
+const DEPLOY_WRITE_CONCURRENCY: usize = 8;
+
+/// Largest ELF `read_program_elf` accepts
+pub const MAX_PROGRAM_ELF_LEN: usize = 10 * 1024 * 1024;
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+
+/// Where `build_program_crate` asks `cargo build-sbf` to put the ELF, relative to the crate
+pub const SBF_OUT_DIR: &str = "target/arch-testing-deploy";
+
+type BuildCache = Mutex<HashMap<PathBuf, Arc<OnceCell<PathBuf>>>>;
+
+type DeployCache = Mutex<HashMap<Pubkey, Arc<tokio::sync::Mutex<Option<sha256::Hash>>>>>;
+
+/// ELF path per crate directory; each crate is built at most once per test process
+fn build_cache() -> &'static BuildCache {
+    static CACHE: OnceLock<BuildCache> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+/// Build the program crate at `crate_dir` with `cargo build-sbf` and return the path of its ELF.
+///
+/// Builds are cached per crate for the life of the process (concurrent callers wait for the
+/// same build), and cargo's own incremental build makes later processes cheap too.
+pub async fn build_program_crate(crate_dir: impl AsRef<Path>) -> Result<PathBuf> {
+    let crate_dir = std::fs::canonicalize(crate_dir.as_ref())
+        .with_context(|| format!("Program crate {} not found", crate_dir.as_ref().display()))?;
+
+    let cell = build_cache()
+        .lock()
+        .expect("build cache poisoned")
+        .entry(crate_dir.clone())
+        .or_default()
+        .clone();
+
+    cell.get_or_try_init(|| async move {
+        spawn_blocking(move || build_sbf(&crate_dir))
+            .await
+            .context("Failed to spawn blocking task")?
+    })
+    .await
+    .cloned()
+}
+
+/// sha256 of the ELF last deployed to each program by any context of this process, for
+/// `deploy_program_cached`. Each program's entry is locked across its deployment.
+fn deploy_cache(program_pubkey: Pubkey) -> Arc<tokio::sync::Mutex<Option<sha256::Hash>>> {
+    static CACHE: OnceLock<DeployCache> = OnceLock::new();
+
+    CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .expect("deploy cache poisoned")
+        .entry(program_pubkey)
+        .or_default()
+        .clone()
+}
+
+fn build_sbf(crate_dir: &Path) -> Result<PathBuf> {
+    let out_dir = crate_dir.join(SBF_OUT_DIR);
+    tracing::info!("Building program {}", crate_dir.display());
+
+    let output = Command::new("cargo")
+        .arg("build-sbf")
+        .arg("--manifest-path")
+        .arg(crate_dir.join("Cargo.toml"))
+        .arg("--sbf-out-dir")
+        .arg(&out_dir)
+        .output()
+        .context("Failed to run cargo build-sbf (is the Solana/Arch toolchain installed?)")?;
+
+    ensure!(
+        output.status.success(),
+        "cargo build-sbf failed for {}:\n{}",
+        crate_dir.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let elfs = std::fs::read_dir(&out_dir)
+        .with_context(|| format!("Failed to read {}", out_dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "so"))
+        .collect::<Vec<_>>();
+
+    match elfs.as_slice() {
+        [elf] => Ok(elf.clone()),
+        [] => Err(anyhow!(
+            "cargo build-sbf produced no ELF in {}",
+            out_dir.display()
+        )),
+        _ => Err(anyhow!(
+            "Found several ELFs in {}, expected one: {:?}",
+            out_dir.display(),
+            elfs
+        )),
+    }
+}
+
+/// Read the program ELF at `path`, checking it looks deployable: it exists, isn't empty or
+/// larger than `MAX_PROGRAM_ELF_LEN`, and starts with the ELF magic
+pub fn read_program_elf(path: impl AsRef<Path>) -> Result<Vec<u8>> {
+    let path = path.as_ref();
+    let elf_bytes = match std::fs::read(path) {
+        Ok(elf_bytes) => elf_bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(anyhow!(
+                "Program {} not found (build it with `cargo build-sbf` or `build_program_crate`)",
+                path.display()
+            ));
+        }
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read program {}", path.display()))
+        }
+    };
+
+    validate_program_elf(&elf_bytes)
+        .with_context(|| format!("Invalid program {}", path.display()))?;
+    Ok(elf_bytes)
+}
+
+fn validate_program_elf(elf_bytes: &[u8]) -> Result<()> {
+    ensure!(!elf_bytes.is_empty(), "The file is empty");
+    ensure!(
+        elf_bytes.len() <= MAX_PROGRAM_ELF_LEN,
+        "{} bytes is more than the {} byte limit",
+        elf_bytes.len(),
+        MAX_PROGRAM_ELF_LEN
+    );
+    ensure!(
+        elf_bytes.starts_with(ELF_MAGIC),
+        "Not an ELF file (starts with {}, expected {})",
+        hex::encode(&elf_bytes[..elf_bytes.len().min(ELF_MAGIC.len())]),
+        hex::encode(ELF_MAGIC)
+    );
+    Ok(())
+}
+
+/// What `deploy_program_cached` did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeployOutcome {
+    Deployed,
+    /// The same ELF was already deployed to the program
+    AlreadyDeployed,
+}
+
+/// Before and after of `upgrade_program`, as hashes of the program account's data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramUpgrade {
+    pub program_pubkey: Pubkey,
+    pub data_hash_before: sha256::Hash,
+    pub data_hash_after: sha256::Hash,
+}
+
+impl ProgramUpgrade {
+    pub fn assert_data_changed(&self) -> Result<()> {
+        ensure!(
+            self.data_hash_before != self.data_hash_after,
+            "Program {} data didn't change across the upgrade ({})",
+            self.program_pubkey,
+            self.data_hash_after
+        );
+        Ok(())
+    }
+}
+
+impl TestContext {
+    /// Deploy `elf_bytes` to `program_kp`'s program, with `authority_kp` as its authority (and
+    /// payer). Redeploying an existing program requires the same authority.
+    pub async fn deploy_program(
+        &self,
+        program_kp: Keypair,
+        authority_kp: Keypair,
+        elf_bytes: &[u8],
+    ) -> Result<()> {
+        let program_pubkey = Pubkey::from_slice(&program_kp.x_only_public_key().0.serialize());
+        let authority_pubkey = Pubkey::from_slice(&authority_kp.x_only_public_key().0.serialize());
+        let chunk_count = elf_bytes.len().div_ceil(DEPLOY_CHUNK_LEN);
+
+        tracing::info!(
+            "Deploying program {} ({} bytes, {} chunks)",
+            program_pubkey,
+            elf_bytes.len(),
+            chunk_count
+        );
+
+        let existing = self
+            .try_read_account_info(program_pubkey)
+            .await
+            .with_context(|| format!("Failed to read program account {}", program_pubkey))?;
+        let data_len = match existing {
+            Some(account) => {
+                if account.is_executable {
+                    self.send_deploy_instruction(
+                        loader_instruction::retract(program_pubkey, authority_pubkey),
+                        vec![authority_kp],
+                        "retract",
+                    )
+                    .await?;
+                }
+                account.data.len()
+            }
+            None => {
+                self.send_deploy_instruction(
+                    system_instruction::create_account(
+                        &authority_pubkey,
+                        &program_pubkey,
+                        minimum_rent(PROGRAM_ACCOUNT_HEADER_LEN + elf_bytes.len()),
+                        0,
+                        &BPF_LOADER_ID,
+                    ),
+                    vec![authority_kp, program_kp],
+                    "create account",
+                )
+                .await?;
+                0
+            }
+        };
+
+        if data_len != PROGRAM_ACCOUNT_HEADER_LEN + elf_bytes.len() {
+            self.send_deploy_instruction(
+                loader_instruction::truncate(
+                    program_pubkey,
+                    authority_pubkey,
+                    elf_bytes.len() as u32,
+                ),
+                vec![authority_kp],
+                "truncate",
+            )
+            .await?;
+        }
+
+        let written = AtomicUsize::new(0);
+        let written = &written;
+        stream::iter(elf_bytes.chunks(DEPLOY_CHUNK_LEN).enumerate())
+            .map(|(i, chunk)| async move {
+                let instruction = loader_instruction::write(
+                    program_pubkey,
+                    authority_pubkey,
+                    (i * DEPLOY_CHUNK_LEN) as u32,
+                    chunk.to_vec(),
+                );
+                self.send_deploy_instruction(instruction, vec![authority_kp], "write")
+                    .await?;
+
+                let done = written.fetch_add(1, Ordering::Relaxed) + 1;
+                tracing::debug!(
+                    "Program {}: wrote chunk {}/{}",
+                    program_pubkey,
+                    done,
+                    chunk_count
+                );
+                Ok::<_, anyhow::Error>(())
+            })
+            .buffer_unordered(DEPLOY_WRITE_CONCURRENCY)
+            .try_collect::<()>()
+            .await?;
+
+        self.send_deploy_instruction(
+            loader_instruction::deploy(program_pubkey, authority_pubkey),
+            vec![authority_kp],
+            "deploy",
+        )
+        .await?;
+
+        // finalize: the program only counts as deployed once it's executable with this ELF
+        self.assert_program_elf(program_pubkey, elf_bytes)
+            .await
+            .context("Program deployment failed")?;
+
+        tracing::info!("Deployed program {}", program_pubkey);
+        Ok(())
+    }
+
+    async fn send_deploy_instruction(
+        &self,
+        instruction: Instruction,
+        signers: Vec<Keypair>,
+        step: &str,
+    ) -> Result<()> {
+        let processed_tx = self
+            .send_instructions(&[instruction], signers)
+            .await
+            .with_context(|| format!("Program deployment failed to {}", step))?;
+
+        match processed_tx.failure() {
+            Some(failure) => Err(anyhow!(
+                "Program deployment failed to {}: {}",
+                step,
+                failure
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Deploy `elf_bytes` unless a context of this test process already deployed the same ELF
+    /// to `program_kp`'s program and the program on-chain still holds it. `force` always
+    /// deploys.
+    ///
+    /// Useful when many tests share an environment and deploy the same program: only the
+    /// first one pays for the deployment.
+    pub async fn deploy_program_cached(
+        &self,
+        program_kp: Keypair,
+        authority_kp: Keypair,
+        elf_bytes: &[u8],
+        force: bool,
+    ) -> Result<DeployOutcome> {
+        let program_pubkey = Pubkey::from_slice(&program_kp.x_only_public_key().0.serialize());
+        let elf_hash = sha256::Hash::hash(elf_bytes);
+
+        // held across the deployment, so concurrent callers don't deploy the same program twice
+        let cache = deploy_cache(program_pubkey);
+        let mut deployed_elf = cache.lock().await;
+
+        // the cache outlives environments, so a hit still has to match what's on-chain
+        if !force
+            && *deployed_elf == Some(elf_hash)
+            && self
+                .assert_program_elf(program_pubkey, elf_bytes)
+                .await
+                .is_ok()
+        {
+            tracing::debug!("Program {} already has ELF {}", program_pubkey, elf_hash);
+            return Ok(DeployOutcome::AlreadyDeployed);
+        }
+
+        self.deploy_program(program_kp, authority_kp, elf_bytes)
+            .await?;
+        *deployed_elf = Some(elf_hash);
+
+        Ok(DeployOutcome::Deployed)
+    }
+
+    /// Read the ELF at `elf_path` (see `read_program_elf`) and deploy it
+    pub async fn deploy_program_from_path(
+        &self,
+        program_kp: Keypair,
+        authority_kp: Keypair,
+        elf_path: impl AsRef<Path>,
+    ) -> Result<()> {
+        let elf_bytes = read_program_elf(elf_path)?;
+        self.deploy_program(program_kp, authority_kp, &elf_bytes)
+            .await
+    }
+
+    /// Build the program crate at `crate_dir` (see `build_program_crate`) and deploy it under a
+    /// new program keypair, with a faucet-funded authority. Returns the program keypair and pubkey.
+    pub async fn deploy_program_from_crate(
+        &self,
+        crate_dir: impl AsRef<Path>,
+    ) -> Result<(Keypair, Pubkey)> {
+        let elf_path = build_program_crate(crate_dir).await?;
+
+        let (program_kp, program_pubkey, _) = self.generate_new_keypair();
+        let (authority_kp, _, _) = self.generate_funded_keypair().await?;
+
+        self.deploy_program_from_path(program_kp, authority_kp, &elf_path)
+            .await?;
+
+        Ok((program_kp, program_pubkey))
+    }
+
+    /// Redeploy an existing program with `new_elf`, signed by `authority_kp` (which must be the
+    /// program's authority for the upgrade to succeed)
+    pub async fn upgrade_program(
+        &self,
+        program_kp: Keypair,
+        authority_kp: Keypair,
+        new_elf: &[u8],
+    ) -> Result<ProgramUpgrade> {
+        let program_pubkey = Pubkey::from_slice(&program_kp.x_only_public_key().0.serialize());
+        let before = self
+            .read_account_info(program_pubkey)
+            .await
+            .with_context(|| format!("Program {} isn't deployed", program_pubkey))?;
+        ensure!(
+            before.is_executable,
+            "Account {} isn't an executable program",
+            program_pubkey
+        );
+
+        self.deploy_program(program_kp, authority_kp, new_elf)
+            .await
+            .with_context(|| format!("Failed to upgrade program {}", program_pubkey))?;
+
+        let after = self.read_account_info(program_pubkey).await?;
+        *deploy_cache(program_pubkey).lock().await = Some(sha256::Hash::hash(new_elf));
+
+        Ok(ProgramUpgrade {
+            program_pubkey,
+            data_hash_before: sha256::Hash::hash(&before.data),
+            data_hash_after: sha256::Hash::hash(&after.data),
+        })
+    }
+
+    /// Assert that `program_pubkey` is executable and holds `elf` (the loader keeps its own
+    /// state ahead of the ELF, so only the end of the account data is compared)
+    pub async fn assert_program_elf(&self, program_pubkey: Pubkey, elf: &[u8]) -> Result<()> {
+        let account = self
+            .read_account_info(program_pubkey)
+            .await
+            .with_context(|| format!("Program {} isn't deployed", program_pubkey))?;
+
+        ensure!(
+            account.is_executable,
+            "Account {} isn't an executable program",
+            program_pubkey
+        );
+        ensure!(
+            account.data.ends_with(elf),
+            "Program {} doesn't hold the expected ELF ({} bytes of account data, ELF is {} bytes, sha256 {})",
+            program_pubkey,
+            account.data.len(),
+            elf.len(),
+            sha256::Hash::hash(elf)
+        );
+
+        Ok(())
+    }
+
+    /// Deploy `programs` (see `TestRunnerConfig::preloaded_programs`), sharing one authority
+    pub(crate) async fn deploy_preloaded_programs(
+        &self,
+        programs: &[PreloadedProgram],
+    ) -> Result<()> {
+        if programs.is_empty() {
+            return Ok(());
+        }
+
+        let (authority_kp, _, _) = self.generate_funded_keypair().await?;
+
+        for program in programs {
+            let elf_bytes = read_program_elf(&program.elf_path)?;
+
+            self.deploy_program_cached(program.program_kp, authority_kp, &elf_bytes, false)
+                .await
+                .with_context(|| {
+                    format!("Failed to preload program {}", program.elf_path.display())
+                })?;
+
+            tracing::debug!("Preloaded program {}", program.elf_path.display());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_program_elf_rejects_non_elfs() {
+        assert!(validate_program_elf(b"\x7fELF\x02\x01\x01").is_ok());
+        assert!(validate_program_elf(b"").is_err());
+        assert!(validate_program_elf(b"#!/bin/sh").is_err());
+        assert!(validate_program_elf(&vec![0x7f; MAX_PROGRAM_ELF_LEN + 1]).is_err());
+    }
+}
@@ -0,0 +1,54 @@
+//! Rent-exempt minimums, so tests fund accounts by size rather than with hardcoded lamport
+//! amounts.
+//!
+//! The validator exposes no rent RPC, so minimums come from the SDK's compiled-in
+//! `arch_program::rent::minimum_rent`: they follow rent changes that ship with an SDK upgrade,
+//! but not a validator configured with different rent parameters.
+
+use arch_program::rent::minimum_rent;
+
+use crate::TestContext;
+
+/// How many lamports a new account starts with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountLamports {
+    Exact(u64),
+    /// The rent-exempt minimum for the account's size
+    RentExempt,
+}
+
+impl AccountLamports {
+    /// The lamports for an account of `space` bytes
+    pub fn resolve(self, space: usize) -> u64 {
+        match self {
+            AccountLamports::Exact(lamports) => lamports,
+            AccountLamports::RentExempt => minimum_rent(space),
+        }
+    }
+}
+
+impl From<u64> for AccountLamports {
+    fn from(lamports: u64) -> Self {
+        AccountLamports::Exact(lamports)
+    }
+}
+
+impl TestContext {
+    /// The rent-exempt minimum balance of an account holding `space` bytes of data, per the
+    /// SDK's `minimum_rent`
+    pub fn minimum_balance_for(&self, space: usize) -> u64 {
+        minimum_rent(space)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_lamports_resolve() {
+        assert_eq!(AccountLamports::from(1_000).resolve(64), 1_000);
+        assert_eq!(AccountLamports::RentExempt.resolve(64), minimum_rent(64));
+        assert!(AccountLamports::RentExempt.resolve(1024) > AccountLamports::RentExempt.resolve(0));
+    }
+}
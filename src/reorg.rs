@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{ensure, Context, Result};
+use bitcoin::BlockHash;
+use bitcoincore_rpc::RpcApi;
+
+use crate::TestContext;
+
+pub const REORG_CONVERGENCE_TIMEOUT: Duration = Duration::from_secs(60);
+pub const REORG_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The outcome of `TestContext::trigger_reorg`
+#[derive(Debug, Clone)]
+pub struct Reorg {
+    /// Blocks that were disconnected, oldest first
+    pub invalidated: Vec<BlockHash>,
+    /// The longer replacement chain, oldest first
+    pub replacement: Vec<BlockHash>,
+}
+
+impl TestContext {
+    /// Invalidate the last `depth` blocks on bitcoind, mine a replacement chain one block longer,
+    /// and wait for Titan and the validator to converge on it.
+    ///
+    /// Transactions from the invalidated blocks return to the mempool and will usually be
+    /// re-mined into the replacement chain.
+    pub async fn trigger_reorg(&self, depth: u64) -> Result<Reorg> {
+        let tip_height = self
+            .with_bitcoin_client(|client| client.get_block_count())
+            .await?;
+        // the genesis block can't be invalidated
+        ensure!(
+            depth > 0 && depth <= tip_height,
+            "Reorg depth must be between 1 and the tip height {}, got {}",
+            tip_height,
+            depth
+        );
+
+        let fork_height = tip_height - depth + 1;
+        let invalidated = self
+            .with_bitcoin_client(move |client| {
+                (fork_height..=tip_height)
+                    .map(|height| client.get_block_hash(height))
+                    .collect::<bitcoincore_rpc::Result<Vec<_>>>()
+            })
+            .await?;
+
+        let fork_point = invalidated[0];
+        self.with_bitcoin_client(move |client| client.invalidate_block(&fork_point))
+            .await
+            .with_context(|| format!("Failed to invalidate block {}", fork_point))?;
+
+        let replacement = self
+            .bitcoin_mine_blocks(invalidated.len() as u64 + 1)
+            .await?;
+
+        tracing::info!(
+            "Reorged {} blocks starting at {}, new tip {}",
+            invalidated.len(),
+            fork_point,
+            replacement[replacement.len() - 1]
+        );
+
+        self.wait_for_reorg_convergence().await?;
+
+        Ok(Reorg {
+            invalidated,
+            replacement,
+        })
+    }
+
+    /// Titan converged once its tip hash matches bitcoind's; the validator follows Titan, so we
+    /// treat it as converged once it has produced a block after that.
+    async fn wait_for_reorg_convergence(&self) -> Result<()> {
+        let deadline = Instant::now() + REORG_CONVERGENCE_TIMEOUT;
+        let best_block_hash = self
+            .with_bitcoin_client(|client| client.get_best_block_hash())
+            .await?
+            .to_string();
+
+        let mut titan_tip = String::new();
+        while titan_tip != best_block_hash {
+            ensure!(
+                Instant::now() < deadline,
+                "Titan didn't converge on {} after reorg (tip: {})",
+                best_block_hash,
+                titan_tip
+            );
+            tokio::time::sleep(REORG_POLL_INTERVAL).await;
+
+            if let Ok((_, hash)) = self.titan_tip().await {
+                titan_tip = hash;
+            }
+        }
+
+        let validator_height = self.arch_async_rpc_client.get_block_count().await?;
+        loop {
+            ensure!(
+                Instant::now() < deadline,
+                "Validator didn't produce a block after the reorg (height: {})",
+                validator_height
+            );
+            tokio::time::sleep(REORG_POLL_INTERVAL).await;
+
+            if self.arch_async_rpc_client.get_block_count().await? > validator_height {
+                return Ok(());
+            }
+        }
+    }
+}
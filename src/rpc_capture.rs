@@ -0,0 +1,593 @@
+//! Capturing every Arch RPC call a test makes (`TestRunnerConfig::capture_rpc`).
+//!
+//! The capture is a small HTTP proxy in front of the validator's RPC port; when it's enabled,
+//! every Arch RPC client of the `TestContext` (and `ValidatorHandle::call`) goes through it.
+//! Failed tests log the last `DEFAULT_RPC_CAPTURE_DUMP_LEN` calls. The same proxy answers from
+//! a cassette when a run is replayed (see `RpcCassetteMode`).
+//!
+//! ```ignore
+//! let capture = ctx.rpc_capture()?;
+//! capture.clear();
+//! ctx.send_instructions(&instructions, signers).await?;
+//! capture.assert_at_most(5)?;
+//! assert_eq!(capture.calls_to("send_transaction").len(), 1);
+//! ```
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    task::{JoinHandle, JoinSet},
+};
+use tracing::Instrument;
+
+use crate::{error::ArchTestingResult, RpcCassette, TestContext};
+
+/// How many of the most recent calls a failed test logs
+pub const DEFAULT_RPC_CAPTURE_DUMP_LEN: usize = 20;
+
+/// Longest result or error printed per call by `Display`
+const MAX_DISPLAYED_RESPONSE_LEN: usize = 200;
+
+/// JSON-RPC error code of the answer to a request a replayed cassette doesn't contain
+const UNEXPECTED_REQUEST_ERROR_CODE: i64 = -32099;
+
+/// One JSON-RPC request and what the validator answered
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcCall {
+    pub method: String,
+    pub params: serde_json::Value,
+    /// HTTP status of the response; 502 if the validator couldn't be reached
+    pub status: u16,
+    /// Raw response body: the JSON-RPC response, unless the call failed at the HTTP level
+    pub body: String,
+    pub latency: Duration,
+}
+
+impl RpcCall {
+    /// The `result` member of a successful call
+    pub fn result(&self) -> Option<serde_json::Value> {
+        if !self.is_ok() {
+            return None;
+        }
+        let mut response = serde_json::from_str::<serde_json::Value>(&self.body).ok()?;
+        Some(response["result"].take())
+    }
+
+    /// Why the call failed: the JSON-RPC error, or the HTTP status and body
+    pub fn error(&self) -> Option<String> {
+        if !(200..300).contains(&self.status) {
+            return Some(format!("http {}: {}", self.status, self.body));
+        }
+
+        match serde_json::from_str::<serde_json::Value>(&self.body) {
+            Ok(response) => response
+                .get("error")
+                .filter(|error| !error.is_null())
+                .map(|error| error.to_string()),
+            Err(e) => Some(format!("invalid response {:?}: {}", self.body, e)),
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.error().is_none()
+    }
+}
+
+impl fmt::Display for RpcCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let outcome = match self.error() {
+            Some(error) => format!("error {}", error),
+            None => self.result().unwrap_or_default().to_string(),
+        };
+        let outcome = match outcome.char_indices().nth(MAX_DISPLAYED_RESPONSE_LEN) {
+            Some((end, _)) => format!("{}...", &outcome[..end]),
+            None => outcome,
+        };
+
+        write!(
+            f,
+            "{}({}) -> {} [{:?}]",
+            self.method, self.params, outcome, self.latency
+        )
+    }
+}
+
+/// Cheap to clone: clones share the same proxy and recorded calls
+#[derive(Clone)]
+pub struct RpcCapture {
+    url: String,
+    calls: Arc<Mutex<Vec<RpcCall>>>,
+    replay: Option<Arc<Mutex<Replay>>>,
+    listener: Arc<JoinHandle<()>>,
+}
+
+impl RpcCapture {
+    /// Start proxying (and recording) RPC requests to `upstream_url`, over `http_client`
+    pub async fn start(upstream_url: &str, http_client: reqwest::Client) -> Result<Self> {
+        let capture = Self::serve(Backend::Upstream {
+            url: upstream_url.trim_end_matches('/').to_string(),
+            http_client,
+        })
+        .await?;
+
+        tracing::debug!(
+            "Capturing RPC calls to {} through {}",
+            upstream_url,
+            capture.url
+        );
+        Ok(capture)
+    }
+
+    /// Answer RPC requests from `cassette` instead of a validator. Each recorded call answers
+    /// one request with the same method and params, in recorded order; anything else gets a
+    /// JSON-RPC error and is listed by `unexpected_requests`.
+    pub async fn replay(cassette: RpcCassette) -> Result<Self> {
+        let replay = Arc::new(Mutex::new(Replay {
+            used: vec![false; cassette.interactions.len()],
+            interactions: cassette.interactions,
+            unexpected: Vec::new(),
+        }));
+
+        let capture = Self::serve(Backend::Replay(replay)).await?;
+        tracing::debug!("Replaying RPC calls through {}", capture.url);
+        Ok(capture)
+    }
+
+    async fn serve(backend: Backend) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .context("Failed to bind the RPC capture proxy")?;
+        let url = format!("http://{}", listener.local_addr()?);
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let replay = match &backend {
+            Backend::Replay(replay) => Some(replay.clone()),
+            Backend::Upstream { .. } => None,
+        };
+        let proxy = Proxy {
+            backend,
+            calls: calls.clone(),
+        };
+
+        let listener = tokio::spawn(
+            async move {
+                // connections are aborted with the accept loop when this is dropped
+                let mut connections = JoinSet::new();
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => {
+                            connections.spawn(
+                                serve_connection(stream, proxy.clone())
+                                    .instrument(tracing::Span::current()),
+                            );
+                        }
+                        Err(e) => tracing::warn!("RPC capture proxy failed to accept: {}", e),
+                    }
+                }
+            }
+            .instrument(tracing::Span::current()),
+        );
+
+        Ok(Self {
+            url,
+            calls,
+            replay,
+            listener: Arc::new(listener),
+        })
+    }
+
+    /// Whether requests are answered from a cassette rather than a validator
+    pub fn is_replay(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    /// Requests a replayed cassette had no answer for, as `method(params)`
+    pub fn unexpected_requests(&self) -> Vec<String> {
+        self.replay
+            .as_ref()
+            .map(|replay| {
+                replay
+                    .lock()
+                    .expect("rpc replay poisoned")
+                    .unexpected
+                    .clone()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The calls so far, as a cassette to `save` and replay later
+    pub fn cassette(&self) -> RpcCassette {
+        RpcCassette::new(self.calls())
+    }
+
+    /// Where clients should send their RPC requests
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Stop proxying; calls made through the capture fail afterwards
+    pub fn stop(&self) {
+        self.listener.abort();
+    }
+
+    /// Every call so far, in the order they completed
+    pub fn calls(&self) -> Vec<RpcCall> {
+        self.calls.lock().expect("rpc capture poisoned").clone()
+    }
+
+    pub fn calls_to(&self, method: &str) -> Vec<RpcCall> {
+        self.calls()
+            .into_iter()
+            .filter(|call| call.method == method)
+            .collect()
+    }
+
+    pub fn call_count(&self) -> usize {
+        self.calls.lock().expect("rpc capture poisoned").len()
+    }
+
+    /// Forget the calls so far, e.g. to count only the calls of one step
+    pub fn clear(&self) {
+        self.calls.lock().expect("rpc capture poisoned").clear();
+    }
+
+    /// The `n` most recent calls, one per line
+    pub fn dump(&self, n: usize) -> String {
+        let calls = self.calls();
+        calls[calls.len().saturating_sub(n)..]
+            .iter()
+            .map(|call| format!("{}\n", call))
+            .collect()
+    }
+
+    /// Fail (listing the calls) if more than `max_calls` were made
+    pub fn assert_at_most(&self, max_calls: usize) -> Result<()> {
+        let count = self.call_count();
+        ensure!(
+            count <= max_calls,
+            "Expected at most {} RPC calls, {} were made:\n{}",
+            max_calls,
+            count,
+            self.dump(count)
+        );
+        Ok(())
+    }
+}
+
+/// Where the proxy gets its answers
+#[derive(Clone)]
+enum Backend {
+    Upstream {
+        url: String,
+        http_client: reqwest::Client,
+    },
+    Replay(Arc<Mutex<Replay>>),
+}
+
+/// A cassette being replayed
+struct Replay {
+    interactions: Vec<RpcCall>,
+    used: Vec<bool>,
+    unexpected: Vec<String>,
+}
+
+impl Replay {
+    fn answer(&mut self, method: &str, params: &serde_json::Value) -> (u16, String) {
+        let next = (0..self.interactions.len()).find(|&i| {
+            !self.used[i]
+                && self.interactions[i].method == method
+                && self.interactions[i].params == *params
+        });
+
+        match next {
+            Some(i) => {
+                self.used[i] = true;
+                let interaction = &self.interactions[i];
+                (interaction.status, interaction.body.clone())
+            }
+            None => {
+                let request = format!("{}({})", method, params);
+                tracing::error!("Unexpected RPC request during replay: {}", request);
+
+                let error = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "error": {
+                        "code": UNEXPECTED_REQUEST_ERROR_CODE,
+                        "message": format!("Unexpected request during replay: {}", request),
+                    },
+                });
+                self.unexpected.push(request);
+                (200, error.to_string())
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Proxy {
+    backend: Backend,
+    calls: Arc<Mutex<Vec<RpcCall>>>,
+}
+
+impl Proxy {
+    async fn respond(
+        &self,
+        request: &HttpRequest,
+        method: &str,
+        params: &serde_json::Value,
+    ) -> (u16, String) {
+        match &self.backend {
+            Backend::Upstream { url, http_client } => {
+                forward(http_client, url, &request.path, request.body.clone()).await
+            }
+            Backend::Replay(replay) => {
+                let (status, body) = replay
+                    .lock()
+                    .expect("rpc replay poisoned")
+                    .answer(method, params);
+                (status, with_request_id(&body, &request.body))
+            }
+        }
+    }
+
+    fn record(&self, call: RpcCall) {
+        tracing::trace!("RPC {}", call);
+        self.calls.lock().expect("rpc capture poisoned").push(call);
+    }
+}
+
+/// `body` answering the request `request_body`: clients match responses to requests by id,
+/// which needn't be the one recorded
+fn with_request_id(body: &str, request_body: &[u8]) -> String {
+    let request_id = serde_json::from_slice::<serde_json::Value>(request_body)
+        .ok()
+        .and_then(|mut request| request.get_mut("id").map(serde_json::Value::take));
+
+    match (serde_json::from_str::<serde_json::Value>(body), request_id) {
+        (Ok(mut response), Some(request_id)) if response.is_object() => {
+            response["id"] = request_id;
+            response.to_string()
+        }
+        _ => body.to_string(),
+    }
+}
+
+async fn forward(
+    http_client: &reqwest::Client,
+    url: &str,
+    path: &str,
+    body: Vec<u8>,
+) -> (u16, String) {
+    let response = http_client
+        .post(format!("{}{}", url, path))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await;
+
+    match response {
+        Ok(response) => {
+            let status = response.status().as_u16();
+            match response.text().await {
+                Ok(body) => (status, body),
+                Err(e) => (502, e.to_string()),
+            }
+        }
+        Err(e) => (502, e.to_string()),
+    }
+}
+
+/// A JSON-RPC request, as read off a client connection
+struct HttpRequest {
+    path: String,
+    body: Vec<u8>,
+    keep_alive: bool,
+}
+
+/// (method, params) of a JSON-RPC request body; batches are recorded as one `batch` call
+fn describe_request(body: &[u8]) -> (String, serde_json::Value) {
+    match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(request) if request.is_array() => ("batch".to_string(), request),
+        Ok(mut request) => (
+            request["method"].as_str().unwrap_or("?").to_string(),
+            request["params"].take(),
+        ),
+        Err(_) => (
+            "?".to_string(),
+            serde_json::Value::String(String::from_utf8_lossy(body).into_owned()),
+        ),
+    }
+}
+
+async fn serve_connection(stream: TcpStream, proxy: Proxy) {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    loop {
+        let request = match read_request(&mut reader).await {
+            Ok(Some(request)) => request,
+            Ok(None) => break,
+            Err(e) => {
+                tracing::debug!("RPC capture proxy dropped a connection: {:#}", e);
+                break;
+            }
+        };
+
+        let (method, params) = describe_request(&request.body);
+        let started = Instant::now();
+        let (status, body) = proxy.respond(&request, &method, &params).await;
+        proxy.record(RpcCall {
+            method,
+            params,
+            status,
+            body: body.clone(),
+            latency: started.elapsed(),
+        });
+
+        if let Err(e) = write_response(&mut writer, status, &body, request.keep_alive).await {
+            tracing::debug!("RPC capture proxy failed to respond: {}", e);
+            break;
+        }
+        if !request.keep_alive {
+            break;
+        }
+    }
+}
+
+/// Read one HTTP/1.1 request; `None` once the client closed the connection
+async fn read_request<R>(reader: &mut R) -> Result<Option<HttpRequest>>
+where
+    R: AsyncBufReadExt + Unpin,
+{
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).await? == 0 {
+        return Ok(None);
+    }
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .ok_or_else(|| anyhow!("Malformed request line {:?}", request_line))?
+        .to_string();
+
+    let mut content_length = 0;
+    let mut keep_alive = true;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 {
+            return Err(anyhow!("Connection closed in the middle of a request"));
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .parse()
+                    .with_context(|| format!("Invalid content-length {:?}", value))?;
+            } else if name.eq_ignore_ascii_case("connection") {
+                keep_alive = !value.eq_ignore_ascii_case("close");
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+
+    Ok(Some(HttpRequest {
+        path,
+        body,
+        keep_alive,
+    }))
+}
+
+async fn write_response<W>(writer: &mut W, status: u16, body: &str, keep_alive: bool) -> Result<()>
+where
+    W: AsyncWriteExt + Unpin,
+{
+    let reason = reqwest::StatusCode::from_u16(status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or("Unknown");
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: {}\r\n\r\n",
+        status,
+        reason,
+        body.len(),
+        if keep_alive { "keep-alive" } else { "close" }
+    );
+
+    writer.write_all(head.as_bytes()).await?;
+    writer.write_all(body.as_bytes()).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+impl TestContext {
+    /// The run's RPC capture, when `TestRunnerConfig::capture_rpc` is set
+    pub fn rpc_capture(&self) -> ArchTestingResult<&RpcCapture> {
+        self.validator().rpc_capture.as_ref().ok_or_else(|| {
+            anyhow!("RPC capture isn't enabled (set TestRunnerConfig::capture_rpc)").into()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(status: u16, body: &str) -> RpcCall {
+        RpcCall {
+            method: "get_block_count".to_string(),
+            params: serde_json::json!([]),
+            status,
+            body: body.to_string(),
+            latency: Duration::from_millis(3),
+        }
+    }
+
+    #[test]
+    fn test_rpc_call_outcomes() {
+        let ok = call(200, r#"{"jsonrpc":"2.0","id":"1","result":42}"#);
+        assert!(ok.is_ok());
+        assert_eq!(ok.result(), Some(serde_json::json!(42)));
+
+        let rpc_error = call(
+            200,
+            r#"{"jsonrpc":"2.0","id":"1","error":{"code":-32601,"message":"no such method"}}"#,
+        );
+        assert_eq!(rpc_error.result(), None);
+        assert!(rpc_error.error().unwrap().contains("no such method"));
+
+        let http_error = call(429, "Too Many Requests");
+        assert_eq!(
+            http_error.error().as_deref(),
+            Some("http 429: Too Many Requests")
+        );
+    }
+
+    #[test]
+    fn test_describe_request() {
+        let (method, params) = describe_request(
+            br#"{"jsonrpc":"2.0","id":"1","method":"read_account_info","params":"ab"}"#,
+        );
+        assert_eq!(method, "read_account_info");
+        assert_eq!(params, serde_json::json!("ab"));
+    }
+
+    #[test]
+    fn test_replay_answers_in_recorded_order() {
+        let pending = call(200, r#"{"jsonrpc":"2.0","id":"a","result":null}"#);
+        let processed = call(200, r#"{"jsonrpc":"2.0","id":"b","result":7}"#);
+        let mut replay = Replay {
+            used: vec![false; 2],
+            interactions: vec![pending.clone(), processed.clone()],
+            unexpected: Vec::new(),
+        };
+        let params = serde_json::json!([]);
+
+        assert_eq!(replay.answer("get_block_count", &params).1, pending.body);
+        assert_eq!(replay.answer("get_block_count", &params).1, processed.body);
+        assert!(replay.unexpected.is_empty());
+
+        let (_, body) = replay.answer("get_block_count", &params);
+        assert!(body.contains("Unexpected request during replay"));
+        assert_eq!(replay.unexpected, vec!["get_block_count([])".to_string()]);
+
+        let request = br#"{"jsonrpc":"2.0","id":"42","method":"get_block_count","params":[]}"#;
+        let answered: serde_json::Value =
+            serde_json::from_str(&with_request_id(&processed.body, request)).unwrap();
+        assert_eq!(answered["id"], "42");
+        assert_eq!(answered["result"], 7);
+    }
+}
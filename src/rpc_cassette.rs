@@ -0,0 +1,131 @@
+//! VCR-style record and replay of Arch RPC traffic (`TestRunnerConfig::rpc_cassette`).
+//!
+//! A recording run captures every Arch RPC call into a cassette file (written only if the
+//! test passes). A replaying run starts no containers at all: the RPC clients are answered from
+//! the cassette, and any request it doesn't contain fails the run.
+//!
+//! Only deterministic tests replay: keys, amounts and the order of requests with the same
+//! method and params must not change between runs (see `keypair_from_seed`). Replayed runs
+//! have no bitcoind, Titan or validator websocket, so tests using them can't be replayed.
+//!
+//! ```ignore
+//! let cassette = PathBuf::from("tests/cassettes/transfer.json");
+//! config.rpc_cassette = Some(if cassette.exists() {
+//!     RpcCassetteMode::Replay(cassette)
+//! } else {
+//!     RpcCassetteMode::Record(cassette)
+//! });
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{RpcCall, RpcCapture};
+
+/// Bumped when the cassette format changes incompatibly
+pub const RPC_CASSETTE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RpcCassetteMode {
+    /// Run against the containers and save every Arch RPC call to this file
+    Record(PathBuf),
+    /// Run without containers, answering Arch RPC calls from this file
+    Replay(PathBuf),
+}
+
+impl RpcCassetteMode {
+    pub fn path(&self) -> &Path {
+        match self {
+            RpcCassetteMode::Record(path) | RpcCassetteMode::Replay(path) => path,
+        }
+    }
+
+    pub fn is_replay(&self) -> bool {
+        matches!(self, RpcCassetteMode::Replay(_))
+    }
+}
+
+/// Recorded Arch RPC calls, in the order they completed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcCassette {
+    pub version: u32,
+    pub interactions: Vec<RpcCall>,
+}
+
+impl RpcCassette {
+    pub fn new(interactions: Vec<RpcCall>) -> Self {
+        Self {
+            version: RPC_CASSETTE_VERSION,
+            interactions,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read RPC cassette {}", path.display()))?;
+        let cassette: Self = serde_json::from_str(&json)
+            .with_context(|| format!("Invalid RPC cassette {}", path.display()))?;
+
+        ensure!(
+            cassette.version == RPC_CASSETTE_VERSION,
+            "RPC cassette {} has version {}, expected {} (record it again)",
+            path.display(),
+            cassette.version,
+            RPC_CASSETTE_VERSION
+        );
+
+        Ok(cassette)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write RPC cassette {}", path.display()))
+    }
+}
+
+/// After the test: save the recording of a passed run, or fail a replay that saw requests
+/// its cassette doesn't contain
+pub(crate) fn finish_rpc_cassette(
+    mode: &RpcCassetteMode,
+    rpc_capture: &RpcCapture,
+    test_passed: bool,
+) -> Result<()> {
+    match mode {
+        RpcCassetteMode::Record(path) if test_passed => {
+            let cassette = rpc_capture.cassette();
+            cassette.save(path)?;
+            tracing::info!(
+                "Recorded {} RPC calls into {}",
+                cassette.interactions.len(),
+                path.display()
+            );
+        }
+        RpcCassetteMode::Record(path) => {
+            tracing::warn!("Test failed, not saving RPC cassette {}", path.display());
+        }
+        RpcCassetteMode::Replay(path) => {
+            let unexpected = rpc_capture.unexpected_requests();
+            ensure!(
+                unexpected.is_empty(),
+                "{} RPC requests aren't in cassette {} (record it again):\n{}",
+                unexpected.len(),
+                path.display(),
+                unexpected.join("\n")
+            );
+        }
+    }
+
+    Ok(())
+}
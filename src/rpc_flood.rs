@@ -0,0 +1,221 @@
+//! Flooding the validator RPC, to test rate limiting and overload behavior.
+//!
+//! ```ignore
+//! let report = ctx
+//!     .flood_rpc(RpcFlood::new("get_block_count").rate(500.0).duration(Duration::from_secs(10)))
+//!     .await?;
+//! assert!(report.outcomes.contains_key("http 429"));
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use anyhow::{ensure, Result};
+use futures::{stream, StreamExt};
+use tokio::time::MissedTickBehavior;
+
+use crate::{load::percentile, TestContext};
+
+pub const DEFAULT_FLOOD_RATE: f64 = 100.0;
+pub const DEFAULT_FLOOD_DURATION: Duration = Duration::from_secs(5);
+pub const DEFAULT_FLOOD_MAX_IN_FLIGHT: usize = 256;
+
+/// Shortest gap between requests; higher rates send in bursts at this period
+const MIN_FLOOD_PERIOD: Duration = Duration::from_micros(1);
+
+/// Outcome key for requests that returned a result
+pub const FLOOD_OUTCOME_OK: &str = "ok";
+
+/// An RPC flood: `rate` requests per second of one method, for `duration`
+#[derive(Debug, Clone)]
+pub struct RpcFlood {
+    pub method: String,
+    pub params: serde_json::Value,
+    pub rate: f64,
+    pub duration: Duration,
+    /// Requests beyond this many outstanding wait, which lowers the achieved rate
+    pub max_in_flight: usize,
+}
+
+impl RpcFlood {
+    pub fn new(method: impl Into<String>) -> Self {
+        Self {
+            method: method.into(),
+            params: serde_json::Value::Array(Vec::new()),
+            rate: DEFAULT_FLOOD_RATE,
+            duration: DEFAULT_FLOOD_DURATION,
+            max_in_flight: DEFAULT_FLOOD_MAX_IN_FLIGHT,
+        }
+    }
+
+    pub fn params(mut self, params: serde_json::Value) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Requests per second
+    pub fn rate(mut self, rate: f64) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight.max(1);
+        self
+    }
+}
+
+/// What an RPC flood observed
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcFloodReport {
+    pub sent: usize,
+    pub elapsed: Duration,
+    /// Requests per second actually sent
+    pub achieved_rate: f64,
+    /// Request count per outcome: `FLOOD_OUTCOME_OK`, `http <status>`, `rpc error <code>`,
+    /// `timeout`, `connect`, `invalid response` or another transport error
+    pub outcomes: BTreeMap<String, usize>,
+    pub latency_p50: Duration,
+    pub latency_p99: Duration,
+    pub latency_max: Duration,
+}
+
+impl RpcFloodReport {
+    pub fn ok_count(&self) -> usize {
+        self.outcomes
+            .get(FLOOD_OUTCOME_OK)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Fraction of requests that didn't succeed
+    pub fn error_rate(&self) -> f64 {
+        (self.sent - self.ok_count()) as f64 / self.sent.max(1) as f64
+    }
+}
+
+impl fmt::Display for RpcFloodReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} requests in {:?} ({:.1}/s), outcomes {:?}, latency p50 {:?} / p99 {:?} / max {:?}",
+            self.sent,
+            self.elapsed,
+            self.achieved_rate,
+            self.outcomes,
+            self.latency_p50,
+            self.latency_p99,
+            self.latency_max
+        )
+    }
+}
+
+impl TestContext {
+    /// Send `flood.rate` requests per second to the validator RPC for `flood.duration`,
+    /// recording the outcome and latency of each
+    pub async fn flood_rpc(&self, flood: RpcFlood) -> Result<RpcFloodReport> {
+        ensure!(
+            flood.rate.is_finite() && flood.rate > 0.0,
+            "Flood rate must be positive and finite, got {}",
+            flood.rate
+        );
+
+        let total = (flood.rate * flood.duration.as_secs_f64()).ceil() as usize;
+        // `interval` panics on a zero period, which very high rates round down to
+        let period = Duration::from_secs_f64(1.0 / flood.rate).max(MIN_FLOOD_PERIOD);
+        let mut ticks = tokio::time::interval(period);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Burst);
+
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": "arch-testing-flood",
+            "method": flood.method,
+            "params": flood.params,
+        });
+        let request = &request;
+        let validator = self.validator();
+
+        tracing::info!(
+            "Flooding {} with {} at {}/s for {:?}",
+            validator.rpc_url(),
+            flood.method,
+            flood.rate,
+            flood.duration
+        );
+
+        let started = Instant::now();
+        let samples: Vec<(String, Duration)> = stream::unfold(ticks, |mut ticks| async move {
+            ticks.tick().await;
+            Some(((), ticks))
+        })
+        .take(total)
+        .map(|_| async move {
+            let sent = Instant::now();
+            let response = validator
+                .http_client
+                .post(validator.rpc_url())
+                .json(request)
+                .send()
+                .await;
+            (classify_response(response).await, sent.elapsed())
+        })
+        .buffer_unordered(flood.max_in_flight)
+        .collect()
+        .await;
+        let elapsed = started.elapsed();
+
+        let mut latencies = samples
+            .iter()
+            .map(|(_, latency)| *latency)
+            .collect::<Vec<_>>();
+        latencies.sort();
+
+        let mut outcomes = BTreeMap::new();
+        for (outcome, _) in samples {
+            *outcomes.entry(outcome).or_default() += 1;
+        }
+
+        let report = RpcFloodReport {
+            sent: latencies.len(),
+            elapsed,
+            achieved_rate: latencies.len() as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            outcomes,
+            latency_p50: percentile(&latencies, 50.0),
+            latency_p99: percentile(&latencies, 99.0),
+            latency_max: latencies.last().copied().unwrap_or_default(),
+        };
+        tracing::info!("RPC flood finished: {}", report);
+
+        Ok(report)
+    }
+}
+
+async fn classify_response(response: reqwest::Result<reqwest::Response>) -> String {
+    let response = match response {
+        Ok(response) => response,
+        Err(e) if e.is_timeout() => return String::from("timeout"),
+        Err(e) if e.is_connect() => return String::from("connect"),
+        Err(e) => return format!("error: {}", e),
+    };
+
+    let status = response.status();
+    if !status.is_success() {
+        return format!("http {}", status.as_u16());
+    }
+
+    match response.json::<serde_json::Value>().await {
+        Ok(body) => match body.get("error").filter(|error| !error.is_null()) {
+            Some(error) => format!("rpc error {}", error["code"]),
+            None => String::from(FLOOD_OUTCOME_OK),
+        },
+        Err(_) => String::from("invalid response"),
+    }
+}
@@ -0,0 +1,235 @@
+//! Runes lifecycle helpers: etch, mint and transfer on regtest, then assert balances via Titan.
+//!
+//! Every helper builds its runestone transaction, funds it from the test wallet, mines it and
+//! waits for Titan to index the block. Rune-bearing outputs go to test wallet addresses (unless
+//! transferred elsewhere), so the wallet may pick them up as fee inputs later; unallocated runes
+//! then flow to the transaction's first non-OP_RETURN output, never burned.
+
+use std::time::{Duration, Instant};
+
+use anyhow::{ensure, Context, Result};
+use bitcoin::{
+    absolute::LockTime, transaction::Version, Address, Amount, OutPoint, Sequence, Transaction,
+    TxIn, TxOut, Txid, Witness,
+};
+use bitcoincore_rpc::{json::FundRawTransactionOptions, RpcApi};
+use ordinals::{Edict, Etching, RuneId, Runestone};
+use titan_client::TitanApi;
+
+use crate::TestContext;
+
+/// Value of each rune-bearing output (comfortably above dust for any address type)
+pub const RUNE_OUTPUT_VALUE: Amount = Amount::from_sat(10_000);
+
+pub const TITAN_INDEX_TIMEOUT: Duration = Duration::from_secs(30);
+const TITAN_INDEX_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A rune etched by `TestContext::etch_rune`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EtchedRune {
+    pub id: RuneId,
+    pub txid: Txid,
+    /// Output holding the premine (if any)
+    pub premine_outpoint: OutPoint,
+}
+
+impl TestContext {
+    /// Etch a rune and wait for Titan to index it.
+    ///
+    /// `etching.rune` must be `None`: named runes require a commit/reveal with six confirmations
+    /// in between, while unnamed etchings are assigned a reserved name immediately.
+    pub async fn etch_rune(&self, etching: Etching) -> Result<EtchedRune> {
+        ensure!(
+            etching.rune.is_none(),
+            "Named etchings require a commitment; leave `rune` unset to get a reserved name"
+        );
+
+        let runestone = Runestone {
+            etching: Some(etching),
+            ..Default::default()
+        };
+        let recipient = self.bitcoin().client.get_new_address().await?;
+
+        let txid = self.send_runestone(runestone, &[], &[recipient]).await?;
+        let height = self.confirm_and_index().await?;
+
+        let block_hash = self
+            .with_bitcoin_client(move |client| client.get_block_hash(height))
+            .await?;
+        let block = self
+            .with_bitcoin_client(move |client| client.get_block(&block_hash))
+            .await?;
+        let tx_index = block
+            .txdata
+            .iter()
+            .position(|tx| tx.compute_txid() == txid)
+            .with_context(|| format!("Etching {} wasn't mined in block {}", txid, block_hash))?;
+
+        let id = RuneId {
+            block: height,
+            tx: tx_index as u32,
+        };
+        tracing::debug!("Etched rune {} in {}", id, txid);
+
+        Ok(EtchedRune {
+            id,
+            txid,
+            premine_outpoint: OutPoint::new(txid, 1),
+        })
+    }
+
+    /// Mint one batch of `rune` (per its etching's terms) to a fresh test wallet address,
+    /// returning the output holding the minted amount
+    pub async fn mint_rune(&self, rune: RuneId) -> Result<OutPoint> {
+        let runestone = Runestone {
+            mint: Some(rune),
+            ..Default::default()
+        };
+        let recipient = self.bitcoin().client.get_new_address().await?;
+
+        let txid = self.send_runestone(runestone, &[], &[recipient]).await?;
+        self.confirm_and_index().await?;
+
+        Ok(OutPoint::new(txid, 1))
+    }
+
+    /// Move `amount` of `rune` held by `from` to `to`; whatever is left goes back to the test
+    /// wallet. Returns the output holding the transferred amount.
+    pub async fn transfer_rune(
+        &self,
+        from: OutPoint,
+        rune: RuneId,
+        amount: u128,
+        to: &Address,
+    ) -> Result<OutPoint> {
+        // outputs: [OP_RETURN, recipient, remainder, change]; the remainder output is explicit so
+        // the pointer is in range however the wallet funds the transaction
+        let remainder = self.bitcoin().client.get_new_address().await?;
+        let runestone = Runestone {
+            edicts: vec![Edict {
+                id: rune,
+                amount,
+                output: 1,
+            }],
+            pointer: Some(2),
+            ..Default::default()
+        };
+
+        let txid = self
+            .send_runestone(runestone, &[from], &[to.clone(), remainder])
+            .await?;
+        self.confirm_and_index().await?;
+
+        Ok(OutPoint::new(txid, 1))
+    }
+
+    /// Assert that Titan reports exactly `expected` of `rune` across the outputs of `address`
+    pub async fn assert_rune_balance(
+        &self,
+        address: &Address,
+        rune: RuneId,
+        expected: u128,
+    ) -> Result<()> {
+        let balance = self.rune_balance(address, rune).await?;
+
+        ensure!(
+            balance == expected,
+            "Expected {} to hold {} of rune {}, titan reports {}",
+            address,
+            expected,
+            rune,
+            balance
+        );
+
+        Ok(())
+    }
+
+    /// Titan's view of how much of `rune` the outputs of `address` hold
+    pub async fn rune_balance(&self, address: &Address, rune: RuneId) -> Result<u128> {
+        let address_data = self
+            .titan()
+            .client
+            .get_address(&address.to_string())
+            .await
+            .with_context(|| format!("Failed to fetch {} from titan", address))?;
+
+        Ok(address_data
+            .outputs
+            .iter()
+            .flat_map(|output| output.runes.iter())
+            .filter(|rune_amount| rune_amount.rune_id == rune)
+            .map(|rune_amount| rune_amount.amount)
+            .sum())
+    }
+
+    /// Build `[OP_RETURN runestone, recipients..]`, spending `inputs`, and let the wallet add
+    /// fee inputs and a change output after the recipients
+    async fn send_runestone(
+        &self,
+        runestone: Runestone,
+        inputs: &[OutPoint],
+        recipients: &[Address],
+    ) -> Result<Txid> {
+        let mut output = vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: runestone.encipher(),
+        }];
+        output.extend(recipients.iter().map(|recipient| TxOut {
+            value: RUNE_OUTPUT_VALUE,
+            script_pubkey: recipient.script_pubkey(),
+        }));
+        let change_position = output.len() as u32;
+
+        let unfunded = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: inputs
+                .iter()
+                .map(|outpoint| TxIn {
+                    previous_output: *outpoint,
+                    script_sig: Default::default(),
+                    sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                    witness: Witness::new(),
+                })
+                .collect(),
+            output,
+        };
+
+        self.with_bitcoin_client(move |client| {
+            let options = FundRawTransactionOptions {
+                change_position: Some(change_position),
+                ..Default::default()
+            };
+            let funded = client.fund_raw_transaction(&unfunded, Some(&options), None)?;
+            let signed = client.sign_raw_transaction_with_wallet(&funded.hex, None, None)?;
+            client.send_raw_transaction(&signed.transaction()?)
+        })
+        .await
+        .context("Failed to send runestone transaction")
+    }
+
+    /// Mine a block and wait for Titan to index it, returning its height
+    pub(crate) async fn confirm_and_index(&self) -> Result<u64> {
+        self.bitcoin_mine_blocks(1).await?;
+        let height = self
+            .with_bitcoin_client(|client| client.get_block_count())
+            .await?;
+
+        let deadline = Instant::now() + TITAN_INDEX_TIMEOUT;
+        loop {
+            if let Ok((titan_height, _)) = self.titan_tip().await {
+                if titan_height >= height {
+                    return Ok(height);
+                }
+            }
+
+            ensure!(
+                Instant::now() < deadline,
+                "Titan didn't index block {} within {:?}",
+                height,
+                TITAN_INDEX_TIMEOUT
+            );
+            tokio::time::sleep(TITAN_INDEX_POLL_INTERVAL).await;
+        }
+    }
+}
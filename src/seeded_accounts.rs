@@ -0,0 +1,88 @@
+//! Accounts at addresses derived from a base pubkey and a seed string, for programs that manage
+//! many deterministic sub-accounts of one authority.
+//!
+//! ```ignore
+//! let lamports = ctx.minimum_balance_for(64);
+//! let slot = ctx
+//!     .create_account_with_seed(payer_kp, authority_kp, "slot-7", lamports, 64, &program_id)
+//!     .await?;
+//! assert_eq!(slot, ctx.address_with_seed(&authority, "slot-7", &program_id)?);
+//! ```
+
+use anyhow::{anyhow, ensure, Context, Result};
+use arch_program::{pubkey::Pubkey, system_instruction};
+use bitcoin::key::Keypair;
+
+use crate::{ProcessedTransactionExt, TestContext};
+
+impl TestContext {
+    /// The address of the account `seed` of `base`, owned by `owner`
+    pub fn address_with_seed(&self, base: &Pubkey, seed: &str, owner: &Pubkey) -> Result<Pubkey> {
+        Pubkey::create_with_seed(base, seed, owner)
+            .map_err(|e| anyhow!("Invalid seed {:?} for base {}: {:?}", seed, base, e))
+    }
+
+    /// Create the account `seed` of `base_kp`'s pubkey holding `lamports` and `space` bytes,
+    /// owned by `owner`, paid for by `payer_kp` (which may be the base). Confirms it exists with
+    /// that owner and returns its address.
+    pub async fn create_account_with_seed(
+        &self,
+        payer_kp: Keypair,
+        base_kp: Keypair,
+        seed: &str,
+        lamports: u64,
+        space: u64,
+        owner: &Pubkey,
+    ) -> Result<Pubkey> {
+        let payer = Pubkey::from_slice(&payer_kp.x_only_public_key().0.serialize());
+        let base = Pubkey::from_slice(&base_kp.x_only_public_key().0.serialize());
+        let address = self.address_with_seed(&base, seed, owner)?;
+
+        let instruction = system_instruction::create_account_with_seed(
+            &payer, &address, &base, seed, lamports, space, owner,
+        );
+        let signers = if payer == base {
+            vec![payer_kp]
+        } else {
+            vec![payer_kp, base_kp]
+        };
+
+        let processed_tx = self
+            .send_instructions(&[instruction], signers)
+            .await
+            .with_context(|| {
+                format!("Failed to create account {} with seed {:?}", address, seed)
+            })?;
+        if let Some(failure) = processed_tx.failure() {
+            return Err(anyhow!(
+                "Failed to create account {} with seed {:?}: {}",
+                address,
+                seed,
+                failure
+            ));
+        }
+
+        let account = self
+            .read_account_info(address)
+            .await
+            .with_context(|| format!("Account {} wasn't created", address))?;
+        ensure!(
+            account.owner == *owner,
+            "Account {} is owned by {}, expected {}",
+            address,
+            account.owner,
+            owner
+        );
+
+        tracing::debug!(
+            "Created account {} (seed {:?} of {}, {} lamports, {} bytes, owner {})",
+            address,
+            seed,
+            base,
+            lamports,
+            space,
+            owner
+        );
+        Ok(address)
+    }
+}
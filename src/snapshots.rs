@@ -0,0 +1,291 @@
+//! Account state snapshots and diffs, to assert exactly which accounts a transaction changed.
+//!
+//! ```ignore
+//! let before = ctx.snapshot_accounts(&[payer, counter]).await?;
+//! ctx.send_transaction(tx).await?;
+//! let after = ctx.snapshot_accounts(&[payer, counter]).await?;
+//! before.diff(&after).assert_only_changed(&[counter])?;
+//! ```
+
+use std::{collections::BTreeMap, fmt};
+
+use anyhow::{ensure, Context, Result};
+use arch_program::pubkey::Pubkey;
+use arch_sdk::ProcessedTransaction;
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::Serialize;
+
+use crate::{TestContext, DEFAULT_ACCOUNT_READ_CONCURRENCY};
+
+/// The parts of an account a test cares about when comparing states
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountState {
+    pub lamports: u64,
+    pub owner: Pubkey,
+    pub data: Vec<u8>,
+    pub utxo: String,
+}
+
+impl From<arch_sdk::AccountInfo> for AccountState {
+    fn from(account: arch_sdk::AccountInfo) -> Self {
+        Self {
+            lamports: account.lamports,
+            owner: account.owner,
+            data: account.data,
+            utxo: account.utxo,
+        }
+    }
+}
+
+/// Serializable, run-independent view of an account for golden (insta) snapshots. The anchor
+/// utxo is left out, since its txid differs on every run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AccountRecord {
+    pub lamports: u64,
+    pub owner: String,
+    pub data_len: usize,
+    /// Hex encoded
+    pub data: String,
+}
+
+impl From<&AccountState> for AccountRecord {
+    fn from(state: &AccountState) -> Self {
+        Self {
+            lamports: state.lamports,
+            owner: state.owner.to_string(),
+            data_len: state.data.len(),
+            data: hex::encode(&state.data),
+        }
+    }
+}
+
+/// Serializable view of a processed transaction's outcome for golden (insta) snapshots
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TransactionRecord {
+    pub status: String,
+    pub logs: Vec<String>,
+}
+
+impl From<&ProcessedTransaction> for TransactionRecord {
+    fn from(processed_tx: &ProcessedTransaction) -> Self {
+        Self {
+            status: format!("{:?}", processed_tx.status),
+            logs: processed_tx.logs.clone(),
+        }
+    }
+}
+
+/// Assert that an account matches its insta snapshot:
+/// `assert_account_snapshot!(ctx, pubkey)` or `assert_account_snapshot!("name", ctx, pubkey)`.
+///
+/// Panics (like every insta assertion) if the account can't be read.
+#[macro_export]
+macro_rules! assert_account_snapshot {
+    ($ctx:expr, $pubkey:expr) => {{
+        let record = $ctx
+            .account_record($pubkey)
+            .await
+            .expect("Failed to read account for snapshot");
+        $crate::insta::assert_yaml_snapshot!(record);
+    }};
+    ($name:expr, $ctx:expr, $pubkey:expr) => {{
+        let record = $ctx
+            .account_record($pubkey)
+            .await
+            .expect("Failed to read account for snapshot");
+        $crate::insta::assert_yaml_snapshot!($name, record);
+    }};
+}
+
+/// Accounts as of one point in time; `None` for accounts that didn't exist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountsSnapshot {
+    pub accounts: BTreeMap<Pubkey, Option<AccountState>>,
+}
+
+impl AccountsSnapshot {
+    /// Changes from `self` to `other`, over the accounts in either snapshot
+    pub fn diff(&self, other: &AccountsSnapshot) -> AccountsDiff {
+        let mut pubkeys = self
+            .accounts
+            .keys()
+            .chain(other.accounts.keys())
+            .collect::<Vec<_>>();
+        pubkeys.sort();
+        pubkeys.dedup();
+
+        let changes = pubkeys
+            .into_iter()
+            .filter_map(|pubkey| {
+                let before = self.accounts.get(pubkey).cloned().flatten();
+                let after = other.accounts.get(pubkey).cloned().flatten();
+
+                (before != after).then_some(AccountChange {
+                    pubkey: *pubkey,
+                    before,
+                    after,
+                })
+            })
+            .collect();
+
+        AccountsDiff { changes }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountChange {
+    pub pubkey: Pubkey,
+    pub before: Option<AccountState>,
+    pub after: Option<AccountState>,
+}
+
+impl AccountChange {
+    /// Signed lamport change, treating missing accounts as holding 0
+    pub fn lamports_delta(&self) -> i128 {
+        let lamports = |state: &Option<AccountState>| {
+            state
+                .as_ref()
+                .map(|state| state.lamports)
+                .unwrap_or_default() as i128
+        };
+
+        lamports(&self.after) - lamports(&self.before)
+    }
+}
+
+impl fmt::Display for AccountChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (before, after) = match (&self.before, &self.after) {
+            (None, None) => return write!(f, "{}: unchanged", self.pubkey),
+            (None, Some(after)) => {
+                return write!(
+                    f,
+                    "{}: created ({} lamports, owner {}, {} bytes)",
+                    self.pubkey,
+                    after.lamports,
+                    after.owner,
+                    after.data.len()
+                )
+            }
+            (Some(_), None) => return write!(f, "{}: removed", self.pubkey),
+            (Some(before), Some(after)) => (before, after),
+        };
+
+        let mut changes = Vec::new();
+        if before.lamports != after.lamports {
+            changes.push(format!(
+                "lamports {} -> {} ({:+})",
+                before.lamports,
+                after.lamports,
+                self.lamports_delta()
+            ));
+        }
+        if before.owner != after.owner {
+            changes.push(format!("owner {} -> {}", before.owner, after.owner));
+        }
+        if before.data != after.data {
+            let first_difference = before
+                .data
+                .iter()
+                .zip(&after.data)
+                .position(|(before, after)| before != after)
+                .unwrap_or(before.data.len().min(after.data.len()));
+            changes.push(format!(
+                "data {} -> {} bytes, first difference at byte {}",
+                before.data.len(),
+                after.data.len(),
+                first_difference
+            ));
+        }
+        if before.utxo != after.utxo {
+            changes.push(format!("utxo {} -> {}", before.utxo, after.utxo));
+        }
+
+        write!(f, "{}: {}", self.pubkey, changes.join(", "))
+    }
+}
+
+/// The accounts that differ between two snapshots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountsDiff {
+    pub changes: Vec<AccountChange>,
+}
+
+impl AccountsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn changed_pubkeys(&self) -> Vec<Pubkey> {
+        self.changes.iter().map(|change| change.pubkey).collect()
+    }
+
+    pub fn get(&self, pubkey: &Pubkey) -> Option<&AccountChange> {
+        self.changes.iter().find(|change| change.pubkey == *pubkey)
+    }
+
+    /// Assert that every changed account is in `expected` (unchanged `expected` accounts are fine)
+    pub fn assert_only_changed(&self, expected: &[Pubkey]) -> Result<()> {
+        let unexpected = self
+            .changes
+            .iter()
+            .filter(|change| !expected.contains(&change.pubkey))
+            .map(|change| change.to_string())
+            .collect::<Vec<_>>();
+
+        ensure!(
+            unexpected.is_empty(),
+            "Unexpected account changes:\n  {}",
+            unexpected.join("\n  ")
+        );
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for AccountsDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return write!(f, "no account changes");
+        }
+
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", change)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl TestContext {
+    /// Capture the current state of `pubkeys`, for comparison with `AccountsSnapshot::diff`.
+    /// Accounts that don't exist are recorded as `None`; failing to read one is an error.
+    pub async fn snapshot_accounts(&self, pubkeys: &[Pubkey]) -> Result<AccountsSnapshot> {
+        let accounts = stream::iter(pubkeys.iter().copied())
+            .map(|pubkey| async move {
+                let account = self
+                    .try_read_account_info(pubkey)
+                    .await
+                    .with_context(|| format!("Failed to read account {}", pubkey))?;
+                Ok::<_, anyhow::Error>((pubkey, account.map(AccountState::from)))
+            })
+            .buffer_unordered(DEFAULT_ACCOUNT_READ_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        Ok(AccountsSnapshot { accounts })
+    }
+
+    /// Golden-snapshot view of `pubkey`, see `assert_account_snapshot!`
+    pub async fn account_record(&self, pubkey: Pubkey) -> Result<AccountRecord> {
+        let account = self
+            .read_account_info(pubkey)
+            .await
+            .with_context(|| format!("Failed to read account {}", pubkey))?;
+
+        Ok(AccountRecord::from(&AccountState::from(account)))
+    }
+}
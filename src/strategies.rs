@@ -0,0 +1,144 @@
+//! `proptest` strategies for Arch instructions and accounts, and a runner that replays each
+//! generated case against the live environment.
+//!
+//! ```ignore
+//! use arch_testing::strategies;
+//!
+//! let accounts = vec![counter_pubkey, payer_pubkey];
+//! ctx.run_proptest(
+//!     ProptestConfig::with_cases(32),
+//!     strategies::instruction(program_id, accounts, strategies::instruction_data(64)),
+//!     |instruction| async { check_counter_invariant(&ctx, instruction).await },
+//! )
+//! .await?;
+//! ```
+
+use std::{fmt::Debug, future::Future, ops::RangeInclusive};
+
+use anyhow::{anyhow, Result};
+use arch_program::{account::AccountMeta, instruction::Instruction, pubkey::Pubkey};
+use proptest::{
+    collection::vec,
+    prelude::*,
+    sample::select,
+    strategy::ValueTree,
+    test_runner::{Config as ProptestConfig, TestRunner},
+};
+
+use crate::TestContext;
+
+/// Lamport amounts in `range`, with the bounds themselves generated more often than chance
+pub fn lamports(range: RangeInclusive<u64>) -> impl Strategy<Value = u64> {
+    prop_oneof![
+        1 => Just(*range.start()),
+        1 => Just(*range.end()),
+        8 => range,
+    ]
+}
+
+/// Arbitrary pubkeys (accounts that almost certainly don't exist)
+pub fn pubkey() -> impl Strategy<Value = Pubkey> {
+    any::<[u8; 32]>().prop_map(Pubkey)
+}
+
+/// Non-signer metas over `pubkeys` (which must not be empty), writable or not
+pub fn account_meta(pubkeys: Vec<Pubkey>) -> impl Strategy<Value = AccountMeta> {
+    (select(pubkeys), any::<bool>()).prop_map(|(pubkey, is_writable)| AccountMeta {
+        pubkey,
+        is_signer: false,
+        is_writable,
+    })
+}
+
+/// Up to `max_len` metas drawn from `pubkeys` (which must not be empty)
+pub fn account_metas(
+    pubkeys: Vec<Pubkey>,
+    max_len: usize,
+) -> impl Strategy<Value = Vec<AccountMeta>> {
+    vec(account_meta(pubkeys), 0..=max_len)
+}
+
+/// Instruction data of up to `max_len` bytes
+pub fn instruction_data(max_len: usize) -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..=max_len)
+}
+
+/// Instructions for `program_id` with accounts drawn from `pubkeys` (which must not be empty)
+/// and data from `data`
+pub fn instruction(
+    program_id: Pubkey,
+    pubkeys: Vec<Pubkey>,
+    data: impl Strategy<Value = Vec<u8>>,
+) -> impl Strategy<Value = Instruction> {
+    (account_metas(pubkeys, 8), data).prop_map(move |(accounts, data)| Instruction {
+        program_id,
+        accounts,
+        data,
+    })
+}
+
+impl TestContext {
+    /// Run `test` against the live environment for each case `strategy` generates (per
+    /// `config.cases`), shrinking the first failing case before reporting it.
+    ///
+    /// Cases run one after another, sharing this context, so tests should only assert on
+    /// state they create themselves.
+    pub async fn run_proptest<S, F, Fut>(
+        &self,
+        config: ProptestConfig,
+        strategy: S,
+        test: F,
+    ) -> Result<()>
+    where
+        S: Strategy,
+        S::Value: Debug,
+        F: Fn(S::Value) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let max_shrink_iters = config.max_shrink_iters;
+        let cases = config.cases;
+        let mut runner = TestRunner::new(config);
+
+        for case in 0..cases {
+            let mut tree = strategy
+                .new_tree(&mut runner)
+                .map_err(|e| anyhow!("Failed to generate case {}: {}", case, e))?;
+
+            let Err(error) = test(tree.current()).await else {
+                continue;
+            };
+
+            // shrink: keep simplifying while the test still fails, backing off when it passes
+            let mut minimal = (format!("{:?}", tree.current()), error);
+            let mut last_failed = true;
+            for _ in 0..max_shrink_iters {
+                let moved = if last_failed {
+                    tree.simplify()
+                } else {
+                    tree.complicate()
+                };
+                if !moved {
+                    break;
+                }
+
+                last_failed = match test(tree.current()).await {
+                    Err(error) => {
+                        minimal = (format!("{:?}", tree.current()), error);
+                        true
+                    }
+                    Ok(()) => false,
+                };
+            }
+
+            let (input, error) = minimal;
+            return Err(error.context(format!(
+                "Property failed on case {} of {}, minimal input: {}",
+                case + 1,
+                cases,
+                input
+            )));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+//! Tampering with signed transactions, to check signature verification end-to-end.
+//!
+//! ```ignore
+//! let mut tx = ctx.build_and_sign_transaction(message, vec![payer_kp]).await?;
+//! corrupt_signature(&mut tx, 0)?;
+//! ctx.assert_transaction_rejected(tx, "signature").await?;
+//! ```
+
+use anyhow::{ensure, Result};
+use arch_sdk::RuntimeTransaction;
+
+fn ensure_signature_index(transaction: &RuntimeTransaction, index: usize) -> Result<()> {
+    ensure!(
+        index < transaction.signatures.len(),
+        "Signature index {} out of range, the transaction has {} signatures",
+        index,
+        transaction.signatures.len()
+    );
+    Ok(())
+}
+
+/// Flip a bit of signature `index`, so it no longer verifies
+pub fn corrupt_signature(transaction: &mut RuntimeTransaction, index: usize) -> Result<()> {
+    ensure_signature_index(transaction, index)?;
+    transaction.signatures[index].0[0] ^= 0x01;
+    Ok(())
+}
+
+/// Swap signatures `a` and `b`, so each is checked against the other signer's key
+pub fn swap_signatures(transaction: &mut RuntimeTransaction, a: usize, b: usize) -> Result<()> {
+    ensure_signature_index(transaction, a)?;
+    ensure_signature_index(transaction, b)?;
+    transaction.signatures.swap(a, b);
+    Ok(())
+}
+
+/// Drop signature `index`, leaving the transaction one signature short
+pub fn remove_signature(transaction: &mut RuntimeTransaction, index: usize) -> Result<()> {
+    ensure_signature_index(transaction, index)?;
+    transaction.signatures.remove(index);
+    Ok(())
+}
+
+/// Change instruction `index`'s data after signing, so the signatures no longer cover the
+/// message
+pub fn tamper_instruction_data(transaction: &mut RuntimeTransaction, index: usize) -> Result<()> {
+    let instruction_count = transaction.message.instructions.len();
+    ensure!(
+        index < instruction_count,
+        "Instruction index {} out of range, the transaction has {} instructions",
+        index,
+        instruction_count
+    );
+
+    let data = &mut transaction.message.instructions[index].data;
+    match data.last_mut() {
+        Some(byte) => *byte ^= 0x01,
+        None => data.push(0x01),
+    }
+
+    Ok(())
+}
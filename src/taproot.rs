@@ -0,0 +1,160 @@
+//! Taproot script-path helpers.
+//!
+//! Typical flow: `fund_taproot_output` with a script tree, `build_script_path_spend`, sign with
+//! `TaprootUtxo::script_path_signature`, then `TaprootUtxo::finalize_script_path_spend` and send.
+//! `spend_taproot_with_key` covers the common single `<pubkey> OP_CHECKSIG` leaf.
+
+use anyhow::{anyhow, Context, Result};
+use bitcoin::{
+    absolute::LockTime,
+    hashes::Hash,
+    key::{Keypair, XOnlyPublicKey},
+    secp256k1::{Message, Secp256k1},
+    sighash::{Prevouts, SighashCache},
+    taproot::{self, LeafVersion, TaprootSpendInfo},
+    transaction::Version,
+    Address, Amount, OutPoint, ScriptBuf, Sequence, TapLeafHash, TapSighashType, Transaction, TxIn,
+    TxOut, Txid, Witness,
+};
+
+use crate::TestContext;
+
+/// Fee paid by script-path spends (well above the regtest relay minimum)
+pub const TAPROOT_SPEND_FEE: Amount = Amount::from_sat(1_000);
+
+/// A confirmed taproot output committing to a script tree
+#[derive(Debug, Clone)]
+pub struct TaprootUtxo {
+    pub outpoint: OutPoint,
+    pub output: TxOut,
+    pub address: Address,
+    pub spend_info: TaprootSpendInfo,
+}
+
+impl TaprootUtxo {
+    /// BIP341 signature of `keypair` over input 0 of `tx`, spending through `leaf`
+    pub fn script_path_signature(
+        &self,
+        tx: &Transaction,
+        leaf: &ScriptBuf,
+        keypair: &Keypair,
+    ) -> Result<Vec<u8>> {
+        let leaf_hash = TapLeafHash::from_script(leaf, LeafVersion::TapScript);
+        let sighash = SighashCache::new(tx).taproot_script_spend_signature_hash(
+            0,
+            &Prevouts::All(&[self.output.clone()]),
+            leaf_hash,
+            TapSighashType::Default,
+        )?;
+
+        let signature = taproot::Signature {
+            signature: Secp256k1::new()
+                .sign_schnorr_no_aux_rand(&Message::from_digest(sighash.to_byte_array()), keypair),
+            sighash_type: TapSighashType::Default,
+        };
+
+        Ok(signature.to_vec())
+    }
+
+    /// Set input 0's witness to `stack` (bottom first), followed by `leaf` and its control block
+    pub fn finalize_script_path_spend(
+        &self,
+        tx: &mut Transaction,
+        leaf: &ScriptBuf,
+        stack: Vec<Vec<u8>>,
+    ) -> Result<()> {
+        let control_block = self
+            .spend_info
+            .control_block(&(leaf.clone(), LeafVersion::TapScript))
+            .context("Leaf isn't part of this output's script tree")?;
+
+        let mut witness = Witness::new();
+        for item in stack {
+            witness.push(item);
+        }
+        witness.push(leaf.as_bytes());
+        witness.push(control_block.serialize());
+
+        tx.input[0].witness = witness;
+
+        Ok(())
+    }
+}
+
+/// Spend info for a tree of `leaves` (equally weighted) under `internal_key`
+pub fn taproot_script_tree(
+    internal_key: XOnlyPublicKey,
+    leaves: &[ScriptBuf],
+) -> Result<TaprootSpendInfo> {
+    TaprootSpendInfo::with_huffman_tree(
+        &Secp256k1::new(),
+        internal_key,
+        leaves.iter().map(|leaf| (1, leaf.clone())),
+    )
+    .map_err(|e| anyhow!("Failed to build taproot script tree: {}", e))
+}
+
+impl TestContext {
+    /// Fund a taproot output committing to `spend_info` from the test wallet and confirm it
+    pub async fn fund_taproot_output(
+        &self,
+        spend_info: TaprootSpendInfo,
+        amount: Amount,
+    ) -> Result<TaprootUtxo> {
+        let address = Address::p2tr_tweaked(spend_info.output_key(), self.network);
+        let outpoint = self.send_btc(&address, amount, 1).await?;
+
+        Ok(TaprootUtxo {
+            outpoint,
+            output: TxOut {
+                value: amount,
+                script_pubkey: address.script_pubkey(),
+            },
+            address,
+            spend_info,
+        })
+    }
+
+    /// Unsigned transaction spending `utxo` back to the test wallet, minus `TAPROOT_SPEND_FEE`
+    pub async fn build_script_path_spend(&self, utxo: &TaprootUtxo) -> Result<Transaction> {
+        let destination = self.bitcoin().client.get_new_address().await?;
+        let value = utxo
+            .output
+            .value
+            .checked_sub(TAPROOT_SPEND_FEE)
+            .context("Taproot utxo can't cover the spend fee")?;
+
+        Ok(Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: utxo.outpoint,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: destination.script_pubkey(),
+            }],
+        })
+    }
+
+    /// Spend `utxo` through `leaf`, a single `<pubkey> OP_CHECKSIG` script, signing with `keypair`
+    pub async fn spend_taproot_with_key(
+        &self,
+        utxo: &TaprootUtxo,
+        leaf: &ScriptBuf,
+        keypair: &Keypair,
+    ) -> Result<Txid> {
+        let mut tx = self.build_script_path_spend(utxo).await?;
+        let signature = utxo.script_path_signature(&tx, leaf, keypair)?;
+        utxo.finalize_script_path_spend(&mut tx, leaf, vec![signature])?;
+
+        self.bitcoin()
+            .client
+            .send_raw_transaction(tx)
+            .await
+            .with_context(|| format!("Failed to spend {} through script path", utxo.outpoint))
+    }
+}
@@ -1,7 +1,16 @@
-use std::time::Duration;
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
 
-use crate::containers::{
-    BitcoinContainerConfig, LocalValidatorContainerConfig, TitanContainerConfig,
+use arch_program::pubkey::Pubkey;
+use bitcoin::key::Keypair;
+use bitcoincore_rpc::json::AddressType;
+
+use crate::{
+    containers::{
+        BitcoinContainerConfig, BitcoinRpcAuth, FeeScenario, LocalValidatorContainerConfig,
+        RetryPolicy, TitanContainerConfig, ToxiproxyContainerConfig,
+    },
+    network_mode::{ArchNetworkMode, SignetConfig},
+    RpcCassetteMode, TestService, DEFAULT_SETTLE_TIMEOUT,
 };
 
 pub const MAX_SETUP_TIMEOUT: Duration = Duration::from_secs(120); // 2 minutes
@@ -10,6 +19,173 @@ pub const MAX_TEST_TIMEOUT: Duration = Duration::from_secs(300); // 5 minutes
 pub const DEFAULT_SETUP_TIMEOUT: Duration = Duration::from_secs(15); // 15 seconds for container startup and sync
 pub const DEFAULT_TEST_TIMEOUT: Duration = Duration::from_secs(30); // 30 seconds for test execution
 
+pub const DEFAULT_RPC_POOL_MAX_IDLE_PER_HOST: usize = 32;
+pub const DEFAULT_RPC_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+pub const DEFAULT_RPC_TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+pub const DEFAULT_RPC_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub const DEFAULT_TRANSACTION_WAIT_TIMEOUT: Duration = Duration::from_secs(60);
+pub const DEFAULT_TRANSACTION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub const DEFAULT_SEND_TIMEOUT: Duration = Duration::from_secs(30);
+pub const DEFAULT_ACCOUNT_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// HTTP tuning for the Arch RPC client shared by every `TestContext` of a run
+#[derive(Debug, Clone)]
+pub struct ArchRpcClientConfig {
+    /// Maximum idle (kept-alive) connections to the validator
+    pub pool_max_idle_per_host: usize,
+    /// How long an idle connection is kept in the pool; `None` keeps it forever
+    pub pool_idle_timeout: Option<Duration>,
+    /// TCP keep-alive interval; `None` disables it
+    pub tcp_keepalive: Option<Duration>,
+    /// Timeout for a single RPC request
+    pub request_timeout: Duration,
+}
+
+impl Default for ArchRpcClientConfig {
+    fn default() -> Self {
+        Self {
+            pool_max_idle_per_host: DEFAULT_RPC_POOL_MAX_IDLE_PER_HOST,
+            pool_idle_timeout: Some(DEFAULT_RPC_POOL_IDLE_TIMEOUT),
+            tcp_keepalive: Some(DEFAULT_RPC_TCP_KEEPALIVE),
+            request_timeout: DEFAULT_RPC_REQUEST_TIMEOUT,
+        }
+    }
+}
+
+impl ArchRpcClientConfig {
+    pub fn build_http_client(&self) -> anyhow::Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder()
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .timeout(self.request_timeout);
+
+        if let Some(interval) = self.tcp_keepalive {
+            builder = builder.tcp_keepalive(interval);
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+/// How `TestContext::wait_for_transaction` polls for a transaction to be processed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionWaitConfig {
+    /// Give up (with an error naming the txid and its last status) after this long
+    pub timeout: Duration,
+    pub poll_interval: Duration,
+}
+
+impl Default for TransactionWaitConfig {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_TRANSACTION_WAIT_TIMEOUT,
+            poll_interval: DEFAULT_TRANSACTION_POLL_INTERVAL,
+        }
+    }
+}
+
+/// Default timeouts of individual `TestContext` operations, so a long scenario can give one
+/// kind of operation more time without raising `TestRunnerConfig::test_timeout`. Each
+/// operation also has a `_with` variant taking its own timeout for a single call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TestContextConfig {
+    /// `send_transaction`: until the validator accepts (or rejects) the transaction
+    pub send_timeout: Duration,
+    /// `wait_for_transaction`: until the transaction is processed
+    pub transaction_wait: TransactionWaitConfig,
+    /// Waits on Bitcoin confirmations or indexing: `wait_for_settled_balance`,
+    /// `Commitment::Finalized`, and `send_btc` waiting for Titan
+    pub confirmation_timeout: Duration,
+    /// `read_account_info`
+    pub account_read_timeout: Duration,
+}
+
+impl Default for TestContextConfig {
+    fn default() -> Self {
+        Self {
+            send_timeout: DEFAULT_SEND_TIMEOUT,
+            transaction_wait: TransactionWaitConfig::default(),
+            confirmation_timeout: DEFAULT_SETTLE_TIMEOUT,
+            account_read_timeout: DEFAULT_ACCOUNT_READ_TIMEOUT,
+        }
+    }
+}
+
+/// A program the runner deploys during setup, before the test body runs
+#[derive(Debug, Clone)]
+pub struct PreloadedProgram {
+    pub program_kp: Keypair,
+    pub elf_path: PathBuf,
+}
+
+impl PreloadedProgram {
+    pub fn new(program_kp: Keypair, elf_path: impl Into<PathBuf>) -> Self {
+        Self {
+            program_kp,
+            elf_path: elf_path.into(),
+        }
+    }
+}
+
+/// An account the runner creates during setup, before the test body runs
+#[derive(Debug, Clone)]
+pub struct GenesisAccount {
+    /// The account's keypair, so fixtures can use a known (e.g. seed-derived) pubkey
+    pub keypair: Keypair,
+    pub lamports: u64,
+    pub owner: Pubkey,
+    /// File whose contents become the account data; `None` for a data-less account
+    pub data_path: Option<PathBuf>,
+}
+
+/// An account on a remote Arch network, identified by that network's RPC URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteAccount {
+    pub rpc_url: String,
+    pub pubkey: Pubkey,
+}
+
+/// Programs and accounts to copy from a remote Arch network at startup, see
+/// `TestContext::fork_from`. Only what's listed is copied, including program-owned accounts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForkConfig {
+    pub rpc_url: String,
+    pub programs: Vec<Pubkey>,
+    pub accounts: Vec<Pubkey>,
+}
+
+impl ForkConfig {
+    pub fn new(rpc_url: impl Into<String>) -> Self {
+        Self {
+            rpc_url: rpc_url.into(),
+            programs: Vec::new(),
+            accounts: Vec::new(),
+        }
+    }
+
+    pub fn program(mut self, pubkey: Pubkey) -> Self {
+        self.programs.push(pubkey);
+        self
+    }
+
+    pub fn account(mut self, pubkey: Pubkey) -> Self {
+        self.accounts.push(pubkey);
+        self
+    }
+}
+
+/// Output format for the tracing subscriber installed by the test runner
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TracingFormat {
+    /// Human readable, line oriented output
+    #[default]
+    Text,
+    /// One JSON object per line, including the current span's fields (e.g. `run_id`)
+    Json,
+}
+
 /// Test configuration
 #[derive(Debug, Clone)]
 pub struct TestRunnerConfig {
@@ -22,6 +198,70 @@ pub struct TestRunnerConfig {
 
     pub setup_timeout: Duration,
     pub test_timeout: Duration,
+    /// Readiness retries of bitcoind, the validator and toxiproxy while they start
+    pub retry_policy: RetryPolicy,
+
+    pub network_mode: ArchNetworkMode,
+    pub bitcoin_signet: SignetConfig,
+
+    /// When set, a block is mined every interval for the whole test (see `TestContext::start_auto_mining`)
+    pub auto_mine_interval: Option<Duration>,
+
+    pub tracing_format: TracingFormat,
+
+    /// When set, failed runs capture `docker inspect`/`stats`/log tails of every container
+    /// into `<artifact_dir>/<run_id>/` before teardown
+    pub artifact_dir: Option<PathBuf>,
+
+    pub arch_rpc_client: ArchRpcClientConfig,
+
+    /// Default timeouts of the test's `TestContext` operations; override per context with
+    /// `TestContext::with_config` or per call with the `_with` variants
+    pub context: TestContextConfig,
+
+    /// Deployed (with a faucet-funded authority) before the test body runs
+    pub preloaded_programs: Vec<PreloadedProgram>,
+
+    /// Created (after `preloaded_programs` are deployed) before the test body runs
+    pub genesis_accounts: Vec<GenesisAccount>,
+
+    /// Cloned (see `TestContext::clone_account_from`) before the test body runs
+    pub cloned_accounts: Vec<RemoteAccount>,
+
+    /// When set, the remote programs and accounts are forked into the local validator before
+    /// the test body runs
+    pub fork: Option<ForkConfig>,
+
+    /// When set, Titan's bitcoind connection and the validator's Titan connections run through
+    /// a toxiproxy container, controlled with `TestContext::network_faults`
+    pub network_faults: bool,
+
+    /// When set, every validator websocket event is recorded from setup on (see
+    /// `TestContext::event_recorder`), and failed tests log the event timeline
+    pub record_events: bool,
+
+    /// When set, every Arch RPC call goes through a recording proxy (see
+    /// `TestContext::rpc_capture`), and failed tests log the most recent calls
+    pub capture_rpc: bool,
+
+    /// When set, the run's Arch RPC calls are recorded into, or replayed (without starting
+    /// any containers) from, a cassette file; see `RpcCassetteMode`
+    pub rpc_cassette: Option<RpcCassetteMode>,
+
+    /// When set, an electrs (Esplora HTTP + Electrum) container indexes bitcoind, reached with
+    /// `TestContext::electrs`
+    pub electrs: bool,
+
+    /// When set, a block explorer UI is started in `TestRunner::hold` and `keep_on_failure`
+    /// sessions (never in plain test runs), its URL printed with the other endpoints
+    pub explorer: bool,
+
+    /// When set, a failed run keeps the environment up, printing how to reach it, until ctrl-c.
+    /// For debugging locally: a failing test blocks until then.
+    pub keep_on_failure: bool,
+
+    /// Sidecar containers started after the core stack; see `with_service`
+    pub services: Vec<Arc<dyn TestService>>,
 
     // Port configuration
     pub bitcoin_rpc_port: u16,
@@ -29,6 +269,21 @@ pub struct TestRunnerConfig {
     pub titan_tcp_port: u16,
     pub validator_rpc_port: u16,
     pub validator_websocket_port: u16,
+
+    // Titan / validator pass-through configuration
+    pub titan_extra_env: HashMap<String, String>,
+    pub validator_extra_args: Vec<String>,
+    pub validator_extra_env: HashMap<String, String>,
+    /// See `LocalValidatorContainerConfig::data_dir`
+    pub validator_data_dir: Option<PathBuf>,
+
+    // Bitcoin RPC auth / wallet / node configuration
+    pub bitcoin_rpc_auth: BitcoinRpcAuth,
+    pub bitcoin_descriptor_wallet: bool,
+    pub bitcoin_address_type: Option<AddressType>,
+    pub bitcoin_conf: Option<String>,
+    pub bitcoin_fee_scenario: FeeScenario,
+    pub bitcoin_extra_args: Vec<String>,
 }
 
 impl TestRunnerConfig {
@@ -41,19 +296,53 @@ impl TestRunnerConfig {
             bitcoin_image_name: default_bitcoin_config.image_name,
             bitcoin_image_tag: default_bitcoin_config.image_tag,
             bitcoin_rpc_port: default_bitcoin_config.rpc_port,
+            bitcoin_rpc_auth: default_bitcoin_config.rpc_auth,
+            bitcoin_descriptor_wallet: default_bitcoin_config.descriptor_wallet,
+            bitcoin_address_type: default_bitcoin_config.address_type,
+            bitcoin_conf: default_bitcoin_config.bitcoin_conf,
+            bitcoin_fee_scenario: default_bitcoin_config.fee_scenario,
+            bitcoin_extra_args: default_bitcoin_config.extra_args,
 
             titan_http_port: default_titan_config.http_port,
             titan_image_name: default_titan_config.image_name,
             titan_image_tag: default_titan_config.image_tag,
             titan_tcp_port: default_titan_config.tcp_port,
+            titan_extra_env: default_titan_config.extra_env,
 
             validator_image_name: default_validator_config.image_name,
             validator_image_tag: default_validator_config.image_tag,
             validator_rpc_port: default_validator_config.rpc_port,
             validator_websocket_port: default_validator_config.websocket_port,
+            validator_extra_args: default_validator_config.extra_args,
+            validator_extra_env: default_validator_config.extra_env,
+            validator_data_dir: default_validator_config.data_dir,
 
             setup_timeout: DEFAULT_SETUP_TIMEOUT,
             test_timeout: DEFAULT_TEST_TIMEOUT,
+            retry_policy: RetryPolicy::default(),
+
+            network_mode: ArchNetworkMode::default(),
+            bitcoin_signet: default_bitcoin_config.signet,
+
+            auto_mine_interval: None,
+
+            tracing_format: TracingFormat::default(),
+            artifact_dir: None,
+
+            arch_rpc_client: ArchRpcClientConfig::default(),
+            context: TestContextConfig::default(),
+            preloaded_programs: Vec::new(),
+            genesis_accounts: Vec::new(),
+            cloned_accounts: Vec::new(),
+            fork: None,
+            network_faults: false,
+            record_events: false,
+            capture_rpc: false,
+            rpc_cassette: None,
+            electrs: false,
+            explorer: false,
+            keep_on_failure: false,
+            services: Vec::new(),
         })
     }
 }
@@ -66,10 +355,19 @@ impl From<TestRunnerConfig> for BitcoinContainerConfig {
             image_name: config.bitcoin_image_name,
             image_tag: config.bitcoin_image_tag,
             rpc_password: default_bitcoin_config.rpc_password,
+            rpc_auth: config.bitcoin_rpc_auth,
             rpc_port: config.bitcoin_rpc_port,
             rpc_user: default_bitcoin_config.rpc_user,
             startup_timeout: config.setup_timeout,
+            retry_policy: config.retry_policy,
             tcp_port: default_bitcoin_config.tcp_port,
+            network_mode: config.network_mode,
+            signet: config.bitcoin_signet,
+            descriptor_wallet: config.bitcoin_descriptor_wallet,
+            address_type: config.bitcoin_address_type,
+            fee_scenario: config.bitcoin_fee_scenario,
+            bitcoin_conf: config.bitcoin_conf,
+            extra_args: config.bitcoin_extra_args,
         }
     }
 }
@@ -84,6 +382,8 @@ impl From<TestRunnerConfig> for TitanContainerConfig {
             http_port: config.titan_http_port,
             tcp_port: config.titan_tcp_port,
             startup_timeout: config.setup_timeout,
+            network_mode: config.network_mode,
+            extra_env: config.titan_extra_env,
         }
     }
 }
@@ -98,6 +398,21 @@ impl From<TestRunnerConfig> for LocalValidatorContainerConfig {
             rpc_port: config.validator_rpc_port,
             websocket_port: config.validator_websocket_port,
             startup_timeout: config.setup_timeout,
+            retry_policy: config.retry_policy,
+            network_mode: config.network_mode,
+            extra_args: config.validator_extra_args,
+            extra_env: config.validator_extra_env,
+            data_dir: config.validator_data_dir,
+        }
+    }
+}
+
+impl From<TestRunnerConfig> for ToxiproxyContainerConfig {
+    fn from(config: TestRunnerConfig) -> Self {
+        Self {
+            startup_timeout: config.setup_timeout,
+            retry_policy: config.retry_policy,
+            ..ToxiproxyContainerConfig::default()
         }
     }
 }
@@ -124,4 +439,28 @@ mod tests {
             .local_network_rpc_url()
             .starts_with("http://127.0.0.1:"));
     }
+
+    #[test]
+    fn test_config_retry_policy_applies_to_every_polled_container() {
+        let mut config = TestRunnerConfig::new().expect("Failed to create test config");
+        config.retry_policy = RetryPolicy {
+            initial_interval: Duration::from_millis(100),
+            max_interval: Duration::from_secs(1),
+            max_elapsed: Duration::from_secs(30),
+        };
+
+        let retry_policy = config.retry_policy;
+        assert_eq!(
+            BitcoinContainerConfig::from(config.clone()).retry_policy,
+            retry_policy
+        );
+        assert_eq!(
+            LocalValidatorContainerConfig::from(config.clone()).retry_policy,
+            retry_policy
+        );
+        assert_eq!(
+            ToxiproxyContainerConfig::from(config).retry_policy,
+            retry_policy
+        );
+    }
 }
@@ -1,75 +1,248 @@
-use std::sync::Arc;
-
-use anyhow::Result;
-use arch_program::{
-    hash::Hash, instruction::Instruction, pubkey::Pubkey, sanitized::ArchMessage,
-    system_instruction,
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+
+use anyhow::{Context, Result};
+use arch_program::{hash::Hash, instruction::Instruction, pubkey::Pubkey, sanitized::ArchMessage};
 use arch_sdk::{
     build_and_sign_transaction, generate_new_keypair, ArchRpcClient, AsyncArchRpcClient,
-    ProcessedTransaction, ProgramDeployer, RuntimeTransaction, Status,
+    ProcessedTransaction, RuntimeTransaction, Status,
+};
+use bitcoin::{key::Keypair, Address, Amount, BlockHash, Network, OutPoint};
+use bitcoincore_rpc::RpcApi;
+use titan_client::{TitanApi, TitanClient};
+use tokio::task::{spawn_blocking, JoinHandle};
+use tracing::Instrument;
+
+use crate::{
+    containers::{
+        AsyncBitcoinClient, BitcoinContainerConfig, ElectrsContainer,
+        LocalValidatorContainerConfig, TitanContainerConfig,
+    },
+    error::{ArchTestingError, ArchTestingResult},
+    AccountLamports, ArchRpcClientConfig, EventRecorder, NetworkFaults, RpcCapture, ServiceHandle,
+    TestContextConfig, TestService, TransactionWaitConfig, ValidatorSubscription,
 };
-use bitcoin::{key::Keypair, Address, Network};
-use tokio::task::spawn_blocking;
 
+pub const DEFAULT_SETTLE_TIMEOUT: Duration = Duration::from_secs(60);
+pub const DEFAULT_SETTLE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The Bitcoin side of the environment: an async client for the test wallet plus the
+/// configuration bitcoind was started with (ports, credentials, network)
+#[derive(Clone)]
+pub struct BitcoinHandle {
+    pub client: AsyncBitcoinClient,
+    pub config: BitcoinContainerConfig,
+}
+
+/// The Titan indexer: its client plus the configuration it was started with
+#[derive(Clone)]
+pub struct TitanHandle {
+    pub client: Arc<TitanClient>,
+    pub config: TitanContainerConfig,
+}
+
+impl TitanHandle {
+    pub fn http_url(&self) -> String {
+        self.config.local_network_http_url()
+    }
+
+    /// Address of Titan's TCP subscription endpoint
+    pub fn tcp_address(&self) -> String {
+        self.config.local_network_tcp_address()
+    }
+}
+
+/// The local validator: the configuration it was started with, plus an HTTP client for RPC
+/// methods the SDK clients don't cover and a websocket connection for its events
+#[derive(Clone)]
+pub struct ValidatorHandle {
+    pub config: LocalValidatorContainerConfig,
+    pub http_client: reqwest::Client,
+    /// What `http_client` was built from, to rebuild clients after a restart
+    pub rpc_client_config: ArchRpcClientConfig,
+    /// Websocket connection subscribed to every topic, made on the first `websocket` call
+    pub(crate) websocket: Arc<tokio::sync::OnceCell<ValidatorSubscription>>,
+    /// Set when the run captures RPC calls; `rpc_url` then points at the capture proxy
+    pub rpc_capture: Option<RpcCapture>,
+}
+
+impl ValidatorHandle {
+    /// Where RPC clients should connect: the validator, or the capture proxy in front of it
+    pub fn rpc_url(&self) -> String {
+        match &self.rpc_capture {
+            Some(rpc_capture) => rpc_capture.url().to_string(),
+            None => self.config.local_network_rpc_url(),
+        }
+    }
+
+    pub fn websocket_url(&self) -> String {
+        self.config.local_network_websocket_url()
+    }
+
+    /// The shared websocket connection, subscribed to every topic (connected on first use);
+    /// `resubscribe` for a receiver. It closes if the validator restarts; the context's
+    /// `restart_validator` connects a new one, on its next use.
+    pub async fn websocket(&self) -> Result<&ValidatorSubscription> {
+        self.websocket
+            .get_or_try_init(|| async {
+                ValidatorSubscription::connect_all(&self.websocket_url())
+                    .await
+                    .context("Failed to connect to the validator websocket")
+            })
+            .await
+    }
+
+    /// Raw JSON-RPC call against the validator, returning the `result` member
+    pub async fn call(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> ArchTestingResult<serde_json::Value> {
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": "arch-testing",
+            "method": method,
+            "params": params,
+        });
+
+        let mut response: serde_json::Value = self
+            .http_client
+            .post(self.rpc_url())
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| ArchTestingError::rpc_unavailable("validator", e))?
+            .json()
+            .await
+            .map_err(|e| ArchTestingError::rpc_unavailable("validator", e))?;
+
+        if let Some(error) = response.get("error").filter(|error| !error.is_null()) {
+            return Err(
+                anyhow::anyhow!("Validator rpc method {} failed: {}", method, error).into(),
+            );
+        }
+
+        Ok(response["result"].take())
+    }
+}
+
+/// Cheap to clone: clones share the same RPC clients (and their connection pools)
+#[derive(Clone)]
 pub struct TestContext {
-    pub arch_async_rpc_client: AsyncArchRpcClient,
+    pub arch_async_rpc_client: Arc<AsyncArchRpcClient>,
     pub network: Network,
 
     // Please _do not pub_ these fields, because they can't be used well in an async context.
     // we'll keep all the spawn_blocking calls in this file until we have proper async clients.
     // (aka, hide the ugly / keep the ugly in one place)
-    program_deployer: Arc<ProgramDeployer>,
     arch_rpc_client: Arc<ArchRpcClient>,
+    bitcoin: BitcoinHandle,
+    titan: TitanHandle,
+    validator: ValidatorHandle,
+
+    // default timeouts of sends, transaction and confirmation waits, and account reads
+    config: TestContextConfig,
+
+    // bitcoind has no getter for its mocktime, so remember what we last set
+    bitcoin_mocktime: Arc<Mutex<Option<u64>>>,
+
+    auto_miner: Arc<Mutex<Option<JoinHandle<()>>>>,
+
+    // faucet-funded keypair that exact-amount transfers are paid from, created on first use
+    funder: Arc<tokio::sync::Mutex<Option<Keypair>>>,
+
+    // named keypairs handed out by `actor`, and whether each has been funded yet
+    actors: Arc<tokio::sync::Mutex<HashMap<String, (Keypair, bool)>>>,
+
+    // local pubkey of each account cloned from a remote network, keyed by its remote pubkey
+    cloned_accounts: Arc<tokio::sync::Mutex<HashMap<Pubkey, Pubkey>>>,
+
+    // set when the run proxies service links through toxiproxy
+    network_faults: Option<NetworkFaults>,
+
+    // set when the run records validator events
+    event_recorder: Option<EventRecorder>,
+
+    // sidecar containers of the run, see `TestRunnerConfig::with_service`
+    services: Arc<Vec<ServiceHandle>>,
 }
 
 impl TestContext {
     pub fn new(
         arch_async_rpc_client: AsyncArchRpcClient,
         arch_rpc_client: ArchRpcClient,
-        program_deployer: ProgramDeployer,
+        bitcoin: BitcoinHandle,
+        titan: TitanHandle,
+        validator: ValidatorHandle,
+        network: Network,
     ) -> Self {
         Self {
-            arch_async_rpc_client,
+            arch_async_rpc_client: Arc::new(arch_async_rpc_client),
             arch_rpc_client: Arc::new(arch_rpc_client),
-            network: Network::Regtest,
-            program_deployer: Arc::new(program_deployer),
+            bitcoin,
+            titan,
+            validator,
+            config: TestContextConfig::default(),
+            bitcoin_mocktime: Arc::new(Mutex::new(None)),
+            auto_miner: Arc::new(Mutex::new(None)),
+            funder: Arc::new(tokio::sync::Mutex::new(None)),
+            actors: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            cloned_accounts: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            network_faults: None,
+            event_recorder: None,
+            services: Arc::new(Vec::new()),
+            network,
         }
     }
 
-    pub async fn fund_keypair_with_faucet(&self, keypair: &Keypair) -> anyhow::Result<()> {
-        let client = self.arch_rpc_client.clone();
-        let keypair = keypair.clone();
+    /// This context (and clones made from it afterwards) with different default operation
+    /// timeouts
+    pub fn with_config(mut self, config: TestContextConfig) -> Self {
+        self.config = config;
+        self
+    }
 
-        spawn_blocking(move || client.create_and_fund_account_with_faucet(&keypair)).await??;
+    /// This context (and clones made from it afterwards) with different default
+    /// `wait_for_transaction` polling
+    pub fn with_transaction_wait(mut self, transaction_wait: TransactionWaitConfig) -> Self {
+        self.config.transaction_wait = transaction_wait;
+        self
+    }
 
-        Ok(())
+    /// Default operation timeouts (see `with_config`)
+    pub fn config(&self) -> &TestContextConfig {
+        &self.config
     }
 
-    pub async fn deploy_program(
-        &self,
-        program_kp: Keypair,
-        authority_kp: Keypair,
-        elf_bytes: &[u8],
-    ) -> anyhow::Result<()> {
-        let program_pubkey = Pubkey::from_slice(&program_kp.x_only_public_key().0.serialize());
-        let program_deployer = self.program_deployer.clone();
-        let elf = elf_bytes.to_vec();
-
-        // write ELF to a temp file (no extra deps)
-        let tmp_dir = std::env::temp_dir();
-        let elf_path = tmp_dir.join(format!("deploy-{}.elf", hex::encode(program_pubkey.0)));
-        std::fs::write(&elf_path, &elf)?;
-
-        let elf_path_str = elf_path.to_string_lossy().to_string();
-        let program_name = hex::encode(program_pubkey.0);
-
-        tokio::task::spawn_blocking(move || {
-            program_deployer
-                .try_deploy_program(program_name, program_kp, authority_kp, &elf_path_str)
-                .map_err(|e| anyhow::anyhow!("Program deployment failed: {}", e))
-        })
-        .await??;
+    /// This context with fault injection through `network_faults`
+    pub fn with_network_faults(mut self, network_faults: NetworkFaults) -> Self {
+        self.network_faults = Some(network_faults);
+        self
+    }
+
+    /// This context with the run's validator events available through `event_recorder`
+    pub fn with_event_recorder(mut self, event_recorder: EventRecorder) -> Self {
+        self.event_recorder = Some(event_recorder);
+        self
+    }
+
+    /// This context with the run's sidecar services available through `service`
+    pub fn with_services(mut self, services: Vec<ServiceHandle>) -> Self {
+        self.services = Arc::new(services);
+        self
+    }
+
+    pub async fn fund_keypair_with_faucet(&self, keypair: &Keypair) -> ArchTestingResult<()> {
+        let client = self.arch_rpc_client.clone();
+        let keypair = keypair.clone();
+
+        spawn_blocking(move || client.create_and_fund_account_with_faucet(&keypair))
+            .await
+            .context("Failed to spawn blocking task")?
+            .map_err(|e| ArchTestingError::rpc_unavailable("faucet", e))?;
 
         Ok(())
     }
@@ -78,67 +251,46 @@ impl TestContext {
         generate_new_keypair(self.network)
     }
 
-    pub async fn generate_funded_keypair(&self) -> Result<(Keypair, Pubkey, Address)> {
+    pub async fn generate_funded_keypair(&self) -> ArchTestingResult<(Keypair, Pubkey, Address)> {
         let (keypair, pubkey, address) = self.generate_new_keypair();
         self.fund_keypair_with_faucet(&keypair).await?;
         Ok((keypair, pubkey, address))
     }
 
-    /// Create an account with specific lamports (with UTXO anchoring)
+    /// Create an account with specific lamports (with UTXO anchoring); pass
+    /// `AccountLamports::RentExempt` for the rent-exempt minimum
     pub async fn create_account_with_lamports(
         &self,
         authority_kp: Keypair,
-        initial_lamports: u64,
-    ) -> Result<(Keypair, Pubkey)> {
-        let (account_keypair, account_pubkey, _) = self.generate_new_keypair();
-        let authority_pubkey = Pubkey::from_slice(&authority_kp.x_only_public_key().0.serialize());
-
-        // Get UTXO for account creation (in the old tests this was done with send_utxo)
-        // For now, we'll create the account without UTXO anchoring since send_utxo is complex
-        let recent_blockhash = self.get_recent_blockhash().await?;
-
-        let message = ArchMessage::new(
-            &[system_instruction::create_account(
-                &authority_pubkey,
-                &account_pubkey,
-                initial_lamports,
-                0,
-                &Pubkey::system_program(),
-            )],
-            Some(authority_pubkey),
-            recent_blockhash.parse()?,
-        );
-
-        let create_account_tx = build_and_sign_transaction(
-            message,
-            vec![authority_kp, account_keypair.clone()],
-            self.network,
-        )?;
+        initial_lamports: impl Into<AccountLamports>,
+    ) -> ArchTestingResult<(Keypair, Pubkey)> {
+        let initial_lamports = initial_lamports.into().resolve(0);
+        let (account_keypair, account_pubkey, _) = self
+            .create_anchored_account(authority_kp, initial_lamports, 0, Pubkey::system_program())
+            .await?;
 
-        let txid = self.send_transaction(create_account_tx).await?;
-        let processed_tx = self.wait_for_transaction(&txid).await?;
-
-        match processed_tx.status {
-            Status::Processed => Ok((account_keypair, account_pubkey)),
-            Status::Failed(e) => Err(anyhow::anyhow!("Account creation failed: {}", e)),
-            Status::Queued => Err(anyhow::anyhow!("Account creation transaction still queued")),
-        }
+        Ok((account_keypair, account_pubkey))
     }
 
-    pub async fn get_best_blockhash(&self) -> Result<Hash> {
-        let blockhash = self.arch_async_rpc_client.get_best_block_hash().await?;
-        Ok(blockhash.parse()?)
+    pub async fn get_best_blockhash(&self) -> ArchTestingResult<Hash> {
+        let blockhash = self.get_recent_blockhash().await?;
+        Ok(blockhash
+            .parse()
+            .with_context(|| format!("Invalid block hash {}", blockhash))?)
     }
 
-    pub async fn get_recent_blockhash(&self) -> Result<String> {
-        Ok(self.arch_async_rpc_client.get_best_block_hash().await?)
+    pub async fn get_recent_blockhash(&self) -> ArchTestingResult<String> {
+        self.arch_async_rpc_client
+            .get_best_block_hash()
+            .await
+            .map_err(|e| ArchTestingError::rpc_unavailable("validator", e))
     }
 
     pub async fn build_message(
         &self,
         instructions: &[Instruction],
         payer: Option<Pubkey>,
-    ) -> Result<ArchMessage> {
+    ) -> ArchTestingResult<ArchMessage> {
         Ok(ArchMessage::new(
             instructions,
             payer,
@@ -150,25 +302,467 @@ impl TestContext {
         &self,
         message: ArchMessage,
         signers: Vec<Keypair>,
-    ) -> Result<RuntimeTransaction> {
-        Ok(build_and_sign_transaction(message, signers, self.network)?)
+    ) -> ArchTestingResult<RuntimeTransaction> {
+        Ok(build_and_sign_transaction(message, signers, self.network)
+            .context("Failed to sign transaction")?)
+    }
+
+    /// Submit `transaction`, giving up after `TestContextConfig::send_timeout`
+    pub async fn send_transaction(
+        &self,
+        transaction: RuntimeTransaction,
+    ) -> ArchTestingResult<String> {
+        self.send_transaction_with(transaction, self.config.send_timeout)
+            .await
+    }
+
+    /// `send_transaction` with a timeout specific to this call
+    pub async fn send_transaction_with(
+        &self,
+        transaction: RuntimeTransaction,
+        timeout: Duration,
+    ) -> ArchTestingResult<String> {
+        tokio::time::timeout(
+            timeout,
+            self.arch_async_rpc_client.send_transaction(transaction),
+        )
+        .await
+        .map_err(|_| {
+            ArchTestingError::rpc_unavailable(
+                "validator",
+                anyhow::anyhow!("send_transaction timed out after {:?}", timeout),
+            )
+        })?
+        .map_err(|e| {
+            let reason = e.to_string();
+            if is_transport_failure(&reason) {
+                ArchTestingError::rpc_unavailable("validator", reason)
+            } else {
+                ArchTestingError::TransactionFailed { txid: None, reason }
+            }
+        })
+    }
+
+    /// Wait for `txid` to be processed (or fail), polling as configured by
+    /// `TestContextConfig::transaction_wait`
+    pub async fn wait_for_transaction(
+        &self,
+        txid: &str,
+    ) -> ArchTestingResult<ProcessedTransaction> {
+        self.wait_for_transaction_with(txid, self.config.transaction_wait)
+            .await
+    }
+
+    /// `wait_for_transaction` with polling specific to this call
+    pub async fn wait_for_transaction_with(
+        &self,
+        txid: &str,
+        wait: TransactionWaitConfig,
+    ) -> ArchTestingResult<ProcessedTransaction> {
+        let deadline = Instant::now() + wait.timeout;
+        let mut last_status = String::from("not found");
+
+        while Instant::now() < deadline {
+            match self
+                .arch_async_rpc_client
+                .get_processed_transaction(txid)
+                .await
+            {
+                Ok(Some(processed_tx)) if !matches!(processed_tx.status, Status::Queued) => {
+                    return Ok(processed_tx)
+                }
+                Ok(Some(processed_tx)) => last_status = format!("{:?}", processed_tx.status),
+                Ok(None) => last_status = String::from("not found"),
+                Err(e) => last_status = format!("error: {}", e),
+            }
+
+            tokio::time::sleep(wait.poll_interval).await;
+        }
+
+        Err(ArchTestingError::TransactionFailed {
+            txid: Some(txid.to_string()),
+            reason: format!(
+                "timed out after {:?} waiting for it to be processed (last status: {})",
+                wait.timeout, last_status
+            ),
+        })
+    }
+
+    /// Read `pubkey`'s account, giving up after `TestContextConfig::account_read_timeout`
+    pub async fn read_account_info(
+        &self,
+        pubkey: Pubkey,
+    ) -> ArchTestingResult<arch_sdk::AccountInfo> {
+        self.read_account_info_with(pubkey, self.config.account_read_timeout)
+            .await
+    }
+
+    /// `read_account_info` with a timeout specific to this call
+    pub async fn read_account_info_with(
+        &self,
+        pubkey: Pubkey,
+        timeout: Duration,
+    ) -> ArchTestingResult<arch_sdk::AccountInfo> {
+        let account = tokio::time::timeout(
+            timeout,
+            self.arch_async_rpc_client.read_account_info(pubkey),
+        )
+        .await
+        .map_err(|_| {
+            ArchTestingError::rpc_unavailable(
+                "validator",
+                anyhow::anyhow!("Reading account {} timed out after {:?}", pubkey, timeout),
+            )
+        })?
+        .with_context(|| format!("Failed to read account {}", pubkey))?;
+
+        Ok(account)
+    }
+
+    /// Wait until `pubkey` holds `expected` lamports *and* the Bitcoin transaction anchoring the
+    /// account has at least `confirmations` confirmations (i.e. true settlement, not just Arch state).
+    ///
+    /// This doesn't mine blocks; something else has to produce the confirmations. Gives up after
+    /// `TestContextConfig::confirmation_timeout`.
+    pub async fn wait_for_settled_balance(
+        &self,
+        pubkey: Pubkey,
+        expected: u64,
+        confirmations: u32,
+    ) -> ArchTestingResult<arch_sdk::AccountInfo> {
+        self.wait_for_settled_balance_with(
+            pubkey,
+            expected,
+            confirmations,
+            self.config.confirmation_timeout,
+        )
+        .await
     }
 
-    pub async fn send_transaction(&self, transaction: RuntimeTransaction) -> Result<String> {
-        Ok(self
-            .arch_async_rpc_client
-            .send_transaction(transaction)
-            .await?)
+    /// `wait_for_settled_balance` with a timeout specific to this call
+    pub async fn wait_for_settled_balance_with(
+        &self,
+        pubkey: Pubkey,
+        expected: u64,
+        confirmations: u32,
+        timeout: Duration,
+    ) -> ArchTestingResult<arch_sdk::AccountInfo> {
+        let deadline = Instant::now() + timeout;
+        let mut last_observed = String::from("account not found");
+
+        while Instant::now() < deadline {
+            if let Ok(account) = self.read_account_info(pubkey).await {
+                if account.lamports != expected {
+                    last_observed = format!("{} lamports", account.lamports);
+                } else {
+                    let anchor: OutPoint = account.utxo.parse().map_err(|e| {
+                        anyhow::anyhow!("Account {} has no valid anchor utxo: {}", pubkey, e)
+                    })?;
+
+                    let anchor_confirmations = self.bitcoin_confirmations(anchor.txid).await?;
+                    if anchor_confirmations >= confirmations {
+                        return Ok(account);
+                    }
+
+                    last_observed = format!(
+                        "{} lamports, anchor {} has {} confirmations",
+                        account.lamports, anchor, anchor_confirmations
+                    );
+                }
+            }
+
+            tokio::time::sleep(DEFAULT_SETTLE_POLL_INTERVAL).await;
+        }
+
+        Err(anyhow::anyhow!(
+            "Timed out waiting for {} to settle at {} lamports with {} confirmations (last observed: {})",
+            pubkey,
+            expected,
+            confirmations,
+            last_observed
+        )
+        .into())
     }
 
-    pub async fn wait_for_transaction(&self, txid: &str) -> Result<ProcessedTransaction> {
-        Ok(self
-            .arch_async_rpc_client
-            .wait_for_processed_transaction(txid)
-            .await?)
+    /// Confirmations of a Bitcoin transaction (0 while it's in the mempool)
+    pub(crate) async fn bitcoin_confirmations(&self, txid: bitcoin::Txid) -> Result<u32> {
+        let tx_info = self
+            .with_bitcoin_client(move |client| client.get_raw_transaction_info(&txid, None))
+            .await?;
+
+        Ok(tx_info.confirmations.unwrap_or_default())
     }
 
-    pub async fn read_account_info(&self, pubkey: Pubkey) -> Result<arch_sdk::AccountInfo> {
-        Ok(self.arch_async_rpc_client.read_account_info(pubkey).await?)
+    /// Pin bitcoind's clock to `timestamp` (unix seconds) via `setmocktime`; `0` restores the real clock
+    pub async fn bitcoin_set_mocktime(&self, timestamp: u64) -> ArchTestingResult<()> {
+        self.with_bitcoin_client(move |client| {
+            client.call::<serde_json::Value>("setmocktime", &[timestamp.into()])
+        })
+        .await?;
+
+        *self.bitcoin_mocktime.lock().unwrap() = (timestamp != 0).then_some(timestamp);
+
+        Ok(())
     }
+
+    /// Move bitcoind's clock forward by `secs`, starting from the current mocktime
+    /// (or the real clock, if mocktime isn't set). Returns the new mocktime.
+    pub async fn bitcoin_advance_time(&self, secs: u64) -> ArchTestingResult<u64> {
+        let current = match *self.bitcoin_mocktime.lock().unwrap() {
+            Some(mocktime) => mocktime,
+            None => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .context("System clock is before the unix epoch")?
+                .as_secs(),
+        };
+
+        let mocktime = current + secs;
+        self.bitcoin_set_mocktime(mocktime).await?;
+
+        Ok(mocktime)
+    }
+
+    /// Mine `count` blocks to a fresh test wallet address
+    pub async fn bitcoin_mine_blocks(&self, count: u64) -> ArchTestingResult<Vec<BlockHash>> {
+        Ok(self.bitcoin.client.generate(count).await?)
+    }
+
+    /// Send `amount` to `address` from the test wallet, mine `confirmations` blocks, and wait for
+    /// Titan to index the new output. `confirmations == 0` leaves it in the mempool.
+    pub async fn send_btc(
+        &self,
+        address: &Address,
+        amount: Amount,
+        confirmations: u64,
+    ) -> ArchTestingResult<OutPoint> {
+        let txid = self
+            .bitcoin
+            .client
+            .send_to_address(address, amount)
+            .await
+            .with_context(|| format!("Failed to send {} to {}", amount, address))?;
+
+        let tx = self.bitcoin.client.get_raw_transaction(txid).await?;
+        let vout = tx
+            .output
+            .iter()
+            .position(|output| output.script_pubkey == address.script_pubkey())
+            .with_context(|| format!("Transaction {} doesn't pay {}", txid, address))?;
+        let outpoint = OutPoint::new(txid, vout as u32);
+
+        if confirmations > 0 {
+            self.bitcoin_mine_blocks(confirmations).await?;
+        }
+
+        self.wait_for_titan_output(address, outpoint).await?;
+
+        Ok(outpoint)
+    }
+
+    /// Wait until Titan lists `outpoint` among the outputs of `address`
+    pub(crate) async fn wait_for_titan_output(
+        &self,
+        address: &Address,
+        outpoint: OutPoint,
+    ) -> Result<()> {
+        let deadline = Instant::now() + self.config.confirmation_timeout;
+
+        while Instant::now() < deadline {
+            if let Ok(address_data) = self.titan.client.get_address(&address.to_string()).await {
+                let indexed = address_data
+                    .outputs
+                    .iter()
+                    .any(|output| output.txid == outpoint.txid && output.vout == outpoint.vout);
+                if indexed {
+                    return Ok(());
+                }
+            }
+
+            tokio::time::sleep(DEFAULT_SETTLE_POLL_INTERVAL).await;
+        }
+
+        Err(anyhow::anyhow!(
+            "Timed out waiting for titan to index {} for {}",
+            outpoint,
+            address
+        ))
+    }
+
+    /// Generate a block every `interval` in the background, to an address managed by the test
+    /// wallet, until `stop_auto_mining` is called (or the test ends). Restarts if already running.
+    pub async fn start_auto_mining(&self, interval: Duration) -> ArchTestingResult<()> {
+        let client = self.bitcoin.client.clone();
+        let address = client.get_new_address().await?;
+
+        let auto_miner = tokio::spawn(
+            async move {
+                let mut ticker = tokio::time::interval(interval);
+                ticker.tick().await; // the first tick completes immediately
+
+                loop {
+                    ticker.tick().await;
+
+                    match client.generate_to_address(1, &address).await {
+                        Ok(block_hashes) => tracing::trace!("Auto-mined {:?}", block_hashes),
+                        Err(e) => tracing::warn!("Auto-mining failed: {}", e),
+                    }
+                }
+            }
+            .instrument(tracing::Span::current()),
+        );
+
+        if let Some(previous) = self.auto_miner.lock().unwrap().replace(auto_miner) {
+            previous.abort();
+        }
+
+        Ok(())
+    }
+
+    pub fn stop_auto_mining(&self) {
+        if let Some(auto_miner) = self.auto_miner.lock().unwrap().take() {
+            auto_miner.abort();
+        }
+    }
+
+    /// Height and hash of the tip Titan has indexed
+    pub(crate) async fn titan_tip(&self) -> Result<(u64, String)> {
+        let tip = self.titan.client.get_tip().await?;
+        Ok((tip.height, tip.hash.to_string()))
+    }
+
+    /// Keypair that `fund_lamports` and `top_up_to` transfer from
+    pub(crate) fn funder(&self) -> &tokio::sync::Mutex<Option<Keypair>> {
+        &self.funder
+    }
+
+    /// Named keypairs handed out by `actor` / `funded_actor`
+    pub(crate) fn actors(&self) -> &tokio::sync::Mutex<HashMap<String, (Keypair, bool)>> {
+        &self.actors
+    }
+
+    pub(crate) fn cloned_accounts(&self) -> &tokio::sync::Mutex<HashMap<Pubkey, Pubkey>> {
+        &self.cloned_accounts
+    }
+
+    /// Async client and configuration of the bitcoind node, e.g. to mine blocks or send BTC
+    pub fn bitcoin(&self) -> &BitcoinHandle {
+        &self.bitcoin
+    }
+
+    /// Titan client and endpoints, e.g. to assert on indexer state (UTXOs, tip height, runes)
+    pub fn titan(&self) -> &TitanHandle {
+        &self.titan
+    }
+
+    /// Validator configuration and raw RPC access
+    pub fn validator(&self) -> &ValidatorHandle {
+        &self.validator
+    }
+
+    /// Latency, bandwidth limits and partitions on the links between services. Only
+    /// available when the run was configured with `TestRunnerConfig::network_faults`.
+    pub fn network_faults(&self) -> ArchTestingResult<&NetworkFaults> {
+        self.network_faults.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("Network faults aren't enabled (set TestRunnerConfig::network_faults)")
+                .into()
+        })
+    }
+
+    /// The sidecar services of the run (see `TestRunnerConfig::with_service`)
+    pub fn services(&self) -> &[ServiceHandle] {
+        &self.services
+    }
+
+    /// The running service registered under `name` with `TestRunnerConfig::with_service`
+    pub fn service_handle(&self, name: &str) -> ArchTestingResult<&ServiceHandle> {
+        self.services
+            .iter()
+            .find(|service| service.name == name)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No service named {} (see TestRunnerConfig::with_service)",
+                    name
+                )
+                .into()
+            })
+    }
+
+    /// The first running service of type `S`, as registered with
+    /// `TestRunnerConfig::with_service`, e.g. `ctx.service::<PostgresContainer>()?`
+    pub fn service<S: TestService>(&self) -> ArchTestingResult<&S> {
+        self.services
+            .iter()
+            // deref first: the `Arc` itself is `Any` too
+            .find_map(|handle| (*handle.service).as_any().downcast_ref::<S>())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No {} service (see TestRunnerConfig::with_service)",
+                    std::any::type_name::<S>()
+                )
+                .into()
+            })
+    }
+
+    /// The electrs indexer, e.g. its `esplora_url`. Only available when the run was configured
+    /// with `TestRunnerConfig::electrs`.
+    pub fn electrs(&self) -> ArchTestingResult<&ElectrsContainer> {
+        self.service::<ElectrsContainer>().map_err(|_| {
+            anyhow::anyhow!("Electrs isn't enabled (set TestRunnerConfig::electrs)").into()
+        })
+    }
+
+    /// Every validator event of the run. Only available when the run was configured with
+    /// `TestRunnerConfig::record_events`.
+    pub fn event_recorder(&self) -> ArchTestingResult<&EventRecorder> {
+        self.event_recorder.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("Event recording isn't enabled (set TestRunnerConfig::record_events)")
+                .into()
+        })
+    }
+
+    pub(crate) fn set_validator_http_client(&mut self, http_client: reqwest::Client) {
+        self.validator.http_client = http_client;
+    }
+
+    pub(crate) fn set_arch_rpc_client(&mut self, arch_rpc_client: ArchRpcClient) {
+        self.arch_rpc_client = Arc::new(arch_rpc_client);
+    }
+
+    /// Drop this context's websocket connection; `ValidatorHandle::websocket` connects anew
+    pub(crate) fn reset_validator_websocket(&mut self) {
+        self.validator.websocket = Arc::new(tokio::sync::OnceCell::new());
+    }
+
+    /// Async client for the bitcoind test wallet
+    pub fn bitcoin_client(&self) -> &AsyncBitcoinClient {
+        &self.bitcoin.client
+    }
+
+    /// Run a blocking call against the bitcoind test wallet client on the blocking pool.
+    /// Helper modules go through here rather than holding the client themselves.
+    pub(crate) async fn with_bitcoin_client<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&bitcoincore_rpc::Client) -> bitcoincore_rpc::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.bitcoin.client.with_client(f).await
+    }
+}
+
+/// Whether a client error means the request never got an answer (connection refused or reset,
+/// timeouts, ...), as opposed to the validator answering with a rejection
+fn is_transport_failure(message: &str) -> bool {
+    const TRANSPORT_FAILURES: [&str; 6] = [
+        "error sending request",
+        "connection refused",
+        "connection reset",
+        "connection closed",
+        "timed out",
+        "dns error",
+    ];
+
+    let message = message.to_lowercase();
+    TRANSPORT_FAILURES
+        .iter()
+        .any(|failure| message.contains(failure))
 }
@@ -1,24 +1,40 @@
-use std::future::Future;
+use std::{
+    future::Future,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use anyhow::{anyhow, Context, Result};
-use arch_sdk::{ArchRpcClient, AsyncArchRpcClient, ProgramDeployer};
-use bitcoin::Network;
-use tokio::time::timeout;
+use anyhow::{anyhow, ensure, Context, Result};
+use arch_sdk::{ArchRpcClient, AsyncArchRpcClient};
+use titan_client::TitanClient;
+use tokio::{sync::OnceCell, time::timeout};
+use tracing::Instrument;
 
 use crate::{
+    artifacts::capture_docker_state,
     containers::{
-        BitcoinContainer, BitcoinContainerConfig, LocalValidatorContainer,
-        LocalValidatorContainerConfig, TitanContainer, TitanContainerConfig,
+        bitcoin_container::DEFAULT_WALLET_NAME, AsyncBitcoinClient, BitcoinContainer,
+        BitcoinContainerConfig, ElectrsContainer, ExplorerContainer, LocalValidatorContainer,
+        LocalValidatorContainerConfig, TitanContainer, TitanContainerConfig, ToxiproxyContainer,
+        ToxiproxyContainerConfig,
     },
+    error::{ArchTestingError, ArchTestingResult},
     init_tracing,
+    rpc_cassette::finish_rpc_cassette,
     test_config::{TestRunnerConfig, MAX_SETUP_TIMEOUT, MAX_TEST_TIMEOUT},
-    test_context::TestContext,
+    test_context::{BitcoinHandle, TestContext, TitanHandle, ValidatorHandle},
+    test_service::{check_service_names, start_service, ServiceContainer},
+    EventRecorder, NetworkFaults, RpcCapture, RpcCassette, RpcCassetteMode, ServiceEndpoints,
+    TestService, ValidatorSubscription, DEFAULT_RPC_CAPTURE_DUMP_LEN,
 };
 
 pub struct TestRunner {
     bitcoin_container: Option<BitcoinContainer>,
     titan_container: Option<TitanContainer>,
     local_validator_conainer: Option<LocalValidatorContainer>,
+    toxiproxy_container: Option<ToxiproxyContainer>,
+    services: Vec<ServiceContainer>,
 }
 
 impl TestRunner {
@@ -36,84 +52,258 @@ impl TestRunner {
         F: FnOnce(TestContext) -> Fut,
         Fut: Future<Output = Result<()>>,
     {
-        init_tracing();
+        if let Err(e) = Self::try_run_with_config(config, test_fn).await {
+            panic!("Test run failed: {:#}", anyhow::Error::from(e));
+        }
+    }
+
+    /// `run_with_config`, returning the failure instead of panicking, so it can be matched on
+    /// (e.g. to tell a slow docker host's `SetupTimeout` from a test failure). Errors returned
+    /// by `test_fn` come back as `ArchTestingError::Other`, or as themselves if they are
+    /// `ArchTestingError`s.
+    pub async fn try_run_with_config<F, Fut>(
+        config: TestRunnerConfig,
+        test_fn: F,
+    ) -> ArchTestingResult<()>
+    where
+        F: FnOnce(TestContext) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        init_tracing(config.tracing_format);
+
+        let run_id = new_run_id();
+        let run_span = tracing::info_span!("test_run", run_id = %run_id);
+
+        async {
+            let mut ctx = Self::new();
+
+            // replayed runs answer RPC calls from a cassette, without any containers
+            let setup_result = match &config.rpc_cassette {
+                Some(mode) if mode.is_replay() => Ok(()),
+                _ => ctx.setup_with_timeout(&config).await,
+            };
+
+            let final_result = match setup_result {
+                Ok(_) => ctx.test_with_timeout(&config, test_fn).await,
+                Err(setup_err) => Err(setup_err),
+            };
+
+            if final_result.is_err() {
+                if let Some(artifact_dir) = &config.artifact_dir {
+                    ctx.capture_failure_state(artifact_dir.join(&run_id)).await;
+                }
+
+                if config.keep_on_failure && ctx.bitcoin_container.is_some() {
+                    tracing::error!("Test failed, keeping the environment up for debugging");
+                    if let Err(e) = ctx.wait_for_ctrl_c(&config).await {
+                        tracing::warn!("{:#}", e);
+                    }
+                }
+            }
+
+            // IMPORTANT: Always teardown, regardless of {setup, test} success or failure
+            let teardown_result = ctx.teardown().await;
+
+            match (final_result, teardown_result) {
+                (Err(e), Err(teardown_err)) => {
+                    tracing::warn!(
+                        "Teardown also failed: {:#}",
+                        anyhow::Error::from(teardown_err)
+                    );
+                    Err(e)
+                }
+                (final_result, Ok(())) => final_result,
+                (Ok(()), teardown_result) => teardown_result,
+            }
+        }
+        .instrument(run_span)
+        .await
+    }
+
+    /// Start the environment without running a test, e.g. to keep it up for manual use (see the
+    /// `arch-test-env` binary). Stop it with `shutdown`; dropping the runner removes the
+    /// containers too.
+    pub async fn start(config: &TestRunnerConfig) -> ArchTestingResult<Self> {
+        init_tracing(config.tracing_format);
+
+        let mut runner = Self::new();
+        if let Err(e) = runner.setup_with_timeout(config).await {
+            if let Err(teardown_err) = runner.teardown().await {
+                tracing::warn!(
+                    "Teardown also failed: {:#}",
+                    anyhow::Error::from(teardown_err)
+                );
+            }
+            return Err(e);
+        }
+
+        Ok(runner)
+    }
+
+    /// Stop and remove every container started by `start`
+    pub async fn shutdown(mut self) -> ArchTestingResult<()> {
+        self.teardown().await
+    }
+
+    /// Set up the environment, print how to reach it (RPC and websocket URLs, Titan, bitcoind
+    /// credentials) and keep it up until ctrl-c, then tear it down. For poking at the stack by
+    /// hand, instead of a dummy test that sleeps.
+    pub async fn hold(config: TestRunnerConfig) -> ArchTestingResult<()> {
+        let mut runner = Self::start(&config).await?;
+
+        let held = runner.wait_for_ctrl_c(&config).await;
+
+        let teardown_result = runner.shutdown().await;
+        held?;
+        teardown_result
+    }
 
-        let mut ctx = Self {
+    /// Start the explorer (when configured), print how to reach the environment, and wait
+    /// for ctrl-c
+    async fn wait_for_ctrl_c(&mut self, config: &TestRunnerConfig) -> Result<()> {
+        if config.explorer {
+            let explorer: Arc<dyn TestService> = Arc::new(ExplorerContainer::from(config.clone()));
+            let explorer = start_service(&explorer, &ServiceEndpoints::new(config)?).await?;
+            self.services.push(explorer);
+        }
+
+        println!("{}", config.connection_details()?);
+        println!("\nEnvironment is up, press ctrl-c to tear it down");
+        tokio::signal::ctrl_c()
+            .await
+            .context("Failed to wait for ctrl-c")
+    }
+
+    fn new() -> Self {
+        Self {
             bitcoin_container: None,
             titan_container: None,
             local_validator_conainer: None,
-        };
+            toxiproxy_container: None,
+            services: Vec::new(),
+        }
+    }
 
-        let setup_result = ctx.setup_with_timeout(&config).await;
+    fn build_async_arch_rpc_client(
+        &self,
+        config: &TestRunnerConfig,
+        validator: &ValidatorHandle,
+    ) -> Result<AsyncArchRpcClient> {
+        let http_client = config.arch_rpc_client.build_http_client()?;
+        Ok(AsyncArchRpcClient::with_client(
+            &validator.rpc_url(),
+            http_client,
+        ))
+    }
 
-        let final_result = match setup_result {
-            Ok(_) => ctx.test_with_timeout(&config, test_fn).await,
-            Err(setup_err) => Err(setup_err),
-        };
+    fn build_arch_rpc_client(
+        &self,
+        config: &TestRunnerConfig,
+        validator: &ValidatorHandle,
+    ) -> Result<ArchRpcClient> {
+        Ok(ArchRpcClient::new(
+            &self.get_network_config(config, validator)?,
+        ))
+    }
 
-        // IMPORTANT: Always teardown, regardless of {setup, test} success or failure
-        ctx.teardown().await;
+    fn build_bitcoin_handle(&self, config: &TestRunnerConfig) -> Result<BitcoinHandle> {
+        let bitcoin_config = BitcoinContainerConfig::from(config.clone());
+        let rpc_url = bitcoin_config.local_network_wallet_rpc_url(DEFAULT_WALLET_NAME);
 
-        if let Err(e) = final_result {
-            panic!("Test run failed: {}", e);
-        }
-    }
+        let client = bitcoincore_rpc::Client::new(&rpc_url, (&bitcoin_config).into())
+            .with_context(|| format!("Failed to create bitcoin rpc_client for {}", rpc_url))?;
 
-    fn build_program_deployer(&self, config: &TestRunnerConfig) -> Result<ProgramDeployer> {
-        Ok(ProgramDeployer::new(&self.get_network_config(config)?))
+        Ok(BitcoinHandle {
+            client: AsyncBitcoinClient::new(client),
+            config: bitcoin_config,
+        })
     }
 
-    fn build_async_arch_rpc_client(&self) -> Result<AsyncArchRpcClient> {
-        Ok(AsyncArchRpcClient::new(&self.get_rpc_url()?))
+    fn build_titan_handle(&self, config: &TestRunnerConfig) -> TitanHandle {
+        let titan_config = TitanContainerConfig::from(config.clone());
+
+        TitanHandle {
+            client: Arc::new(TitanClient::new(&titan_config.local_network_http_url())),
+            config: titan_config,
+        }
     }
 
-    fn build_arch_rpc_client(&self, config: &TestRunnerConfig) -> Result<ArchRpcClient> {
-        Ok(ArchRpcClient::new(&self.get_network_config(config)?))
+    async fn build_validator_handle(&self, config: &TestRunnerConfig) -> Result<ValidatorHandle> {
+        let (websocket, rpc_capture) = match &config.rpc_cassette {
+            Some(RpcCassetteMode::Replay(path)) => {
+                ensure!(
+                    !config.record_events,
+                    "record_events needs a validator, which replayed runs don't have"
+                );
+                ensure!(
+                    config.services.is_empty() && !config.electrs,
+                    "Services need the containers, which replayed runs don't have"
+                );
+                let rpc_capture = RpcCapture::replay(RpcCassette::load(path)?).await?;
+                let websocket = OnceCell::new_with(Some(ValidatorSubscription::offline()));
+                (websocket, Some(rpc_capture))
+            }
+            rpc_cassette => {
+                let validator = self.get_validator()?;
+
+                let rpc_capture = if config.capture_rpc || rpc_cassette.is_some() {
+                    Some(
+                        RpcCapture::start(
+                            &validator.rpc_url(),
+                            config.arch_rpc_client.build_http_client()?,
+                        )
+                        .await?,
+                    )
+                } else {
+                    None
+                };
+
+                (OnceCell::new(), rpc_capture)
+            }
+        };
+
+        Ok(ValidatorHandle {
+            config: LocalValidatorContainerConfig::from(config.clone()),
+            http_client: config.arch_rpc_client.build_http_client()?,
+            rpc_client_config: config.arch_rpc_client.clone(),
+            websocket: Arc::new(websocket),
+            rpc_capture,
+        })
     }
 
-    fn get_network_config(&self, config: &TestRunnerConfig) -> Result<arch_sdk::Config> {
-        let validator = self.get_validator()?;
+    fn get_network_config(
+        &self,
+        config: &TestRunnerConfig,
+        validator: &ValidatorHandle,
+    ) -> Result<arch_sdk::Config> {
         let bitcoin_config = BitcoinContainerConfig::from(config.clone());
+        let (node_username, node_password) = bitcoin_config.rpc_credentials()?;
 
         Ok(arch_sdk::Config {
             node_endpoint: bitcoin_config.local_network_rpc_url(),
-            node_username: bitcoin_config.rpc_user,
-            node_password: bitcoin_config.rpc_password,
-            network: Network::Regtest,
+            node_username,
+            node_password,
+            network: config.network_mode.bitcoin_network(),
             arch_node_url: validator.rpc_url(),
         })
     }
 
-    fn get_rpc_url(&self) -> Result<String> {
-        let validator = self.get_validator()?;
-        Ok(validator.rpc_url())
-    }
-
     fn get_validator(&self) -> Result<&LocalValidatorContainer> {
         self.local_validator_conainer
             .as_ref()
             .ok_or(anyhow!("Validator not found"))
     }
 
-    async fn setup_with_timeout(&mut self, config: &TestRunnerConfig) -> Result<()> {
-        let setup_timeout = if config.setup_timeout > MAX_SETUP_TIMEOUT {
-            tracing::warn!(
-                "Configured setup_timeout {:?} exceeds maximum {:?}. Capping at maximum",
-                config.setup_timeout,
-                MAX_SETUP_TIMEOUT
-            );
-            MAX_SETUP_TIMEOUT
-        } else {
-            config.setup_timeout
-        };
+    async fn setup_with_timeout(&mut self, config: &TestRunnerConfig) -> ArchTestingResult<()> {
+        let setup_timeout = capped_setup_timeout(config);
 
         match timeout(setup_timeout, self.setup_internal(config)).await {
             Ok(result) => result,
-            Err(e) => Err(e.into()),
+            Err(_) => Err(ArchTestingError::SetupTimeout(setup_timeout)),
         }
     }
 
-    async fn setup_internal(&mut self, config: &TestRunnerConfig) -> Result<()> {
+    async fn setup_internal(&mut self, config: &TestRunnerConfig) -> ArchTestingResult<()> {
         let bitcoin_config = BitcoinContainerConfig::from(config.clone());
         self.bitcoin_container = Some(
             BitcoinContainer::start(&bitcoin_config).await?, //
@@ -121,21 +311,58 @@ impl TestRunner {
         tracing::debug!("Bitcoin container started");
 
         let titan_config = TitanContainerConfig::from(config.clone());
+
+        // with network faults, Titan and the validator see their upstreams through toxiproxy
+        let (upstream_bitcoin_config, upstream_titan_config) = if config.network_faults {
+            let toxiproxy_config = ToxiproxyContainerConfig::from(config.clone());
+            self.toxiproxy_container = Some(
+                ToxiproxyContainer::start(&toxiproxy_config, &bitcoin_config, &titan_config)
+                    .await?,
+            );
+            tracing::debug!("Toxiproxy container started");
+
+            (
+                toxiproxy_config.proxied_bitcoin_config(&bitcoin_config),
+                toxiproxy_config.proxied_titan_config(&titan_config),
+            )
+        } else {
+            (bitcoin_config.clone(), titan_config.clone())
+        };
+
         self.titan_container = Some(
-            TitanContainer::start(&bitcoin_config, &titan_config).await?, //
+            TitanContainer::start(&upstream_bitcoin_config, &titan_config).await?, //
         );
         tracing::debug!("Titan container started");
 
         let local_validator_config = LocalValidatorContainerConfig::from(config.clone());
         self.local_validator_conainer = Some(
-            LocalValidatorContainer::start(&local_validator_config, &titan_config).await?, //
+            LocalValidatorContainer::start(&local_validator_config, &upstream_titan_config).await?,
         );
         tracing::debug!("Validator container started");
 
+        let mut services = config.services.clone();
+        if config.electrs {
+            services.insert(0, Arc::new(ElectrsContainer::from(config.clone())));
+        }
+
+        if !services.is_empty() {
+            check_service_names(&services)?;
+            let endpoints = ServiceEndpoints::new(config)?;
+
+            for service in &services {
+                self.services
+                    .push(start_service(service, &endpoints).await?);
+            }
+        }
+
         Ok(())
     }
 
-    async fn test_with_timeout<F, Fut>(&self, config: &TestRunnerConfig, test_fn: F) -> Result<()>
+    async fn test_with_timeout<F, Fut>(
+        &self,
+        config: &TestRunnerConfig,
+        test_fn: F,
+    ) -> ArchTestingResult<()>
     where
         F: FnOnce(TestContext) -> Fut,
         Fut: Future<Output = Result<()>>,
@@ -152,48 +379,215 @@ impl TestRunner {
             config.test_timeout
         };
 
-        let ctx = TestContext::new(
-            self.build_async_arch_rpc_client()?,
-            self.build_arch_rpc_client(config)?,
-            self.build_program_deployer(config)?,
+        let validator = self.build_validator_handle(config).await?;
+        let mut ctx = TestContext::new(
+            self.build_async_arch_rpc_client(config, &validator)?,
+            self.build_arch_rpc_client(config, &validator)?,
+            self.build_bitcoin_handle(config)?,
+            self.build_titan_handle(config),
+            validator,
+            config.network_mode.bitcoin_network(),
+        )
+        .with_config(config.context)
+        .with_services(
+            self.services
+                .iter()
+                .map(|service| service.handle.clone())
+                .collect(),
         );
+        if config.network_faults {
+            ctx = ctx.with_network_faults(NetworkFaults::new(ToxiproxyContainerConfig::from(
+                config.clone(),
+            )));
+        }
+
+        if config.record_events {
+            let recorder = EventRecorder::start(&ctx.validator().websocket_url()).await?;
+            ctx = ctx.with_event_recorder(recorder);
+        }
+
+        // keep a handle, so background work started by the test is stopped when it ends
+        let runner_ctx = ctx.clone();
+
+        // preparing state can hang on a deploy or a remote fork too, so it gets a setup timeout
+        // of its own, and its failures go through the same cleanup as the test's
+        let setup_timeout = capped_setup_timeout(config);
+        let prepared = match timeout(setup_timeout, prepare_state(&ctx, config)).await {
+            Ok(result) => result,
+            Err(_) => Err(ArchTestingError::SetupTimeout(setup_timeout)),
+        };
+
+        let mut test_result = match prepared {
+            Ok(()) => match timeout(test_timeout, test_fn(ctx)).await {
+                // keep typed errors the test passed through with `?` matchable
+                Ok(test_result) => {
+                    test_result.map_err(|e| match e.downcast::<ArchTestingError>() {
+                        Ok(e) => e,
+                        Err(e) => ArchTestingError::Other(e),
+                    })
+                }
+                Err(_) => Err(ArchTestingError::TestTimeout(test_timeout)),
+            },
+            Err(e) => Err(e),
+        };
+
+        runner_ctx.stop_auto_mining();
+
+        if let Ok(recorder) = runner_ctx.event_recorder() {
+            recorder.stop();
+            if test_result.is_err() {
+                tracing::info!(
+                    "Validator events ({} recorded):\n{}",
+                    recorder.events().len(),
+                    recorder.timeline()
+                );
+            }
+        }
+
+        if let Ok(rpc_capture) = runner_ctx.rpc_capture() {
+            rpc_capture.stop();
+
+            if let Some(mode) = &config.rpc_cassette {
+                if let Err(e) = finish_rpc_cassette(mode, rpc_capture, test_result.is_ok()) {
+                    if test_result.is_ok() {
+                        test_result = Err(e.into());
+                    } else {
+                        tracing::warn!("{:#}", e);
+                    }
+                }
+            }
+
+            if test_result.is_err() {
+                tracing::info!(
+                    "Last RPC calls ({} captured):\n{}",
+                    rpc_capture.call_count(),
+                    rpc_capture.dump(DEFAULT_RPC_CAPTURE_DUMP_LEN)
+                );
+            }
+        }
+
+        test_result
+    }
 
-        match timeout(test_timeout, test_fn(ctx)).await {
-            Ok(test_result) => test_result,
-            Err(e) => Err(e.into()),
+    /// Best effort: a failed capture is logged, never allowed to mask the test failure
+    async fn capture_failure_state(&self, artifact_dir: PathBuf) {
+        let mut containers = Vec::new();
+
+        if let Some(bitcoin_container) = &self.bitcoin_container {
+            containers.push((
+                "bitcoind".to_string(),
+                bitcoin_container.container.id().to_string(),
+            ));
+        }
+        if let Some(titan_container) = &self.titan_container {
+            containers.push((
+                "titand".to_string(),
+                titan_container.container.id().to_string(),
+            ));
+        }
+        if let Some(validator_container) = &self.local_validator_conainer {
+            containers.push((
+                "local_validator".to_string(),
+                validator_container.container.id().to_string(),
+            ));
+        }
+
+        if let Some(toxiproxy_container) = &self.toxiproxy_container {
+            containers.push((
+                "toxiproxy".to_string(),
+                toxiproxy_container.container.id().to_string(),
+            ));
+        }
+
+        for service in &self.services {
+            containers.push((
+                service.handle.name.clone(),
+                service.handle.container_id.clone(),
+            ));
+        }
+
+        tracing::info!("Capturing docker state into {}", artifact_dir.display());
+
+        if let Err(e) = capture_docker_state(artifact_dir, containers).await {
+            tracing::warn!("Failed to capture docker state: {}", e);
         }
     }
 
-    async fn teardown(&mut self) {
+    /// Stops every container, even if stopping one fails; returns the first failure
+    async fn teardown(&mut self) -> ArchTestingResult<()> {
         tracing::trace!("Starting teardown...");
 
-        // Stop validator container first
+        let mut results = Vec::new();
+
+        // Stop services first, they depend on the core stack
+        while let Some(service) = self.services.pop() {
+            results.push(service.shutdown().await);
+        }
+
+        // Stop validator container
         if let Some(validator_container) = self.local_validator_conainer.take() {
-            validator_container
-                .shutdown()
-                .await
-                .context("Failed to stop validator container")
-                .unwrap();
+            results.push(validator_container.shutdown().await);
         }
 
         // Stop Titan container
         if let Some(titan_container) = self.titan_container.take() {
-            titan_container
-                .shutdown()
-                .await
-                .context("Failed to stop titan container")
-                .unwrap();
+            results.push(titan_container.shutdown().await);
+        }
+
+        // Stop Toxiproxy container
+        if let Some(toxiproxy_container) = self.toxiproxy_container.take() {
+            results.push(toxiproxy_container.shutdown().await);
         }
 
         // Stop Bitcoin container
         if let Some(bitcoin_container) = self.bitcoin_container.take() {
-            bitcoin_container
-                .shutdown()
-                .await
-                .context("Failed to stop bitcoin container")
-                .unwrap();
+            results.push(bitcoin_container.shutdown().await);
         }
 
         tracing::debug!("Completed teardown");
+        results.into_iter().collect()
     }
 }
+
+/// Identifies a single `TestRunner` run in logs (process id + start time, hex encoded)
+/// `config.setup_timeout`, capped at `MAX_SETUP_TIMEOUT`
+fn capped_setup_timeout(config: &TestRunnerConfig) -> Duration {
+    if config.setup_timeout > MAX_SETUP_TIMEOUT {
+        tracing::warn!(
+            "Configured setup_timeout {:?} exceeds maximum {:?}. Capping at maximum",
+            config.setup_timeout,
+            MAX_SETUP_TIMEOUT
+        );
+        MAX_SETUP_TIMEOUT
+    } else {
+        config.setup_timeout
+    }
+}
+
+/// Programs, accounts and background mining the test expects to find in place
+async fn prepare_state(ctx: &TestContext, config: &TestRunnerConfig) -> ArchTestingResult<()> {
+    ctx.deploy_preloaded_programs(&config.preloaded_programs)
+        .await?;
+    ctx.create_genesis_accounts(&config.genesis_accounts)
+        .await?;
+    ctx.clone_configured_accounts(&config.cloned_accounts)
+        .await?;
+    if let Some(fork) = &config.fork {
+        ctx.fork_from(fork).await?;
+    }
+
+    if let Some(interval) = config.auto_mine_interval {
+        ctx.start_auto_mining(interval).await?;
+    }
+
+    Ok(())
+}
+
+fn new_run_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+
+    format!("{:x}-{:x}", std::process::id(), nanos)
+}
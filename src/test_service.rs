@@ -0,0 +1,196 @@
+//! Custom sidecar containers (an indexer, a webhook receiver, a simulator, ...) that join the
+//! environment: started after the core stack and handed its endpoints, their logs traced and
+//! captured on failure like the core containers', and torn down with it.
+//!
+//! ```ignore
+//! #[derive(Debug)]
+//! struct Indexer;
+//!
+//! impl TestService for Indexer {
+//!     fn name(&self) -> &str {
+//!         "indexer"
+//!     }
+//!
+//!     fn container(&self, endpoints: &ServiceEndpoints) -> Result<ContainerRequest<GenericImage>> {
+//!         Ok(GenericImage::new("my-indexer", "latest")
+//!             .with_wait_for(WaitFor::message_on_stdout("listening"))
+//!             .with_mapped_port(7000, ContainerPort::Tcp(7000))
+//!             .with_env_var("ARCH_RPC_URL", &endpoints.docker.validator_rpc_url))
+//!     }
+//! }
+//!
+//! let config = TestRunnerConfig::new()?.with_service(Indexer);
+//! TestRunner::run_with_config(config, |ctx| async move {
+//!     let indexer = ctx.service_handle("indexer")?;
+//!     ...
+//! })
+//! .await;
+//! ```
+
+use std::{any::Any, collections::HashSet, fmt, sync::Arc};
+
+use anyhow::{ensure, Context, Result};
+use futures::future::BoxFuture;
+use testcontainers::{
+    runners::AsyncRunner, ContainerAsync, ContainerRequest, GenericImage, ImageExt,
+};
+
+use crate::{
+    containers::container_log_consumer,
+    error::{ArchTestingError, ArchTestingResult},
+    exec::{command_args, docker_exec},
+    ConnectionDetails, ExecOutput, TestRunnerConfig,
+};
+
+/// A container started alongside the core stack; register it with
+/// `TestRunnerConfig::with_service`
+pub trait TestService: AsAny + fmt::Debug + Send + Sync + 'static {
+    /// Unique within a run; tags the service's log lines and failure artifacts, and looks it up
+    /// with `TestContext::service_handle`
+    fn name(&self) -> &str;
+
+    /// The container to start, once bitcoind, Titan and the validator are up. Map fixed host
+    /// ports (`with_mapped_port`) for what the test connects to, and reach the core stack
+    /// through `endpoints.docker`.
+    fn container(&self, endpoints: &ServiceEndpoints) -> Result<ContainerRequest<GenericImage>>;
+
+    /// Resolves once the started service is usable. Returns right away by default, leaving
+    /// readiness to the container's `with_wait_for`.
+    fn wait_until_ready<'a>(&'a self, _service: &'a ServiceHandle) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Lets `TestContext::service` find a service by its type; implemented for every type
+#[doc(hidden)]
+pub trait AsAny {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Where a service finds the core stack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceEndpoints {
+    /// As reached from the test process
+    pub local: ConnectionDetails,
+    /// As reached from inside a container
+    pub docker: ConnectionDetails,
+}
+
+impl ServiceEndpoints {
+    /// Endpoints of the stack `config` runs; needs it up when bitcoind uses cookie auth
+    pub fn new(config: &TestRunnerConfig) -> Result<Self> {
+        Ok(Self {
+            local: config.connection_details()?,
+            docker: config.docker_network_connection_details()?,
+        })
+    }
+}
+
+/// A running service, see `TestContext::service_handle`
+#[derive(Debug, Clone)]
+pub struct ServiceHandle {
+    pub name: String,
+    pub container_id: String,
+    /// What was registered with `TestRunnerConfig::with_service`
+    pub service: Arc<dyn TestService>,
+}
+
+impl ServiceHandle {
+    /// Run `cmd` in the service's container
+    pub async fn exec(
+        &self,
+        cmd: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<ExecOutput> {
+        docker_exec(&self.container_id, command_args(cmd)).await
+    }
+}
+
+/// A service the runner started, stopped on teardown
+pub(crate) struct ServiceContainer {
+    pub handle: ServiceHandle,
+    container: ContainerAsync<GenericImage>,
+}
+
+impl ServiceContainer {
+    pub async fn shutdown(&self) -> ArchTestingResult<()> {
+        self.container.stop().await.map_err(|e| {
+            let source = anyhow::anyhow!("Failed to stop service {}: {}", self.handle.name, e);
+            ArchTestingError::teardown_failed("service", source)
+        })
+    }
+}
+
+impl TestRunnerConfig {
+    /// This configuration with `service` started after the core stack (in registration order)
+    pub fn with_service(mut self, service: impl TestService) -> Self {
+        self.services.push(Arc::new(service));
+        self
+    }
+}
+
+/// Check service names are unique before anything starts
+pub(crate) fn check_service_names(services: &[Arc<dyn TestService>]) -> Result<()> {
+    let mut names = HashSet::new();
+    for service in services {
+        ensure!(
+            names.insert(service.name()),
+            "Two services are named {}",
+            service.name()
+        );
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn start_service(
+    service: &Arc<dyn TestService>,
+    endpoints: &ServiceEndpoints,
+) -> ArchTestingResult<ServiceContainer> {
+    try_start_service(service, endpoints)
+        .await
+        .map_err(|e| ArchTestingError::container_start_failed("service", e))
+}
+
+async fn try_start_service(
+    service: &Arc<dyn TestService>,
+    endpoints: &ServiceEndpoints,
+) -> Result<ServiceContainer> {
+    let name = service.name().to_string();
+    tracing::trace!("Starting service {}", name);
+
+    let container_id = Arc::new(std::sync::OnceLock::new());
+    let log_consumer = container_log_consumer(name.clone(), container_id.clone());
+
+    let container = service
+        .container(endpoints)
+        .with_context(|| format!("Failed to build the {} service container", name))?
+        .with_log_consumer(log_consumer)
+        .start()
+        .await
+        .with_context(|| format!("Failed to start service {}", name))?;
+
+    let _ = container_id.set(container.id().to_string());
+
+    let service_container = ServiceContainer {
+        handle: ServiceHandle {
+            name: name.clone(),
+            container_id: container.id().to_string(),
+            service: service.clone(),
+        },
+        container,
+    };
+
+    service
+        .wait_until_ready(&service_container.handle)
+        .await
+        .with_context(|| format!("Service {} didn't become ready", name))?;
+
+    tracing::debug!("Service {} started", name);
+    Ok(service_container)
+}
@@ -0,0 +1,260 @@
+//! CLTV/CSV timelock helpers.
+//!
+//! Typical flow: `create_timelocked_utxo`, `assert_timelock_spend_rejected`,
+//! `advance_past_timelock`, then `spend_timelocked_utxo`.
+
+use anyhow::{ensure, Context, Result};
+use bitcoin::{
+    absolute::LockTime,
+    ecdsa,
+    hashes::Hash,
+    key::Keypair,
+    opcodes::all::{OP_CHECKSIG, OP_CLTV, OP_CSV, OP_DROP},
+    script::Builder,
+    secp256k1::{Message, Secp256k1},
+    sighash::{EcdsaSighashType, SighashCache},
+    transaction::Version,
+    Address, Amount, OutPoint, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid,
+    Witness,
+};
+use bitcoincore_rpc::RpcApi;
+
+use crate::TestContext;
+
+/// Fee paid by timelock spends (well above the regtest relay minimum)
+pub const TIMELOCK_SPEND_FEE: Amount = Amount::from_sat(1_000);
+
+/// Number of blocks needed to move the median time past forward after a mocktime jump
+const MEDIAN_TIME_SPAN: u64 = 11;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timelock {
+    /// `OP_CHECKLOCKTIMEVERIFY` against a block height
+    AbsoluteHeight(u32),
+    /// `OP_CHECKLOCKTIMEVERIFY` against a unix timestamp (compared to median time past)
+    AbsoluteTime(u32),
+    /// `OP_CHECKSEQUENCEVERIFY` against a number of blocks since the UTXO confirmed
+    RelativeBlocks(u16),
+}
+
+impl Timelock {
+    fn script_value(&self) -> i64 {
+        match self {
+            Timelock::AbsoluteHeight(height) => i64::from(*height),
+            Timelock::AbsoluteTime(time) => i64::from(*time),
+            Timelock::RelativeBlocks(blocks) => i64::from(*blocks),
+        }
+    }
+
+    fn lock_time(&self) -> Result<LockTime> {
+        Ok(match self {
+            Timelock::AbsoluteHeight(height) => LockTime::from_height(*height)?,
+            Timelock::AbsoluteTime(time) => LockTime::from_time(*time)?,
+            Timelock::RelativeBlocks(_) => LockTime::ZERO,
+        })
+    }
+
+    fn sequence(&self) -> Sequence {
+        match self {
+            Timelock::RelativeBlocks(blocks) => Sequence::from_height(*blocks),
+            // anything but MAX, so the transaction's lock time is enforced
+            _ => Sequence::ENABLE_LOCKTIME_NO_RBF,
+        }
+    }
+
+    /// `<lock> OP_CLTV|OP_CSV OP_DROP <pubkey> OP_CHECKSIG`
+    fn witness_script(&self, pubkey: &PublicKey) -> ScriptBuf {
+        let verify = match self {
+            Timelock::RelativeBlocks(_) => OP_CSV,
+            _ => OP_CLTV,
+        };
+
+        Builder::new()
+            .push_int(self.script_value())
+            .push_opcode(verify)
+            .push_opcode(OP_DROP)
+            .push_key(pubkey)
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+}
+
+/// A confirmed P2WSH output that can only be spent once its timelock has passed
+#[derive(Debug, Clone)]
+pub struct TimelockedUtxo {
+    pub outpoint: OutPoint,
+    pub amount: Amount,
+    pub timelock: Timelock,
+    pub address: Address,
+    /// Height of the block the UTXO confirmed in (the base for relative timelocks)
+    pub confirmed_height: u64,
+    pub witness_script: ScriptBuf,
+    keypair: Keypair,
+}
+
+impl TestContext {
+    /// Fund a fresh timelocked P2WSH output from the test wallet and confirm it
+    pub async fn create_timelocked_utxo(
+        &self,
+        timelock: Timelock,
+        amount: Amount,
+    ) -> Result<TimelockedUtxo> {
+        let (keypair, _, _) = self.generate_new_keypair();
+        let pubkey = PublicKey::new(keypair.public_key());
+        let witness_script = timelock.witness_script(&pubkey);
+        let address = Address::p2wsh(&witness_script, self.network);
+
+        let funding_address = address.clone();
+        let txid = self
+            .with_bitcoin_client(move |client| {
+                client.send_to_address(&funding_address, amount, None, None, None, None, None, None)
+            })
+            .await
+            .context("Failed to fund timelocked address")?;
+
+        let funding_tx = self
+            .with_bitcoin_client(move |client| client.get_raw_transaction(&txid, None))
+            .await?;
+
+        let vout = funding_tx
+            .output
+            .iter()
+            .position(|output| output.script_pubkey == address.script_pubkey())
+            .context("Funding transaction doesn't pay the timelocked address")?;
+
+        self.bitcoin_mine_blocks(1).await?;
+        let confirmed_height = self
+            .with_bitcoin_client(|client| client.get_block_count())
+            .await?;
+
+        Ok(TimelockedUtxo {
+            outpoint: OutPoint::new(txid, vout as u32),
+            amount,
+            timelock,
+            address,
+            confirmed_height,
+            witness_script,
+            keypair,
+        })
+    }
+
+    /// Build and sign a transaction spending `utxo` back to the test wallet
+    pub async fn build_timelock_spend(&self, utxo: &TimelockedUtxo) -> Result<Transaction> {
+        let destination = self
+            .with_bitcoin_client(|client| client.get_new_address(None, None))
+            .await?
+            .assume_checked();
+        let value = utxo
+            .amount
+            .checked_sub(TIMELOCK_SPEND_FEE)
+            .with_context(|| {
+                format!(
+                    "Timelocked utxo of {} can't cover the spend fee of {}",
+                    utxo.amount, TIMELOCK_SPEND_FEE
+                )
+            })?;
+
+        let mut tx = Transaction {
+            version: Version::TWO, // required for CSV
+            lock_time: utxo.timelock.lock_time()?,
+            input: vec![TxIn {
+                previous_output: utxo.outpoint,
+                script_sig: ScriptBuf::new(),
+                sequence: utxo.timelock.sequence(),
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: destination.script_pubkey(),
+            }],
+        };
+
+        let sighash = SighashCache::new(&tx).p2wsh_signature_hash(
+            0,
+            &utxo.witness_script,
+            utxo.amount,
+            EcdsaSighashType::All,
+        )?;
+
+        let secp = Secp256k1::new();
+        let signature = ecdsa::Signature {
+            signature: secp.sign_ecdsa(
+                &Message::from_digest(sighash.to_byte_array()),
+                &utxo.keypair.secret_key(),
+            ),
+            sighash_type: EcdsaSighashType::All,
+        };
+
+        tx.input[0].witness =
+            Witness::from_slice(&[signature.to_vec(), utxo.witness_script.to_bytes()]);
+
+        Ok(tx)
+    }
+
+    /// Broadcast a spend of `utxo` and assert bitcoind rejects it as not yet final
+    pub async fn assert_timelock_spend_rejected(&self, utxo: &TimelockedUtxo) -> Result<()> {
+        let tx = self.build_timelock_spend(utxo).await?;
+
+        let result = self
+            .with_bitcoin_client(move |client| client.send_raw_transaction(&tx))
+            .await;
+
+        match result {
+            Ok(txid) => Err(anyhow::anyhow!(
+                "Expected early spend of {} to be rejected, but it was accepted as {}",
+                utxo.outpoint,
+                txid
+            )),
+            Err(e) => {
+                let message = format!("{:#}", e);
+                ensure!(
+                    message.contains("non-final") || message.contains("non-BIP68-final"),
+                    "Early spend of {} was rejected for an unexpected reason: {}",
+                    utxo.outpoint,
+                    message
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// Mine (and, for time locks, move the mocktime) until `utxo` becomes spendable
+    pub async fn advance_past_timelock(&self, utxo: &TimelockedUtxo) -> Result<()> {
+        let height = self
+            .with_bitcoin_client(|client| client.get_block_count())
+            .await?;
+
+        match utxo.timelock {
+            Timelock::AbsoluteHeight(lock_height) => {
+                // the spend has to fit into the block after the tip
+                let needed = u64::from(lock_height).saturating_sub(height);
+                self.bitcoin_mine_blocks(needed).await?;
+            }
+            Timelock::RelativeBlocks(blocks) => {
+                let spendable_height = utxo.confirmed_height + u64::from(blocks) - 1;
+                let needed = spendable_height.saturating_sub(height);
+                self.bitcoin_mine_blocks(needed).await?;
+            }
+            Timelock::AbsoluteTime(lock_time) => {
+                self.bitcoin_set_mocktime(u64::from(lock_time) + 1).await?;
+                self.bitcoin_mine_blocks(MEDIAN_TIME_SPAN).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Broadcast a spend of `utxo` (expected to be spendable now) and confirm it
+    pub async fn spend_timelocked_utxo(&self, utxo: &TimelockedUtxo) -> Result<Txid> {
+        let tx = self.build_timelock_spend(utxo).await?;
+
+        let txid = self
+            .with_bitcoin_client(move |client| client.send_raw_transaction(&tx))
+            .await
+            .with_context(|| format!("Failed to spend timelocked utxo {}", utxo.outpoint))?;
+
+        self.bitcoin_mine_blocks(1).await?;
+
+        Ok(txid)
+    }
+}
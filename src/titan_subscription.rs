@@ -0,0 +1,214 @@
+//! Client for Titan's TCP subscription endpoint.
+//!
+//! Titan streams newline-delimited JSON events after receiving a subscription request. Events
+//! are kept as raw JSON, so this keeps working as Titan adds event kinds or fields.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use bitcoin::Address;
+use titan_client::TitanApi;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    sync::broadcast,
+    task::JoinHandle,
+};
+use tracing::Instrument;
+
+use crate::TestContext;
+
+pub const DEFAULT_TITAN_EVENT_TIMEOUT: Duration = Duration::from_secs(30);
+const TITAN_EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Event kinds to subscribe to when the caller doesn't care
+pub const ALL_TITAN_EVENT_TYPES: &[&str] = &[
+    "AddressModified",
+    "NewBlock",
+    "Reorg",
+    "TransactionsAdded",
+    "TransactionsReplaced",
+    "RuneEtched",
+    "RuneMinted",
+    "RuneBurned",
+    "RuneTransferred",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TitanEvent(pub serde_json::Value);
+
+impl TitanEvent {
+    /// The event kind, whether Titan tags it internally (`{"type": ..}`) or externally (`{kind: ..}`)
+    pub fn event_type(&self) -> Option<&str> {
+        let object = self.0.as_object()?;
+
+        match object.get("type").and_then(|kind| kind.as_str()) {
+            Some(kind) => Some(kind),
+            None if object.len() == 1 => object.keys().next().map(String::as_str),
+            None => None,
+        }
+    }
+
+    /// Whether `needle` (an address, txid, rune, ...) appears anywhere in the event
+    pub fn mentions(&self, needle: &str) -> bool {
+        self.0.to_string().contains(needle)
+    }
+}
+
+/// A live subscription; the listener task stops when this is dropped
+pub struct TitanSubscription {
+    receiver: broadcast::Receiver<TitanEvent>,
+    filter: Option<String>,
+    listener: JoinHandle<()>,
+}
+
+impl TitanSubscription {
+    pub async fn connect(tcp_address: &str, event_types: &[&str]) -> Result<Self> {
+        let stream = TcpStream::connect(tcp_address)
+            .await
+            .with_context(|| format!("Failed to connect to titan at {}", tcp_address))?;
+        let (reader, mut writer) = stream.into_split();
+
+        let mut request = serde_json::json!({ "subscribe": event_types }).to_string();
+        request.push('\n');
+        writer
+            .write_all(request.as_bytes())
+            .await
+            .context("Failed to send titan subscription request")?;
+
+        // the listener owns the only sender, so receivers see the channel close when it ends
+        let (sender, receiver) = broadcast::channel(TITAN_EVENT_CHANNEL_CAPACITY);
+
+        let listener = tokio::spawn(
+            async move {
+                // keep the write half alive, titan drops the subscription when it closes
+                let _writer = writer;
+                let mut lines = BufReader::new(reader).lines();
+
+                loop {
+                    match lines.next_line().await {
+                        Ok(Some(line)) => match serde_json::from_str(&line) {
+                            Ok(event) => {
+                                let _ = sender.send(TitanEvent(event));
+                            }
+                            Err(e) => tracing::debug!("Ignoring titan line {:?}: {}", line, e),
+                        },
+                        Ok(None) => {
+                            tracing::debug!("Titan closed the subscription");
+                            break;
+                        }
+                        Err(e) => {
+                            tracing::warn!("Titan subscription failed: {}", e);
+                            break;
+                        }
+                    }
+                }
+            }
+            .instrument(tracing::Span::current()),
+        );
+
+        Ok(Self {
+            receiver,
+            filter: None,
+            listener,
+        })
+    }
+
+    /// Only yield events mentioning `needle`
+    pub fn filtered(mut self, needle: impl Into<String>) -> Self {
+        self.filter = Some(needle.into());
+        self
+    }
+
+    /// Another receiver over the same stream, starting from now
+    pub fn resubscribe(&self) -> broadcast::Receiver<TitanEvent> {
+        self.receiver.resubscribe()
+    }
+
+    /// The next (matching) event; errors once the stream has ended
+    pub async fn recv(&mut self) -> Result<TitanEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => {
+                    let matches = match &self.filter {
+                        Some(needle) => event.mentions(needle),
+                        None => true,
+                    };
+                    if matches {
+                        return Ok(event);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Titan subscription lagged, skipped {} events", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    return Err(anyhow::anyhow!("Titan subscription closed"));
+                }
+            }
+        }
+    }
+
+    /// The next event satisfying `predicate`, within `timeout`
+    pub async fn wait_for<P>(&mut self, predicate: P, timeout: Duration) -> Result<TitanEvent>
+    where
+        P: Fn(&TitanEvent) -> bool,
+    {
+        tokio::time::timeout(timeout, async {
+            loop {
+                let event = self.recv().await?;
+                if predicate(&event) {
+                    return Ok(event);
+                }
+            }
+        })
+        .await
+        .context("Timed out waiting for titan event")?
+    }
+}
+
+impl Drop for TitanSubscription {
+    fn drop(&mut self) {
+        self.listener.abort();
+    }
+}
+
+impl TestContext {
+    pub async fn titan_subscribe(&self, event_types: &[&str]) -> Result<TitanSubscription> {
+        TitanSubscription::connect(&self.titan().tcp_address(), event_types).await
+    }
+
+    /// Subscribe to every event mentioning `address`
+    pub async fn titan_subscribe_address(&self, address: &Address) -> Result<TitanSubscription> {
+        Ok(self
+            .titan_subscribe(ALL_TITAN_EVENT_TYPES)
+            .await?
+            .filtered(address.to_string()))
+    }
+
+    /// Wait until Titan reports an output paying `address`.
+    ///
+    /// Subscribes before checking Titan's current state, so an output indexed in between
+    /// isn't missed.
+    pub async fn wait_for_utxo(&self, address: &Address) -> Result<()> {
+        let mut subscription = self.titan_subscribe_address(address).await?;
+
+        let address_data = self
+            .titan()
+            .client
+            .get_address(&address.to_string())
+            .await?;
+        if !address_data.outputs.is_empty() {
+            return Ok(());
+        }
+
+        subscription
+            .wait_for(
+                |event| event.event_type() == Some("AddressModified"),
+                DEFAULT_TITAN_EVENT_TIMEOUT,
+            )
+            .await
+            .with_context(|| format!("No utxo for {} showed up in titan", address))?;
+
+        Ok(())
+    }
+}
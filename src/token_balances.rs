@@ -0,0 +1,292 @@
+//! Token balance and supply assertions, and token-level snapshots to assert exactly which
+//! balances a transaction moved.
+//!
+//! ```ignore
+//! let before = ctx.snapshot_token_balances(&[alice_tokens, bob_tokens]).await?;
+//! ctx.transfer(&alice_tokens, &bob_tokens, alice_kp, 250).await?;
+//! let diff = before.diff(&ctx.snapshot_token_balances(&[alice_tokens, bob_tokens]).await?);
+//! diff.assert_changed_by(&alice_tokens, -250)?;
+//! diff.assert_changed_by(&bob_tokens, 250)?;
+//! ```
+
+use std::{collections::BTreeMap, fmt};
+
+use anyhow::{ensure, Context, Result};
+use arch_program::pubkey::Pubkey;
+use futures::{stream, StreamExt, TryStreamExt};
+
+use crate::{tokens::unpack_token_account, TestContext, DEFAULT_ACCOUNT_READ_CONCURRENCY};
+
+/// The parts of a token account a balance comparison cares about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenBalance {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+/// Token balances as of one point in time; `None` for accounts that didn't exist (or weren't
+/// token accounts)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenBalancesSnapshot {
+    pub balances: BTreeMap<Pubkey, Option<TokenBalance>>,
+}
+
+impl TokenBalancesSnapshot {
+    /// Balance changes from `self` to `other`, over the accounts in either snapshot
+    pub fn diff(&self, other: &TokenBalancesSnapshot) -> TokenBalancesDiff {
+        let mut pubkeys = self
+            .balances
+            .keys()
+            .chain(other.balances.keys())
+            .collect::<Vec<_>>();
+        pubkeys.sort();
+        pubkeys.dedup();
+
+        let changes = pubkeys
+            .into_iter()
+            .filter_map(|pubkey| {
+                let before = self.balances.get(pubkey).copied().flatten();
+                let after = other.balances.get(pubkey).copied().flatten();
+
+                (before != after).then_some(TokenBalanceChange {
+                    pubkey: *pubkey,
+                    before,
+                    after,
+                })
+            })
+            .collect();
+
+        TokenBalancesDiff { changes }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenBalanceChange {
+    pub pubkey: Pubkey,
+    pub before: Option<TokenBalance>,
+    pub after: Option<TokenBalance>,
+}
+
+impl TokenBalanceChange {
+    /// Signed token change, treating missing accounts as holding 0
+    pub fn delta(&self) -> i128 {
+        let amount = |balance: &Option<TokenBalance>| {
+            balance
+                .as_ref()
+                .map(|balance| balance.amount)
+                .unwrap_or_default() as i128
+        };
+
+        amount(&self.after) - amount(&self.before)
+    }
+}
+
+impl fmt::Display for TokenBalanceChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.before, &self.after) {
+            (None, None) => write!(f, "{}: unchanged", self.pubkey),
+            (None, Some(after)) => write!(
+                f,
+                "{}: created holding {} (mint {}, owner {})",
+                self.pubkey, after.amount, after.mint, after.owner
+            ),
+            (Some(before), None) => {
+                write!(f, "{}: removed (held {})", self.pubkey, before.amount)
+            }
+            (Some(before), Some(after)) => {
+                write!(
+                    f,
+                    "{}: {} -> {} ({:+})",
+                    self.pubkey,
+                    before.amount,
+                    after.amount,
+                    self.delta()
+                )?;
+                if before.owner != after.owner {
+                    write!(f, ", owner {} -> {}", before.owner, after.owner)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The token accounts that differ between two snapshots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenBalancesDiff {
+    pub changes: Vec<TokenBalanceChange>,
+}
+
+impl TokenBalancesDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn get(&self, pubkey: &Pubkey) -> Option<&TokenBalanceChange> {
+        self.changes.iter().find(|change| change.pubkey == *pubkey)
+    }
+
+    /// Signed token change of `pubkey`; 0 if it didn't change
+    pub fn delta_of(&self, pubkey: &Pubkey) -> i128 {
+        self.get(pubkey)
+            .map(TokenBalanceChange::delta)
+            .unwrap_or_default()
+    }
+
+    /// Assert that the balance of `pubkey` changed by exactly `delta`
+    pub fn assert_changed_by(&self, pubkey: &Pubkey, delta: i128) -> Result<()> {
+        let actual = self.delta_of(pubkey);
+
+        ensure!(
+            actual == delta,
+            "Expected token account {} to change by {:+}, it changed by {:+}\nall changes:\n{}",
+            pubkey,
+            delta,
+            actual,
+            self
+        );
+
+        Ok(())
+    }
+
+    /// Assert that every changed account is in `expected` (unchanged `expected` accounts are fine)
+    pub fn assert_only_changed(&self, expected: &[Pubkey]) -> Result<()> {
+        let unexpected = self
+            .changes
+            .iter()
+            .filter(|change| !expected.contains(&change.pubkey))
+            .map(|change| change.to_string())
+            .collect::<Vec<_>>();
+
+        ensure!(
+            unexpected.is_empty(),
+            "Unexpected token balance changes:\n  {}",
+            unexpected.join("\n  ")
+        );
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for TokenBalancesDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return write!(f, "no token balance changes");
+        }
+
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", change)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl TestContext {
+    /// Assert that the token account `account` holds exactly `expected`
+    pub async fn assert_token_balance(&self, account: &Pubkey, expected: u64) -> Result<()> {
+        let token_account = self.read_token_account(account).await?;
+
+        ensure!(
+            token_account.amount == expected,
+            "Expected token account {} (mint {}, owner {}) to hold {}, it holds {} ({:+})",
+            account,
+            token_account.mint,
+            token_account.owner,
+            expected,
+            token_account.amount,
+            token_account.amount as i128 - expected as i128
+        );
+
+        Ok(())
+    }
+
+    /// Assert that `mint`'s supply is exactly `expected`
+    pub async fn assert_mint_supply(&self, mint: &Pubkey, expected: u64) -> Result<()> {
+        let supply = self.read_mint(mint).await?.supply;
+
+        ensure!(
+            supply == expected,
+            "Expected mint {} to have a supply of {}, it has {} ({:+})",
+            mint,
+            expected,
+            supply,
+            supply as i128 - expected as i128
+        );
+
+        Ok(())
+    }
+
+    /// Capture the balances of the token accounts `pubkeys`, for comparison with
+    /// `TokenBalancesSnapshot::diff`. Accounts that don't exist or aren't token accounts are
+    /// recorded as `None`; failing to read one is an error.
+    pub async fn snapshot_token_balances(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> Result<TokenBalancesSnapshot> {
+        let balances = stream::iter(pubkeys.iter().copied())
+            .map(|pubkey| async move {
+                let account = self
+                    .try_read_account_info(pubkey)
+                    .await
+                    .with_context(|| format!("Failed to read token account {}", pubkey))?;
+                let balance = account
+                    .and_then(|account| unpack_token_account(&pubkey, &account).ok())
+                    .map(|token_account| TokenBalance {
+                        mint: token_account.mint,
+                        owner: token_account.owner,
+                        amount: token_account.amount,
+                    });
+                Ok::<_, anyhow::Error>((pubkey, balance))
+            })
+            .buffer_unordered(DEFAULT_ACCOUNT_READ_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        Ok(TokenBalancesSnapshot { balances })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_balances_diff() {
+        let pubkey = |byte| Pubkey::from_slice(&[byte; 32]);
+        let (mint, owner) = (pubkey(1), pubkey(2));
+        let (alice, bob, carol) = (pubkey(3), pubkey(4), pubkey(5));
+        let balance = |amount| {
+            Some(TokenBalance {
+                mint,
+                owner,
+                amount,
+            })
+        };
+
+        let before = TokenBalancesSnapshot {
+            balances: BTreeMap::from([(alice, balance(1000)), (bob, None), (carol, balance(5))]),
+        };
+        let after = TokenBalancesSnapshot {
+            balances: BTreeMap::from([
+                (alice, balance(750)),
+                (bob, balance(250)),
+                (carol, balance(5)),
+            ]),
+        };
+        let diff = before.diff(&after);
+
+        assert_eq!(diff.changes.len(), 2);
+        assert_eq!(diff.delta_of(&alice), -250);
+        assert_eq!(diff.delta_of(&bob), 250);
+        assert_eq!(diff.delta_of(&carol), 0);
+        assert!(diff.assert_changed_by(&alice, -250).is_ok());
+        assert!(diff.assert_changed_by(&bob, 100).is_err());
+        assert!(diff.assert_only_changed(&[alice, bob]).is_ok());
+        assert!(diff.assert_only_changed(&[alice]).is_err());
+    }
+}
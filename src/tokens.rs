@@ -0,0 +1,244 @@
+//! APL token program helpers: mints, token accounts, and the everyday instructions on them.
+//!
+//! Each helper sends one transaction and fails if it does; the signing authority pays for it
+//! unless a payer is passed explicitly.
+//!
+//! ```ignore
+//! let (authority_kp, authority, _) = ctx.generate_funded_keypair().await?;
+//! let (_, mint) = ctx.create_mint(authority_kp, &authority, 6).await?;
+//! let (_, alice_tokens) = ctx.create_token_account(authority_kp, &mint, &alice).await?;
+//! ctx.mint_to(&mint, &alice_tokens, authority_kp, 1_000_000).await?;
+//! ```
+
+use anyhow::{anyhow, ensure, Context, Result};
+use arch_program::{
+    instruction::Instruction, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
+    rent::minimum_rent, system_instruction,
+};
+use arch_sdk::AccountInfo;
+use bitcoin::key::Keypair;
+
+use crate::{ProcessedTransactionExt, TestContext};
+
+impl TestContext {
+    /// Create and initialize a new mint with `decimals` and `mint_authority` (no freeze
+    /// authority), paid for by `payer_kp`. Returns the mint keypair and pubkey.
+    pub async fn create_mint(
+        &self,
+        payer_kp: Keypair,
+        mint_authority: &Pubkey,
+        decimals: u8,
+    ) -> Result<(Keypair, Pubkey)> {
+        let (mint_kp, mint_pubkey, _) = self.generate_new_keypair();
+        let payer_pubkey = Pubkey::from_slice(&payer_kp.x_only_public_key().0.serialize());
+
+        let instructions = [
+            system_instruction::create_account(
+                &payer_pubkey,
+                &mint_pubkey,
+                minimum_rent(apl_token::state::Mint::LEN),
+                apl_token::state::Mint::LEN as u64,
+                &apl_token::id(),
+            ),
+            token_instruction(
+                "initialize_mint",
+                apl_token::instruction::initialize_mint(
+                    &apl_token::id(),
+                    &mint_pubkey,
+                    mint_authority,
+                    None,
+                    decimals,
+                ),
+            )?,
+        ];
+        self.send_token_instructions(&instructions, vec![payer_kp, mint_kp], "create mint")
+            .await?;
+
+        tracing::debug!("Created mint {} ({} decimals)", mint_pubkey, decimals);
+        Ok((mint_kp, mint_pubkey))
+    }
+
+    /// Create and initialize a new token account for `mint`, owned by `owner`, paid for by
+    /// `payer_kp`. Returns the token account keypair and pubkey.
+    pub async fn create_token_account(
+        &self,
+        payer_kp: Keypair,
+        mint: &Pubkey,
+        owner: &Pubkey,
+    ) -> Result<(Keypair, Pubkey)> {
+        let (account_kp, account_pubkey, _) = self.generate_new_keypair();
+        let payer_pubkey = Pubkey::from_slice(&payer_kp.x_only_public_key().0.serialize());
+
+        let instructions = [
+            system_instruction::create_account(
+                &payer_pubkey,
+                &account_pubkey,
+                minimum_rent(apl_token::state::Account::LEN),
+                apl_token::state::Account::LEN as u64,
+                &apl_token::id(),
+            ),
+            token_instruction(
+                "initialize_account",
+                apl_token::instruction::initialize_account(
+                    &apl_token::id(),
+                    &account_pubkey,
+                    mint,
+                    owner,
+                ),
+            )?,
+        ];
+        self.send_token_instructions(
+            &instructions,
+            vec![payer_kp, account_kp],
+            "create token account",
+        )
+        .await?;
+
+        tracing::debug!(
+            "Created token account {} (mint {}, owner {})",
+            account_pubkey,
+            mint,
+            owner
+        );
+        Ok((account_kp, account_pubkey))
+    }
+
+    /// Mint `amount` of `mint` into `account`, signed (and paid for) by `mint_authority_kp`
+    pub async fn mint_to(
+        &self,
+        mint: &Pubkey,
+        account: &Pubkey,
+        mint_authority_kp: Keypair,
+        amount: u64,
+    ) -> Result<()> {
+        let mint_authority =
+            Pubkey::from_slice(&mint_authority_kp.x_only_public_key().0.serialize());
+
+        let instruction = token_instruction(
+            "mint_to",
+            apl_token::instruction::mint_to(
+                &apl_token::id(),
+                mint,
+                account,
+                &mint_authority,
+                &[],
+                amount,
+            ),
+        )?;
+        self.send_token_instructions(&[instruction], vec![mint_authority_kp], "mint")
+            .await
+    }
+
+    /// Transfer `amount` tokens from `source` to `destination`, signed (and paid for) by the
+    /// source account's owner `owner_kp`
+    pub async fn transfer(
+        &self,
+        source: &Pubkey,
+        destination: &Pubkey,
+        owner_kp: Keypair,
+        amount: u64,
+    ) -> Result<()> {
+        let owner = Pubkey::from_slice(&owner_kp.x_only_public_key().0.serialize());
+
+        let instruction = token_instruction(
+            "transfer",
+            apl_token::instruction::transfer(
+                &apl_token::id(),
+                source,
+                destination,
+                &owner,
+                &[],
+                amount,
+            ),
+        )?;
+        self.send_token_instructions(&[instruction], vec![owner_kp], "transfer")
+            .await
+    }
+
+    /// Burn `amount` of `mint` from `account`, signed (and paid for) by its owner `owner_kp`
+    pub async fn burn(
+        &self,
+        account: &Pubkey,
+        mint: &Pubkey,
+        owner_kp: Keypair,
+        amount: u64,
+    ) -> Result<()> {
+        let owner = Pubkey::from_slice(&owner_kp.x_only_public_key().0.serialize());
+
+        let instruction = token_instruction(
+            "burn",
+            apl_token::instruction::burn(&apl_token::id(), account, mint, &owner, &[], amount),
+        )?;
+        self.send_token_instructions(&[instruction], vec![owner_kp], "burn")
+            .await
+    }
+
+    /// Read and unpack the token account `pubkey`, checking it's owned by the token program
+    pub async fn read_token_account(&self, pubkey: &Pubkey) -> Result<apl_token::state::Account> {
+        let account = self
+            .read_account_info(*pubkey)
+            .await
+            .with_context(|| format!("Failed to read account {}", pubkey))?;
+
+        unpack_token_account(pubkey, &account)
+    }
+
+    /// Read and unpack the mint `pubkey`, checking it's owned by the token program
+    pub async fn read_mint(&self, pubkey: &Pubkey) -> Result<apl_token::state::Mint> {
+        let account = self
+            .read_account_info(*pubkey)
+            .await
+            .with_context(|| format!("Failed to read account {}", pubkey))?;
+        ensure_token_program_account(pubkey, &account)?;
+
+        apl_token::state::Mint::unpack(&account.data)
+            .map_err(|e| anyhow!("Account {} isn't a mint: {:?}", pubkey, e))
+    }
+
+    /// Send `instructions`, failing with `action` in the message if the transaction does
+    pub(crate) async fn send_token_instructions(
+        &self,
+        instructions: &[Instruction],
+        signers: Vec<Keypair>,
+        action: &str,
+    ) -> Result<()> {
+        let processed_tx = self
+            .send_instructions(instructions, signers)
+            .await
+            .with_context(|| format!("Failed to {}", action))?;
+
+        match processed_tx.failure() {
+            Some(failure) => Err(anyhow!("Failed to {}: {}", action, failure)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// `account` (read from `pubkey`) as a token account, checking it's owned by the token program
+pub(crate) fn unpack_token_account(
+    pubkey: &Pubkey,
+    account: &AccountInfo,
+) -> Result<apl_token::state::Account> {
+    ensure_token_program_account(pubkey, account)?;
+
+    apl_token::state::Account::unpack(&account.data)
+        .map_err(|e| anyhow!("Account {} isn't a token account: {:?}", pubkey, e))
+}
+
+fn ensure_token_program_account(pubkey: &Pubkey, account: &AccountInfo) -> Result<()> {
+    ensure!(
+        account.owner == apl_token::id(),
+        "Account {} is owned by {}, not the token program",
+        pubkey,
+        account.owner
+    );
+    Ok(())
+}
+
+/// The instruction the token program's builder made, or which one it refused to build
+pub(crate) fn token_instruction(
+    name: &str,
+    instruction: Result<Instruction, ProgramError>,
+) -> Result<Instruction> {
+    instruction.map_err(|e| anyhow!("Failed to build token {} instruction: {:?}", name, e))
+}
@@ -0,0 +1,211 @@
+//! Higher-level transaction submission.
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use anyhow::{ensure, Result};
+use arch_program::{instruction::Instruction, pubkey::Pubkey};
+use arch_sdk::{ProcessedTransaction, RuntimeTransaction, Status};
+use bitcoin::key::Keypair;
+use futures::{stream, StreamExt};
+
+use crate::{error::ArchTestingResult, TestContext};
+
+/// Outcome of one transaction of `send_transactions_batch`
+#[derive(Debug)]
+pub struct BatchTransactionResult {
+    /// `None` if the transaction couldn't be sent
+    pub txid: Option<String>,
+    /// The processed transaction (which may have failed on-chain), or why it wasn't processed
+    pub result: ArchTestingResult<ProcessedTransaction>,
+    /// From sending to processed (or the error)
+    pub latency: Duration,
+}
+
+impl BatchTransactionResult {
+    /// Sent, processed, and didn't fail
+    pub fn is_success(&self) -> bool {
+        matches!(
+            &self.result,
+            Ok(processed_tx) if !matches!(processed_tx.status, Status::Failed(_))
+        )
+    }
+}
+
+/// Aggregate timing of a `send_transactions_batch` run
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchStats {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Wall clock time for the whole batch
+    pub elapsed: Duration,
+    pub min_latency: Duration,
+    pub mean_latency: Duration,
+    pub max_latency: Duration,
+    /// Successful transactions per second of `elapsed`
+    pub throughput: f64,
+}
+
+impl fmt::Display for BatchStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} succeeded in {:?} ({:.1} tx/s), latency min {:?} / mean {:?} / max {:?}",
+            self.succeeded,
+            self.total,
+            self.elapsed,
+            self.throughput,
+            self.min_latency,
+            self.mean_latency,
+            self.max_latency
+        )
+    }
+}
+
+/// Per-transaction results of `send_transactions_batch`, in submission order, plus their stats
+#[derive(Debug)]
+pub struct BatchResult {
+    pub results: Vec<BatchTransactionResult>,
+    pub stats: BatchStats,
+}
+
+impl BatchResult {
+    fn new(results: Vec<BatchTransactionResult>, elapsed: Duration) -> Self {
+        let latencies = results.iter().map(|result| result.latency);
+        let succeeded = results.iter().filter(|result| result.is_success()).count();
+
+        let stats = BatchStats {
+            total: results.len(),
+            succeeded,
+            failed: results.len() - succeeded,
+            elapsed,
+            min_latency: latencies.clone().min().unwrap_or_default(),
+            mean_latency: latencies.clone().sum::<Duration>() / results.len().max(1) as u32,
+            max_latency: latencies.max().unwrap_or_default(),
+            throughput: succeeded as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        };
+
+        Self { results, stats }
+    }
+
+    /// Fail with every unsuccessful transaction's error or status
+    pub fn assert_all_succeeded(&self) -> Result<()> {
+        let failures = self
+            .results
+            .iter()
+            .enumerate()
+            .filter(|(_, result)| !result.is_success())
+            .map(|(i, result)| match &result.result {
+                Ok(processed_tx) => format!(
+                    "#{} ({}): {:?}",
+                    i,
+                    result.txid.as_deref().unwrap_or_default(),
+                    processed_tx.status
+                ),
+                Err(e) => format!("#{}: {:#}", i, e),
+            })
+            .collect::<Vec<_>>();
+
+        ensure!(
+            failures.is_empty(),
+            "{} of {} transactions failed:\n  {}",
+            failures.len(),
+            self.stats.total,
+            failures.join("\n  ")
+        );
+
+        Ok(())
+    }
+}
+
+/// Whether an error (from sending, or a failed status) means the transaction's recent blockhash
+/// was too old
+fn is_blockhash_expired(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("blockhash")
+        && ["expired", "not found", "too old", "invalid"]
+            .iter()
+            .any(|reason| message.contains(reason))
+}
+
+impl TestContext {
+    /// Build a transaction from `instructions` with a fresh blockhash, sign it with `signers`
+    /// (the first one pays), send it and wait for it to be processed.
+    ///
+    /// If the blockhash expires before the validator gets to it, the transaction is rebuilt with a
+    /// new blockhash and sent once more.
+    pub async fn send_instructions(
+        &self,
+        instructions: &[Instruction],
+        signers: Vec<Keypair>,
+    ) -> Result<ProcessedTransaction> {
+        match self
+            .try_send_instructions(instructions, signers.clone())
+            .await
+        {
+            Ok(processed_tx) => match &processed_tx.status {
+                Status::Failed(message) if is_blockhash_expired(message) => {
+                    tracing::debug!("Blockhash expired ({}), retrying", message);
+                }
+                _ => return Ok(processed_tx),
+            },
+            Err(e) if is_blockhash_expired(&e.to_string()) => {
+                tracing::debug!("Blockhash expired ({}), retrying", e);
+            }
+            Err(e) => return Err(e),
+        }
+
+        self.try_send_instructions(instructions, signers).await
+    }
+
+    async fn try_send_instructions(
+        &self,
+        instructions: &[Instruction],
+        signers: Vec<Keypair>,
+    ) -> Result<ProcessedTransaction> {
+        let payer = signers
+            .first()
+            .map(|payer| Pubkey::from_slice(&payer.x_only_public_key().0.serialize()));
+        let message = self.build_message(instructions, payer).await?;
+        let transaction = self.build_and_sign_transaction(message, signers).await?;
+        let txid = self.send_transaction(transaction).await?;
+
+        Ok(self.wait_for_transaction(&txid).await?)
+    }
+
+    /// Send `transactions`, at most `max_concurrency` in flight at once, and wait for each to be
+    /// processed. Failures don't stop the batch; they're reported per transaction.
+    pub async fn send_transactions_batch(
+        &self,
+        transactions: Vec<RuntimeTransaction>,
+        max_concurrency: usize,
+    ) -> BatchResult {
+        let started = Instant::now();
+
+        let results = stream::iter(transactions)
+            .map(|transaction| async move {
+                let sent = Instant::now();
+                let (txid, result) = match self.send_transaction(transaction).await {
+                    Ok(txid) => {
+                        let result = self.wait_for_transaction(&txid).await;
+                        (Some(txid), result)
+                    }
+                    Err(e) => (None, Err(e)),
+                };
+
+                BatchTransactionResult {
+                    txid,
+                    result,
+                    latency: sent.elapsed(),
+                }
+            })
+            .buffered(max_concurrency.max(1))
+            .collect()
+            .await;
+
+        BatchResult::new(results, started.elapsed())
+    }
+}
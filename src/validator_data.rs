@@ -0,0 +1,227 @@
+//! Inspecting, snapshotting and restoring the validator's on-disk state, when its data
+//! directory is bind-mounted (`TestRunnerConfig::validator_data_dir`).
+//!
+//! ```ignore
+//! let snapshot = ctx.snapshot_validator_state().await?;
+//! for scenario in scenarios {
+//!     run_scenario(&ctx, scenario).await?;
+//!     ctx.restore_validator_state(&snapshot).await?;
+//! }
+//! ```
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use tokio::task::spawn_blocking;
+
+use crate::{components::docker, Component, TestContext};
+
+/// Summary of a validator data directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidatorDataDirInfo {
+    pub path: PathBuf,
+    /// Total size of every file, recursively
+    pub size_bytes: u64,
+    pub file_count: usize,
+    /// Top-level entries, relative to `path`
+    pub entries: Vec<PathBuf>,
+    /// Whether any file or directory name mentions "ledger"
+    pub has_ledger: bool,
+    /// Whether any file or directory name mentions "snapshot"
+    pub has_snapshots: bool,
+}
+
+impl fmt::Display for ValidatorDataDirInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} bytes in {} files, ledger: {}, snapshots: {}, entries {:?}",
+            self.path.display(),
+            self.size_bytes,
+            self.file_count,
+            self.has_ledger,
+            self.has_snapshots,
+            self.entries
+        )
+    }
+}
+
+/// Walk the validator data directory at `path`
+pub fn inspect_validator_data_dir(path: impl AsRef<Path>) -> Result<ValidatorDataDirInfo> {
+    let path = path.as_ref();
+
+    let mut entries = std::fs::read_dir(path)
+        .with_context(|| format!("Failed to read validator data dir {}", path.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| PathBuf::from(entry.file_name())))
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    let mut info = ValidatorDataDirInfo {
+        path: path.to_path_buf(),
+        size_bytes: 0,
+        file_count: 0,
+        entries,
+        has_ledger: false,
+        has_snapshots: false,
+    };
+
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let dir_entries =
+            std::fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+
+        for entry in dir_entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            info.has_ledger |= name.contains("ledger");
+            info.has_snapshots |= name.contains("snapshot");
+
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else {
+                info.size_bytes += metadata.len();
+                info.file_count += 1;
+            }
+        }
+    }
+
+    Ok(info)
+}
+
+/// A copy of the validator's data directory, taken by `snapshot_validator_state`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidatorStateSnapshot {
+    /// Where the copy lives on the host
+    pub path: PathBuf,
+    /// Validator block count when the snapshot was taken
+    pub block_count: u64,
+}
+
+impl TestContext {
+    /// Host path of the validator's data directory, if it's bind-mounted
+    pub fn validator_data_dir(&self) -> Option<&Path> {
+        self.validator().config.data_dir.as_deref()
+    }
+
+    /// Size and contents of the validator's data directory (see `inspect_validator_data_dir`)
+    pub fn inspect_validator_data_dir(&self) -> Result<ValidatorDataDirInfo> {
+        inspect_validator_data_dir(self.require_validator_data_dir()?)
+    }
+
+    /// Copy the validator's data directory aside, stopping the validator while it's copied so
+    /// the copy is consistent. Only the validator's state is captured, not bitcoind's or Titan's.
+    pub async fn snapshot_validator_state(&mut self) -> Result<ValidatorStateSnapshot> {
+        let data_dir = self.require_validator_data_dir()?;
+        let block_count = self.arch_async_rpc_client.get_block_count().await?;
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos())
+            .unwrap_or_default();
+        let path = std::env::temp_dir().join(format!("arch-testing-validator-state-{:x}", nanos));
+
+        let snapshot_path = path.clone();
+        self.with_validator_stopped(move || copy_dir_all(&data_dir, &snapshot_path))
+            .await
+            .context("Failed to snapshot validator state")?;
+
+        tracing::info!(
+            "Snapshotted validator state at block {} into {}",
+            block_count,
+            path.display()
+        );
+
+        Ok(ValidatorStateSnapshot { path, block_count })
+    }
+
+    /// Replace the validator's data directory with `snapshot` and restart it from there
+    pub async fn restore_validator_state(
+        &mut self,
+        snapshot: &ValidatorStateSnapshot,
+    ) -> Result<()> {
+        let data_dir = self.require_validator_data_dir()?;
+        let snapshot_path = snapshot.path.clone();
+
+        self.with_validator_stopped(move || {
+            // the directory itself is the bind mount, so only its contents are replaced
+            for entry in std::fs::read_dir(&data_dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    std::fs::remove_dir_all(&path)?;
+                } else {
+                    std::fs::remove_file(&path)?;
+                }
+            }
+            copy_dir_all(&snapshot_path, &data_dir)
+        })
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to restore validator state from {}",
+                snapshot.path.display()
+            )
+        })?;
+
+        tracing::info!(
+            "Restored validator state from {} (block {})",
+            snapshot.path.display(),
+            snapshot.block_count
+        );
+
+        Ok(())
+    }
+
+    fn require_validator_data_dir(&self) -> Result<PathBuf> {
+        self.validator_data_dir()
+            .map(Path::to_path_buf)
+            .ok_or_else(|| {
+                anyhow!(
+                    "The validator data dir isn't mounted (set TestRunnerConfig::validator_data_dir)"
+                )
+            })
+    }
+
+    /// Stop the validator, run `f`, then start it again (whether or not `f` failed) and wait
+    /// until it answers, with fresh clients like `restart_validator`
+    async fn with_validator_stopped<F>(&mut self, f: F) -> Result<()>
+    where
+        F: FnOnce() -> Result<()> + Send + 'static,
+    {
+        let container_name = self.container_name(Component::Validator);
+
+        docker(vec!["stop".into(), container_name.clone()]).await?;
+        let result = spawn_blocking(f)
+            .await
+            .context("Failed to spawn blocking task")?;
+        docker(vec!["start".into(), container_name]).await?;
+
+        self.rebuild_validator_clients()?;
+        self.wait_for_component_ready(Component::Validator).await?;
+
+        result
+    }
+}
+
+fn copy_dir_all(from: &Path, to: &Path) -> Result<()> {
+    std::fs::create_dir_all(to).with_context(|| format!("Failed to create {}", to.display()))?;
+
+    for entry in
+        std::fs::read_dir(from).with_context(|| format!("Failed to read {}", from.display()))?
+    {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.metadata()?.is_dir() {
+            copy_dir_all(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)
+                .with_context(|| format!("Failed to copy {}", entry.path().display()))?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,319 @@
+//! Client for the validator's websocket event subscriptions.
+//!
+//! The validator pushes `{"topic": .., "data": ..}` JSON events for each subscription. Events
+//! are kept as raw JSON, like Titan's, so this keeps working as the validator adds topics or
+//! fields.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use arch_program::pubkey::Pubkey;
+use futures::{SinkExt, Stream, StreamExt};
+use tokio::{sync::broadcast, task::JoinHandle};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::Instrument;
+
+use crate::TestContext;
+
+pub const DEFAULT_VALIDATOR_EVENT_TIMEOUT: Duration = Duration::from_secs(30);
+const VALIDATOR_EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+pub const ACCOUNT_UPDATE_TOPIC: &str = "account_update";
+pub const BLOCK_TOPIC: &str = "block";
+pub const TRANSACTION_TOPIC: &str = "transaction";
+
+/// Topics to subscribe to when the caller doesn't care
+pub const ALL_VALIDATOR_TOPICS: &[&str] = &[BLOCK_TOPIC, TRANSACTION_TOPIC, ACCOUNT_UPDATE_TOPIC];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidatorEvent(pub serde_json::Value);
+
+impl ValidatorEvent {
+    pub fn topic(&self) -> Option<&str> {
+        self.0.get("topic").and_then(|topic| topic.as_str())
+    }
+
+    /// The event payload
+    pub fn data(&self) -> &serde_json::Value {
+        &self.0["data"]
+    }
+
+    /// Program log lines carried by a transaction event (empty for other events)
+    pub fn logs(&self) -> Vec<String> {
+        self.data()["logs"]
+            .as_array()
+            .map(|logs| {
+                logs.iter()
+                    .filter_map(|line| line.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Whether `needle` (a hex pubkey, txid, ...) appears anywhere in the event
+    pub fn mentions(&self, needle: &str) -> bool {
+        self.0.to_string().contains(needle)
+    }
+}
+
+/// A live subscription; the listener task stops (and the websocket closes) when this is dropped
+pub struct ValidatorSubscription {
+    receiver: broadcast::Receiver<ValidatorEvent>,
+    listener: JoinHandle<()>,
+}
+
+impl ValidatorSubscription {
+    /// Subscribe to `topic` events matching `filter` (the validator's filter object, `{}` for
+    /// every event)
+    pub async fn connect(
+        websocket_url: &str,
+        topic: &str,
+        filter: serde_json::Value,
+    ) -> Result<Self> {
+        Self::connect_topics(websocket_url, &[(topic, filter)]).await
+    }
+
+    /// Subscribe to every event of every topic in `ALL_VALIDATOR_TOPICS`
+    pub async fn connect_all(websocket_url: &str) -> Result<Self> {
+        let topics = ALL_VALIDATOR_TOPICS
+            .iter()
+            .map(|topic| (*topic, serde_json::json!({})))
+            .collect::<Vec<_>>();
+
+        Self::connect_topics(websocket_url, &topics).await
+    }
+
+    /// One subscription over several `(topic, filter)` pairs, sharing a websocket, so events
+    /// of all of them arrive in the order the validator sent them
+    pub async fn connect_topics(
+        websocket_url: &str,
+        topics: &[(&str, serde_json::Value)],
+    ) -> Result<Self> {
+        let (mut socket, _) = connect_async(websocket_url)
+            .await
+            .with_context(|| format!("Failed to connect to validator at {}", websocket_url))?;
+
+        for (topic, filter) in topics {
+            let request = serde_json::json!({
+                "method": "subscribe",
+                "params": {
+                    "topic": topic,
+                    "filter": filter,
+                    "request_id": "arch-testing",
+                },
+            });
+            socket
+                .send(Message::text(request.to_string()))
+                .await
+                .with_context(|| format!("Failed to send validator {} subscription", topic))?;
+        }
+
+        // the listener owns the only sender, so receivers see the channel close when it ends
+        let (sender, receiver) = broadcast::channel(VALIDATOR_EVENT_CHANNEL_CAPACITY);
+
+        let listener = tokio::spawn(
+            async move {
+                while let Some(message) = socket.next().await {
+                    let text = match message {
+                        Ok(Message::Text(text)) => text,
+                        Ok(Message::Close(_)) => {
+                            tracing::debug!("Validator closed the subscription");
+                            break;
+                        }
+                        Ok(_) => continue,
+                        Err(e) => {
+                            tracing::warn!("Validator subscription failed: {}", e);
+                            break;
+                        }
+                    };
+
+                    match serde_json::from_str::<serde_json::Value>(&text) {
+                        // subscription acks and errors carry no data
+                        Ok(event) if event.get("data").is_some() => {
+                            let _ = sender.send(ValidatorEvent(event));
+                        }
+                        Ok(other) => tracing::debug!("Validator subscription message: {}", other),
+                        Err(e) => tracing::debug!("Ignoring validator message {:?}: {}", text, e),
+                    }
+                }
+            }
+            .instrument(tracing::Span::current()),
+        );
+
+        Ok(Self { receiver, listener })
+    }
+
+    /// A subscription that has already ended, for runs without a validator (replayed RPC
+    /// cassettes)
+    pub(crate) fn offline() -> Self {
+        let (_, receiver) = broadcast::channel(1);
+
+        Self {
+            receiver,
+            listener: tokio::spawn(async {}),
+        }
+    }
+
+    /// Another receiver over the same stream, starting from now
+    pub fn resubscribe(&self) -> broadcast::Receiver<ValidatorEvent> {
+        self.receiver.resubscribe()
+    }
+
+    /// The next event; errors once the stream has ended
+    pub async fn recv(&mut self) -> Result<ValidatorEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => return Ok(event),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Validator subscription lagged, skipped {} events", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    return Err(anyhow!("Validator subscription closed"));
+                }
+            }
+        }
+    }
+
+    /// The next event satisfying `predicate`, within `timeout`
+    pub async fn wait_for<P>(&mut self, predicate: P, timeout: Duration) -> Result<ValidatorEvent>
+    where
+        P: Fn(&ValidatorEvent) -> bool,
+    {
+        tokio::time::timeout(timeout, async {
+            loop {
+                let event = self.recv().await?;
+                if predicate(&event) {
+                    return Ok(event);
+                }
+            }
+        })
+        .await
+        .context("Timed out waiting for validator event")?
+    }
+
+    /// The next `count` events, within `timeout` overall (e.g. to measure block cadence)
+    pub async fn next_events(
+        &mut self,
+        count: usize,
+        timeout: Duration,
+    ) -> Result<Vec<ValidatorEvent>> {
+        tokio::time::timeout(timeout, async {
+            let mut events = Vec::with_capacity(count);
+            while events.len() < count {
+                events.push(self.recv().await?);
+            }
+            Ok(events)
+        })
+        .await
+        .with_context(|| format!("Timed out waiting for {} validator events", count))?
+    }
+
+    /// The events as a `Stream`, ending when the subscription does
+    pub fn into_stream(self) -> impl Stream<Item = ValidatorEvent> {
+        futures::stream::unfold(self, |mut subscription| async move {
+            let event = subscription.recv().await.ok()?;
+            Some((event, subscription))
+        })
+    }
+}
+
+impl Drop for ValidatorSubscription {
+    fn drop(&mut self) {
+        self.listener.abort();
+    }
+}
+
+impl TestContext {
+    /// Subscribe to `topic` events matching `filter` on the validator websocket
+    pub async fn validator_subscribe(
+        &self,
+        topic: &str,
+        filter: serde_json::Value,
+    ) -> Result<ValidatorSubscription> {
+        ValidatorSubscription::connect(&self.validator().websocket_url(), topic, filter).await
+    }
+
+    /// Subscribe to every event of every topic in `ALL_VALIDATOR_TOPICS`, unfiltered
+    pub async fn subscribe_all_events(&self) -> Result<ValidatorSubscription> {
+        ValidatorSubscription::connect_all(&self.validator().websocket_url()).await
+    }
+
+    /// Wait for the first event of any topic satisfying `predicate`, e.g.
+    /// `ctx.wait_for_event(|event| event.topic() == Some(BLOCK_TOPIC), timeout)`
+    pub async fn wait_for_event<P>(&self, predicate: P, timeout: Duration) -> Result<ValidatorEvent>
+    where
+        P: Fn(&ValidatorEvent) -> bool,
+    {
+        self.subscribe_all_events()
+            .await?
+            .wait_for(predicate, timeout)
+            .await
+    }
+
+    /// Subscribe to every update of `pubkey`'s account
+    pub async fn subscribe_account(&self, pubkey: Pubkey) -> Result<ValidatorSubscription> {
+        self.validator_subscribe(
+            ACCOUNT_UPDATE_TOPIC,
+            serde_json::json!({ "address": hex::encode(pubkey.0) }),
+        )
+        .await
+    }
+
+    /// Subscribe to every block the validator produces, e.g. to assert on block cadence or
+    /// that a transaction landed within a few blocks of being sent
+    pub async fn subscribe_blocks(&self) -> Result<ValidatorSubscription> {
+        self.validator_subscribe(BLOCK_TOPIC, serde_json::json!({}))
+            .await
+    }
+
+    /// Subscribe to the transactions (and so the logs) of every transaction invoking
+    /// `program_id`, the way an indexer or bot would
+    pub async fn subscribe_logs(&self, program_id: Pubkey) -> Result<ValidatorSubscription> {
+        self.validator_subscribe(
+            TRANSACTION_TOPIC,
+            serde_json::json!({ "program_ids": [hex::encode(program_id.0)] }),
+        )
+        .await
+    }
+
+    /// Subscribe to the status changes of transaction `txid`
+    pub async fn subscribe_transaction(&self, txid: &str) -> Result<ValidatorSubscription> {
+        self.validator_subscribe(TRANSACTION_TOPIC, serde_json::json!({ "hash": txid }))
+            .await
+    }
+
+    /// Wait until `pubkey`'s account satisfies `predicate`, re-reading it on every update.
+    ///
+    /// Subscribes before checking the current state, so an update in between isn't missed.
+    pub async fn wait_for_account_update<P>(
+        &self,
+        pubkey: Pubkey,
+        predicate: P,
+        timeout: Duration,
+    ) -> Result<arch_sdk::AccountInfo>
+    where
+        P: Fn(&arch_sdk::AccountInfo) -> bool,
+    {
+        let mut subscription = self.subscribe_account(pubkey).await?;
+
+        tokio::time::timeout(timeout, async {
+            // the account may not exist yet, in which case the next update creates it
+            if let Ok(account) = self.read_account_info(pubkey).await {
+                if predicate(&account) {
+                    return Ok(account);
+                }
+            }
+
+            loop {
+                subscription.recv().await?;
+                if let Ok(account) = self.read_account_info(pubkey).await {
+                    if predicate(&account) {
+                        return Ok(account);
+                    }
+                }
+            }
+        })
+        .await
+        .with_context(|| format!("Timed out waiting for an update of account {}", pubkey))?
+    }
+}